@@ -0,0 +1,53 @@
+// miniquad plugin for what the game needs from the browser and macroquad doesn't give:
+// somewhere to keep saves between visits and whether the page has focus.
+// index.html loads it after mq_js_bundle.js and before the wasm file.
+miniquad_add_plugin({
+    name: "power_crisis",
+    version: "0.1.0",
+    register_plugin: function (importObject) {
+        // every key is kept under the game's name so other pages on the host don't clash
+        function storage_key(ptr, len) {
+            var bytes = new Uint8Array(wasm_memory.buffer, ptr, len);
+            return "power_crisis." + new TextDecoder().decode(bytes);
+        }
+
+        // localStorage throws when it's turned off, the game then plays without saves
+        function get_item(key) {
+            try {
+                return window.localStorage.getItem(key);
+            } catch (e) {
+                return null;
+            }
+        }
+
+        importObject.env.power_crisis_storage_length = function (key_ptr, key_len) {
+            var value = get_item(storage_key(key_ptr, key_len));
+            if (value === null) {
+                return -1;
+            }
+            return new TextEncoder().encode(value).length;
+        };
+
+        importObject.env.power_crisis_storage_load = function (key_ptr, key_len, buffer_ptr, buffer_len) {
+            var value = get_item(storage_key(key_ptr, key_len));
+            if (value === null) {
+                return;
+            }
+            var encoded = new TextEncoder().encode(value).subarray(0, buffer_len);
+            new Uint8Array(wasm_memory.buffer, buffer_ptr, buffer_len).set(encoded);
+        };
+
+        importObject.env.power_crisis_storage_save = function (key_ptr, key_len, value_ptr, value_len) {
+            var value = new TextDecoder().decode(new Uint8Array(wasm_memory.buffer, value_ptr, value_len));
+            try {
+                window.localStorage.setItem(storage_key(key_ptr, key_len), value);
+            } catch (e) {
+                console.warn("Couldn't save " + storage_key(key_ptr, key_len) + ": " + e);
+            }
+        };
+
+        importObject.env.power_crisis_has_focus = function () {
+            return document.hasFocus() && !document.hidden ? 1 : 0;
+        };
+    },
+});
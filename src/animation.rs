@@ -0,0 +1,233 @@
+use macroquad::prelude::*;
+
+use crate::PIXELS_PER_UNIT;
+
+/// Something that should happen when an animation reaches a certain frame.
+#[derive(Clone, Copy, PartialEq)]
+pub enum AnimationEvent {
+    Footstep,
+    Spark,
+}
+
+/// All frames of a sprite packed side by side into one texture.
+pub struct SpriteSheet {
+    texture: Texture2D,
+    frame_width: f32,
+    frame_height: f32,
+}
+impl SpriteSheet {
+    /// Packs the frames into a single row, every frame gets a cell the size of the largest one.
+    pub fn from_frames(frames: &[Image]) -> Self {
+        let frame_width = frames.iter().map(|frame| frame.width).max().unwrap_or(1);
+        let frame_height = frames.iter().map(|frame| frame.height).max().unwrap_or(1);
+
+        let mut sheet = Image::gen_image_color(
+            frame_width * frames.len().max(1) as u16,
+            frame_height,
+            Color::new(0.0, 0.0, 0.0, 0.0),
+        );
+        for (i, frame) in frames.iter().enumerate() {
+            let offset = i as u32 * frame_width as u32;
+            for y in 0..frame.height as u32 {
+                for x in 0..frame.width as u32 {
+                    sheet.set_pixel(offset + x, y, frame.get_pixel(x, y));
+                }
+            }
+        }
+
+        let texture = Texture2D::from_image(&sheet);
+        texture.set_filter(FilterMode::Nearest);
+        Self {
+            texture,
+            frame_width: frame_width as f32,
+            frame_height: frame_height as f32,
+        }
+    }
+
+    /// Gives the size of one frame in world units.
+    pub fn frame_size(&self) -> Vec2 {
+        vec2(self.frame_width, self.frame_height) / PIXELS_PER_UNIT
+    }
+
+    /// Draws a frame in world space with its bottom left corner at `position`.
+    pub fn draw(&self, frame: usize, position: Vec2, flip_x: bool, colour: Color) {
+        let draw_param = DrawTextureParams {
+            dest_size: Some(self.frame_size()),
+            source: Some(Rect::new(
+                frame as f32 * self.frame_width,
+                0.0,
+                self.frame_width,
+                self.frame_height,
+            )),
+            flip_x,
+            flip_y: true,
+            ..DrawTextureParams::default()
+        };
+        draw_texture_ex(self.texture, position.x, position.y, colour, draw_param);
+    }
+
+    /// Draws a frame in screen space at its pixel size times `scale`.
+    pub fn draw_screen(&self, frame: usize, position: Vec2, scale: f32) {
+        let draw_param = DrawTextureParams {
+            dest_size: Some(vec2(self.frame_width, self.frame_height) * scale),
+            source: Some(Rect::new(
+                frame as f32 * self.frame_width,
+                0.0,
+                self.frame_width,
+                self.frame_height,
+            )),
+            ..DrawTextureParams::default()
+        };
+        draw_texture_ex(self.texture, position.x, position.y, WHITE, draw_param);
+    }
+}
+
+/// A named run of frames from a sprite sheet.
+pub struct Clip {
+    pub name: &'static str,
+    /// Indices into the sprite sheet, played in order.
+    pub frames: Vec<usize>,
+    pub frames_per_second: f32,
+    /// Clips that don't loop hold their last frame once they are done.
+    pub looping: bool,
+    /// Events fired when the clip enters the frame at that position in `frames`.
+    pub events: Vec<(usize, AnimationEvent)>,
+}
+
+/// A sprite sheet and every clip that can be played from it.
+pub struct AnimationSet {
+    pub sheet: SpriteSheet,
+    clips: Vec<Clip>,
+}
+impl AnimationSet {
+    pub fn new(sheet: SpriteSheet, clips: Vec<Clip>) -> Self {
+        Self { sheet, clips }
+    }
+
+    /// Panics if there is no clip called `name`, a typo there is a bug.
+    fn clip(&self, name: &str) -> &Clip {
+        self.clips
+            .iter()
+            .find(|clip| clip.name == name)
+            .unwrap_or_else(|| panic!("no animation clip named {}", name))
+    }
+}
+
+/// Which clip an entity is playing and how far into it it is.
+///
+/// `set` switches freely between looping clips as the entity's state changes,
+/// while `play_once` starts a clip that can't be interrupted until it ends.
+pub struct Animator {
+    clip: &'static str,
+    /// The clip to go back to once a `play_once` clip has ended.
+    next: &'static str,
+    position: usize,
+    time: f32,
+    locked: bool,
+}
+impl Animator {
+    pub fn new(clip: &'static str) -> Self {
+        Self {
+            clip,
+            next: clip,
+            position: 0,
+            time: 0.0,
+            locked: false,
+        }
+    }
+
+    /// Switches to `clip` unless it is already playing or a one shot clip hasn't ended yet.
+    pub fn set(&mut self, clip: &'static str) {
+        self.next = clip;
+        if !self.locked && self.clip != clip {
+            self.start(clip);
+        }
+    }
+
+    /// Plays `clip` from the start, ignoring `set` until it has ended.
+    pub fn play_once(&mut self, clip: &'static str) {
+        self.start(clip);
+        self.locked = true;
+    }
+
+    fn start(&mut self, clip: &'static str) {
+        self.clip = clip;
+        self.position = 0;
+        self.time = 0.0;
+    }
+
+    /// Advances the clip, pushing the events of every frame entered onto `events`.
+    pub fn update(&mut self, set: &AnimationSet, delta: f32, events: &mut Vec<AnimationEvent>) {
+        let clip = set.clip(self.clip);
+        if clip.frames_per_second <= 0.0 || clip.frames.len() < 2 {
+            return;
+        }
+
+        self.time += delta;
+        let frame_time = 1.0 / clip.frames_per_second;
+        while self.time >= frame_time {
+            self.time -= frame_time;
+
+            if self.position + 1 < clip.frames.len() {
+                self.position += 1;
+            } else if self.locked {
+                // the one shot is done, fall back to whatever the entity is doing now
+                self.locked = false;
+                let next = self.next;
+                self.start(next);
+                return;
+            } else if clip.looping {
+                self.position = 0;
+            } else {
+                return;
+            }
+
+            for (position, event) in &clip.events {
+                if *position == self.position {
+                    events.push(*event);
+                }
+            }
+        }
+    }
+
+    /// Gives the sprite sheet frame to draw.
+    pub fn frame(&self, set: &AnimationSet) -> usize {
+        let clip = set.clip(self.clip);
+        clip.frames[self.position.min(clip.frames.len() - 1)]
+    }
+}
+
+/// Gives a copy of `image` blended towards `colour` by `amount`, keeping its transparency.
+pub fn tinted(image: &Image, colour: Color, amount: f32) -> Image {
+    let mut tinted = image.clone();
+    for y in 0..image.height as u32 {
+        for x in 0..image.width as u32 {
+            let pixel = image.get_pixel(x, y);
+            tinted.set_pixel(
+                x,
+                y,
+                Color::new(
+                    pixel.r + (colour.r - pixel.r) * amount,
+                    pixel.g + (colour.g - pixel.g) * amount,
+                    pixel.b + (colour.b - pixel.b) * amount,
+                    pixel.a,
+                ),
+            );
+        }
+    }
+    tinted
+}
+
+/// Gives a copy of `image` moved `dx` pixels sideways, for cheap shaking frames.
+pub fn shifted(image: &Image, dx: i32) -> Image {
+    let mut shifted = Image::gen_image_color(image.width, image.height, Color::new(0.0, 0.0, 0.0, 0.0));
+    for y in 0..image.height as u32 {
+        for x in 0..image.width as i32 {
+            let from = x - dx;
+            if from >= 0 && from < image.width as i32 {
+                shifted.set_pixel(x as u32, y, image.get_pixel(from as u32, y));
+            }
+        }
+    }
+    shifted
+}
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+use macroquad::{
+    file::load_file,
+    prelude::*,
+};
+
+use crate::audio::{PannedSound, SoundEffect};
+
+/// Lists every file the game loads, by the key the code looks it up with.
+pub const MANIFEST_PATH: &str = "assets/manifest.json";
+
+/// Every texture, atlas and sound named in the manifest.
+///
+/// Atlases are lists of frames that are kept as images so they can be packed
+/// into sprite sheets, textures and sounds are ready to use.
+pub struct AssetStore {
+    textures: HashMap<String, Texture2D>,
+    atlases: HashMap<String, Vec<Image>>,
+    sounds: HashMap<String, PannedSound>,
+}
+impl AssetStore {
+    /// Loads everything in the manifest, drawing a loading bar between files.
+    ///
+    /// Nothing stops at the first bad file, every missing or broken one is
+    /// returned together so they can all be fixed at once.
+    pub async fn load(manifest_path: &str) -> Result<Self, Vec<String>> {
+        let manifest = load_file(manifest_path)
+            .await
+            .map_err(|e| vec![format!("{}: {}", manifest_path, e)])?;
+        let manifest: serde_json::Value = serde_json::from_slice(&manifest)
+            .map_err(|e| vec![format!("{}: {}", manifest_path, e)])?;
+
+        let mut errors = Vec::new();
+        let textures = entries(&manifest, "textures", manifest_path, &mut errors);
+        let sounds = sound_entries(&manifest, manifest_path, &mut errors);
+        let mut atlases = Vec::new();
+        if let Some(list) = manifest["atlases"].as_object() {
+            for (key, frames) in list {
+                match frames.as_array() {
+                    Some(frames) => atlases.push((
+                        key.clone(),
+                        frames
+                            .iter()
+                            .filter_map(|frame| frame.as_str().map(String::from))
+                            .collect::<Vec<_>>(),
+                    )),
+                    None => errors.push(format!("{}: atlas {} is not a list of files", manifest_path, key)),
+                }
+            }
+        }
+
+        let total = textures.len() + sounds.len() + atlases.iter().map(|(_, f)| f.len()).sum::<usize>();
+        let mut loaded = 0;
+
+        let mut store = Self {
+            textures: HashMap::new(),
+            atlases: HashMap::new(),
+            sounds: HashMap::new(),
+        };
+
+        for (key, path) in textures {
+            draw_loading_screen(loaded, total, &path).await;
+            match load_image(&path).await {
+                Ok(image) => {
+                    let texture = Texture2D::from_image(&image);
+                    texture.set_filter(FilterMode::Nearest);
+                    store.textures.insert(key, texture);
+                }
+                Err(e) => errors.push(e),
+            }
+            loaded += 1;
+        }
+
+        for (key, paths) in atlases {
+            let mut frames = Vec::new();
+            for path in paths {
+                draw_loading_screen(loaded, total, &path).await;
+                match load_image(&path).await {
+                    Ok(image) => frames.push(image),
+                    Err(e) => errors.push(e),
+                }
+                loaded += 1;
+            }
+            store.atlases.insert(key, frames);
+        }
+
+        for (key, path, effect) in sounds {
+            draw_loading_screen(loaded, total, &path).await;
+            match load_sound(&key, &path, effect).await {
+                Ok(sound) => {
+                    store.sounds.insert(key, sound);
+                }
+                Err(e) => errors.push(e),
+            }
+            loaded += 1;
+        }
+
+        if errors.is_empty() {
+            Ok(store)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Gives a problem for every key the game needs that the manifest doesn't have,
+    /// and for every atlas with fewer frames than the game uses.
+    pub fn missing(&self, textures: &[&str], atlases: &[(&str, usize)], sounds: &[&str]) -> Vec<String> {
+        let mut missing = Vec::new();
+        for key in textures {
+            if !self.textures.contains_key(*key) {
+                missing.push(format!("{}: no texture called {}", MANIFEST_PATH, key));
+            }
+        }
+        for (key, frames) in atlases {
+            match self.atlases.get(*key) {
+                Some(found) if found.len() >= *frames => {}
+                Some(found) => missing.push(format!(
+                    "{}: atlas {} needs {} frames but has {}",
+                    MANIFEST_PATH,
+                    key,
+                    frames,
+                    found.len()
+                )),
+                None => missing.push(format!("{}: no atlas called {}", MANIFEST_PATH, key)),
+            }
+        }
+        for key in sounds {
+            if !self.sounds.contains_key(*key) {
+                missing.push(format!("{}: no sound called {}", MANIFEST_PATH, key));
+            }
+        }
+        missing
+    }
+
+    /// Panics if the manifest has no such texture, check with `missing` first.
+    pub fn texture(&self, key: &str) -> Texture2D {
+        *self
+            .textures
+            .get(key)
+            .unwrap_or_else(|| panic!("no texture called {}", key))
+    }
+
+    /// Panics if the manifest has no such atlas, check with `missing` first.
+    pub fn frames(&self, key: &str) -> &[Image] {
+        self.atlases
+            .get(key)
+            .unwrap_or_else(|| panic!("no atlas called {}", key))
+    }
+
+    /// Panics if the manifest has no such sound, check with `missing` first.
+    pub fn sound(&self, key: &str) -> &PannedSound {
+        self.sounds
+            .get(key)
+            .unwrap_or_else(|| panic!("no sound called {}", key))
+    }
+}
+
+/// Reads a `{ key: path }` section of the manifest.
+fn entries(
+    manifest: &serde_json::Value,
+    section: &str,
+    manifest_path: &str,
+    errors: &mut Vec<String>,
+) -> Vec<(String, String)> {
+    let mut entries = Vec::new();
+    if let Some(list) = manifest[section].as_object() {
+        for (key, path) in list {
+            match path.as_str() {
+                Some(path) => entries.push((key.clone(), path.to_string())),
+                None => errors.push(format!("{}: {} {} is not a file path", manifest_path, section, key)),
+            }
+        }
+    }
+    entries
+}
+
+/// Reads the sounds section of the manifest, where each sound is either a path
+/// or `{ "file": path, "effect": name }` to make a variant of another file.
+fn sound_entries(
+    manifest: &serde_json::Value,
+    manifest_path: &str,
+    errors: &mut Vec<String>,
+) -> Vec<(String, String, SoundEffect)> {
+    let mut entries = Vec::new();
+    if let Some(list) = manifest["sounds"].as_object() {
+        for (key, sound) in list {
+            let path = sound.as_str().or_else(|| sound["file"].as_str());
+            let effect = match sound["effect"].as_str() {
+                Some(name) => SoundEffect::from_name(name),
+                None => Some(SoundEffect::None),
+            };
+            match (path, effect) {
+                (Some(path), Some(effect)) => entries.push((key.clone(), path.to_string(), effect)),
+                (None, _) => errors.push(format!("{}: sounds {} has no file path", manifest_path, key)),
+                (_, None) => errors.push(format!("{}: sounds {} has an unknown effect", manifest_path, key)),
+            }
+        }
+    }
+    entries
+}
+
+/// Loads and decodes an image, macroquad's own loader panics on broken files.
+async fn load_image(path: &str) -> Result<Image, String> {
+    let bytes = load_file(path).await.map_err(|e| format!("{}: {}", path, e))?;
+    let image = image::load_from_memory(&bytes)
+        .map_err(|e| format!("{}: {}", path, e))?
+        .to_rgba8();
+    Ok(Image {
+        width: image.width() as u16,
+        height: image.height() as u16,
+        bytes: image.into_raw(),
+    })
+}
+
+/// Loads a sound, checking it looks like a wav or ogg file before handing it to the mixer.
+async fn load_sound(key: &str, path: &str, effect: SoundEffect) -> Result<PannedSound, String> {
+    let bytes = load_file(path).await.map_err(|e| format!("{}: {}", path, e))?;
+    let wav = bytes.len() > 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WAVE";
+    let ogg = bytes.starts_with(b"OggS");
+    if !wav && !ogg {
+        return Err(format!("{}: not a wav or ogg file", path));
+    }
+    PannedSound::load(key, &bytes, effect)
+        .await
+        .map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Shows how far along loading is, mostly seen on the web where files are downloaded.
+async fn draw_loading_screen(loaded: usize, total: usize, path: &str) {
+    set_default_camera();
+    clear_background(BLACK);
+
+    let width = screen_width() * 0.6;
+    let x = (screen_width() - width) / 2.0;
+    let y = screen_height() / 2.0;
+    let progress = loaded as f32 / total.max(1) as f32;
+
+    draw_text("Loading...", x, y - 20.0, 30.0, WHITE);
+    draw_rectangle(x, y, width, 20.0, DARKGRAY);
+    draw_rectangle(x, y, width * progress, 20.0, YELLOW);
+    draw_text(path, x, y + 45.0, 20.0, GRAY);
+
+    next_frame().await;
+}
+
+/// Lists every asset problem until the player closes the game.
+pub async fn show_asset_errors(errors: &[String]) {
+    for error in errors {
+        eprintln!("{}", error);
+    }
+
+    loop {
+        set_default_camera();
+        clear_background(BLACK);
+        draw_text("Some game files are missing or broken:", 20.0, 40.0, 30.0, RED);
+        for (i, error) in errors.iter().enumerate() {
+            draw_text(error, 20.0, 80.0 + i as f32 * 25.0, 20.0, WHITE);
+        }
+        next_frame().await;
+    }
+}
@@ -0,0 +1,287 @@
+use macroquad::{
+    audio::{load_sound_from_bytes, play_sound, PlaySoundParams, Sound},
+    math::Vec2,
+};
+
+/// Every sound is made at this many positions from hard left to hard right when
+/// it is loaded, macroquad can't pan a sound while it plays.
+const PAN_STEPS: usize = 5;
+
+/// Sounds closer than this play at full volume.
+const REFERENCE_DISTANCE: f32 = 2.0;
+
+/// How fast sounds get quieter past `REFERENCE_DISTANCE`.
+const ROLLOFF: f32 = 0.5;
+
+/// Sounds this far to the side of the listener are panned all the way.
+const PAN_DISTANCE: f32 = 12.0;
+
+/// The most copies of one sound that may play at once, more just get loud and muddy.
+const MAX_VOICES: usize = 4;
+
+/// How long sounds whose length can't be read are counted as playing.
+const UNKNOWN_DURATION: f32 = 1.0;
+
+/// Changes made to a sound as it is loaded, so variants don't need their own files.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SoundEffect {
+    None,
+    /// Slowed down and muffled, like thunder heard from far away.
+    Rumble,
+}
+impl SoundEffect {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(SoundEffect::None),
+            "rumble" => Some(SoundEffect::Rumble),
+            _ => None,
+        }
+    }
+}
+
+/// A sound loaded once per pan position.
+pub struct PannedSound {
+    name: String,
+    /// From hard left to hard right, just one if the file couldn't be panned.
+    variants: Vec<Sound>,
+    duration: f32,
+}
+impl PannedSound {
+    /// Loads a wav or ogg file, making panned copies of wav files.
+    ///
+    /// Effects can only be applied to wav files.
+    pub async fn load(name: &str, bytes: &[u8], effect: SoundEffect) -> Result<Self, String> {
+        let wav = match Wav::parse(bytes) {
+            Some(wav) => match effect {
+                SoundEffect::None => wav,
+                SoundEffect::Rumble => wav.rumble(),
+            },
+            None if effect != SoundEffect::None => {
+                return Err("effects only work on 8 or 16 bit wav files".to_string());
+            }
+            None => {
+                // not a wav we can read, play it as it is from the middle
+                let sound = load_sound_from_bytes(bytes).await.map_err(|e| e.to_string())?;
+                return Ok(Self {
+                    name: name.to_string(),
+                    variants: vec![sound],
+                    duration: UNKNOWN_DURATION,
+                });
+            }
+        };
+
+        let mut variants = Vec::with_capacity(PAN_STEPS);
+        for step in 0..PAN_STEPS {
+            let pan = step as f32 / (PAN_STEPS - 1) as f32 * 2.0 - 1.0;
+            let bytes = wav.to_stereo((1.0 - pan).min(1.0), (1.0 + pan).min(1.0));
+            variants.push(load_sound_from_bytes(&bytes).await.map_err(|e| e.to_string())?);
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            variants,
+            duration: wav.samples.len() as f32 / wav.sample_rate as f32,
+        })
+    }
+
+    /// Gives the copy for `pan` from -1.0 (left) to 1.0 (right).
+    fn variant(&self, pan: f32) -> Sound {
+        let step = ((pan.clamp(-1.0, 1.0) + 1.0) / 2.0 * (self.variants.len() - 1) as f32).round();
+        self.variants[step as usize]
+    }
+}
+
+/// The parts of a PCM wav file needed to remix it.
+struct Wav {
+    sample_rate: u32,
+    /// Mono samples from -1.0 to 1.0, stereo files are mixed down.
+    samples: Vec<f32>,
+}
+impl Wav {
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+            return None;
+        }
+        let u16_at = |i: usize| Some(u16::from_le_bytes(bytes.get(i..i + 2)?.try_into().ok()?));
+        let u32_at = |i: usize| Some(u32::from_le_bytes(bytes.get(i..i + 4)?.try_into().ok()?));
+
+        let mut format = None;
+        let mut data = None;
+        let mut i = 12;
+        while i + 8 <= bytes.len() {
+            let size = u32_at(i + 4)? as usize;
+            let body = i + 8;
+            match &bytes[i..i + 4] {
+                // audio format, channels, sample rate and bits per sample
+                b"fmt " => format = Some((u16_at(body)?, u16_at(body + 2)?, u32_at(body + 4)?, u16_at(body + 14)?)),
+                b"data" => data = Some(bytes.get(body..body.saturating_add(size).min(bytes.len()))?),
+                _ => {}
+            }
+            // chunks are padded to an even length, a size too big to add up means the file is broken
+            match body.checked_add(size).and_then(|end| end.checked_add(size % 2)) {
+                Some(next) => i = next,
+                None => break,
+            }
+        }
+
+        let (audio_format, channels, sample_rate, bits) = format?;
+        let data = data?;
+        if audio_format != 1 || channels == 0 || channels > 2 {
+            return None;
+        }
+        let raw: Vec<f32> = match bits {
+            8 => data.iter().map(|b| (*b as f32 - 128.0) / 128.0).collect(),
+            16 => data
+                .chunks_exact(2)
+                .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+                .collect(),
+            _ => return None,
+        };
+        let samples = raw
+            .chunks_exact(channels as usize)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect();
+
+        Some(Self { sample_rate, samples })
+    }
+
+    /// Plays the sound slower, dropping its pitch, and filters out the highs.
+    fn rumble(&self) -> Self {
+        const SPEED: f32 = 0.6;
+        // how much of each new sample gets through the low pass filter
+        const SMOOTHING: f32 = 0.08;
+        const GAIN: f32 = 2.0;
+
+        if self.samples.is_empty() {
+            return Self {
+                sample_rate: self.sample_rate,
+                samples: vec![],
+            };
+        }
+
+        let length = (self.samples.len() as f32 / SPEED) as usize;
+        let mut filtered = 0.0;
+        let samples = (0..length)
+            .map(|i| {
+                let position = i as f32 * SPEED;
+                let index = position as usize;
+                let next = (index + 1).min(self.samples.len() - 1);
+                let t = position - index as f32;
+                let sample = self.samples[index] * (1.0 - t) + self.samples[next] * t;
+                filtered += (sample - filtered) * SMOOTHING;
+                filtered * GAIN
+            })
+            .collect();
+
+        Self {
+            sample_rate: self.sample_rate,
+            samples,
+        }
+    }
+
+    /// Writes a 16 bit stereo wav file with each side at its own volume.
+    fn to_stereo(&self, left: f32, right: f32) -> Vec<u8> {
+        let samples: Vec<f32> = self
+            .samples
+            .iter()
+            .flat_map(|sample| [sample * left, sample * right])
+            .collect();
+        wav_bytes(self.sample_rate, 2, &samples)
+    }
+}
+
+/// Writes samples from -1.0 to 1.0 as a 16 bit wav file, interleaved if there are two channels.
+pub fn wav_bytes(sample_rate: u32, channels: u16, samples: &[f32]) -> Vec<u8> {
+    let data_size = samples.len() as u32 * 2;
+    let mut bytes = Vec::with_capacity(44 + data_size as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_size).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&channels.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * channels as u32 * 2).to_le_bytes());
+    bytes.extend_from_slice(&(channels * 2).to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_size.to_le_bytes());
+    for sample in samples {
+        let value = sample.clamp(-1.0, 1.0) * i16::MAX as f32;
+        bytes.extend_from_slice(&(value as i16).to_le_bytes());
+    }
+    bytes
+}
+
+/// A sound that is still playing, as far as we can tell.
+struct Voice {
+    name: String,
+    time_left: f32,
+}
+
+/// Plays sounds as if heard from where the listener stands.
+pub struct AudioManager {
+    listener: Vec2,
+    volume: f32,
+    voices: Vec<Voice>,
+}
+impl AudioManager {
+    pub fn new() -> Self {
+        Self {
+            listener: Vec2::ZERO,
+            volume: 1.0,
+            voices: Vec::new(),
+        }
+    }
+
+    /// Moves the ears, sounds are heard relative to this point.
+    pub fn set_listener(&mut self, position: Vec2) {
+        self.listener = position;
+    }
+
+    /// Sets the volume every sound is scaled by, from the player's settings.
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+    }
+
+    /// Forgets sounds that have finished.
+    pub fn update(&mut self, delta: f32) {
+        for voice in &mut self.voices {
+            voice.time_left -= delta;
+        }
+        self.voices.retain(|voice| voice.time_left > 0.0);
+    }
+
+    /// Plays `sound` coming from `position` in the world, `volume` from 0.0 to 1.0.
+    pub fn play_at(&mut self, sound: &PannedSound, position: Vec2, volume: f32) {
+        let offset = position - self.listener;
+        let distance = offset.length();
+        let attenuation = if distance <= REFERENCE_DISTANCE {
+            1.0
+        } else {
+            REFERENCE_DISTANCE / (REFERENCE_DISTANCE + ROLLOFF * (distance - REFERENCE_DISTANCE))
+        };
+        self.play_panned(sound, offset.x / PAN_DISTANCE, volume * attenuation);
+    }
+
+    fn play_panned(&mut self, sound: &PannedSound, pan: f32, volume: f32) {
+        let volume = volume * self.volume;
+        if volume <= 0.001 {
+            return;
+        }
+        let playing = self.voices.iter().filter(|voice| voice.name == sound.name).count();
+        if playing >= MAX_VOICES {
+            return;
+        }
+
+        let sound_params = PlaySoundParams {
+            volume,
+            ..PlaySoundParams::default()
+        };
+        play_sound(sound.variant(pan), sound_params);
+        self.voices.push(Voice {
+            name: sound.name.clone(),
+            time_left: sound.duration,
+        });
+    }
+}
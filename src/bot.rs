@@ -0,0 +1,106 @@
+//! A player that plays by itself, pressing the same actions a person would.
+
+use macroquad::math::Vec2;
+
+use crate::{
+    game::{path_length, CrewTask, Game, HitBox},
+    input::{Action, ActionState},
+    rect_center,
+};
+
+/// Seconds between the bot picking where to go and working out the way there.
+const THINK_INTERVAL: f32 = 0.5;
+
+/// A waypoint counts as reached once the player is this close to it.
+const WAYPOINT_RADIUS: f32 = 0.3;
+
+/// Only this many of the closest boxes as the crow flies are checked for the shortest walk.
+const CANDIDATES: usize = 3;
+
+/// Plays the first player, greedily fixing the nearest broken box and goes back to the van when out of kits.
+pub struct Bot {
+    path: Vec<Vec2>,
+    think_timer: f32,
+}
+impl Bot {
+    pub fn new() -> Self {
+        Self {
+            path: Vec::new(),
+            think_timer: 0.0,
+        }
+    }
+
+    /// Decides what to press this frame.
+    pub fn actions(&mut self, game: &Game, delta: f32) -> ActionState {
+        let mut actions = ActionState::default();
+
+        self.think_timer -= delta;
+        if self.think_timer <= 0.0 {
+            self.think_timer = THINK_INTERVAL;
+            self.path = Bot::plan(game);
+            // with more than one box waiting, the crew takes the closest
+            if Bot::waiting_boxes(game).len() > 1 {
+                actions.press(Action::Dispatch);
+            }
+        }
+
+        let center = rect_center(game.players()[0].hit_box());
+        while let Some(next) = self.path.first() {
+            if center.distance(*next) < WAYPOINT_RADIUS {
+                self.path.remove(0);
+            } else {
+                break;
+            }
+        }
+        if let Some(next) = self.path.first() {
+            let direction = *next - center;
+            if direction.length() > 0.0 {
+                actions.set_analog_movement(direction.normalize());
+                actions.hold(Action::Sprint);
+            }
+        }
+
+        actions
+    }
+
+    /// Gives the broken boxes no one from the crew is on their way to.
+    fn waiting_boxes(game: &Game) -> Vec<usize> {
+        game.electrical_boxes()
+            .iter()
+            .enumerate()
+            .filter(|(i, ebox)| {
+                *ebox.broken() && !game.crew().iter().any(|member| member.task() == CrewTask::Repair(*i))
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Picks the closest box to fix, or the van when the kits have run out.
+    fn plan(game: &Game) -> Vec<Vec2> {
+        let player = &game.players()[0];
+        let from = rect_center(player.hit_box());
+        let size = player.hit_box().w;
+        let van = rect_center(game.restock());
+        let kits = *player.repair_kits();
+
+        if kits == 0 {
+            return game.find_path(from, van, size).unwrap_or_default();
+        }
+
+        let mut waiting = Bot::waiting_boxes(game);
+        let distance = |i: &usize| rect_center(game.electrical_boxes()[*i].hit_box()).distance(from);
+        waiting.sort_by(|a, b| distance(a).total_cmp(&distance(b)));
+        let closest = waiting
+            .iter()
+            .take(CANDIDATES)
+            .filter_map(|i| game.find_path(from, rect_center(game.electrical_boxes()[*i].hit_box()), size))
+            .min_by(|a, b| path_length(from, a).total_cmp(&path_length(from, b)));
+
+        match closest {
+            Some(path) => path,
+            // nothing to fix, top up while it's quiet
+            None if kits < *game.max_number_of_repair_kits() => game.find_path(from, van, size).unwrap_or_default(),
+            None => Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! Calls into `power_crisis.js`, the miniquad plugin `index.html` loads, for
+//! what the browser has and macroquad doesn't give: `localStorage` and
+//! whether the page has focus.
+//!
+//! Native builds have no page, there the window is always taken to have focus
+//! and a long frame stands in for losing it.
+
+#[cfg(target_arch = "wasm32")]
+mod web {
+    extern "C" {
+        fn power_crisis_storage_length(key: *const u8, key_len: usize) -> i32;
+        fn power_crisis_storage_load(key: *const u8, key_len: usize, buffer: *mut u8, buffer_len: usize);
+        fn power_crisis_storage_save(key: *const u8, key_len: usize, value: *const u8, value_len: usize);
+        fn power_crisis_has_focus() -> u32;
+    }
+
+    /// Version 0.1.0 of the plugin, checked by miniquad's loader against the one in `power_crisis.js`.
+    #[no_mangle]
+    pub extern "C" fn power_crisis_crate_version() -> u32 {
+        1 << 16
+    }
+
+    /// Loads the value kept in `localStorage` under `key`, if there is one.
+    pub fn storage_load(key: &str) -> Option<String> {
+        let length = unsafe { power_crisis_storage_length(key.as_ptr(), key.len()) };
+        if length < 0 {
+            return None;
+        }
+        let mut buffer = vec![0; length as usize];
+        unsafe { power_crisis_storage_load(key.as_ptr(), key.len(), buffer.as_mut_ptr(), buffer.len()) };
+        String::from_utf8(buffer).ok()
+    }
+
+    /// Keeps `value` in `localStorage` under `key`.
+    pub fn storage_save(key: &str, value: &str) {
+        unsafe { power_crisis_storage_save(key.as_ptr(), key.len(), value.as_ptr(), value.len()) };
+    }
+
+    /// Gives whether the page is shown and has focus.
+    pub fn has_focus() -> bool {
+        unsafe { power_crisis_has_focus() != 0 }
+    }
+}
+#[cfg(target_arch = "wasm32")]
+pub use web::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn has_focus() -> bool {
+    true
+}
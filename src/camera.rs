@@ -0,0 +1,137 @@
+use macroquad::prelude::*;
+
+/// How quickly the camera catches up with the player, higher is snappier.
+const FOLLOW_SPEED: f32 = 6.0;
+
+/// How quickly zoom changes settle.
+const ZOOM_SPEED: f32 = 10.0;
+
+/// The camera leads the player by where they will be this many seconds from now.
+const LOOK_AHEAD_TIME: f32 = 0.35;
+
+/// The furthest the camera leads the player, in world units.
+const MAX_LOOK_AHEAD: f32 = 3.0;
+
+/// How far the view moves at full shake, in world units.
+const MAX_SHAKE: f32 = 0.6;
+
+/// How much shake wears off per second.
+const SHAKE_DECAY: f32 = 1.5;
+
+/// Follows the player around the map without ever showing past its edges.
+pub struct FollowCamera {
+    view: Camera2D,
+    position: Vec2,
+    look_ahead: Vec2,
+    zoom: f32,
+    /// How shaken the camera is from 0.0 to 1.0, the shake grows with its square.
+    trauma: f32,
+    time: f32,
+}
+impl FollowCamera {
+    pub fn new(target: Vec2, zoom: f32) -> Self {
+        let mut camera = Self {
+            view: Camera2D::default(),
+            position: target,
+            look_ahead: vec2(0.0, 0.0),
+            zoom,
+            trauma: 0.0,
+            time: 0.0,
+        };
+        camera.view.zoom = FollowCamera::zoom_for_screen(zoom);
+        camera.view.offset = -target * camera.view.zoom;
+        camera
+    }
+
+    /// Gives the camera zoom that fits the screen's aspect ratio.
+    ///
+    /// The longer side of the screen always shows the same amount of the city, so
+    /// phones held upright see as much as they do turned sideways.
+    fn zoom_for_screen(zoom: f32) -> Vec2 {
+        let scale = 0.1 * zoom;
+        let aspect = screen_width() / screen_height();
+        if aspect >= 1.0 {
+            vec2(scale, aspect * scale)
+        } else {
+            vec2(scale / aspect, scale)
+        }
+    }
+
+    /// Gives the zoom that shows an area `size` units big, for framing several players.
+    pub fn zoom_to_fit(size: Vec2) -> f32 {
+        // how far the view reaches either side of its centre at a zoom of 1.0
+        let half_view = Vec2::ONE / FollowCamera::zoom_for_screen(1.0);
+        let fit = half_view * 2.0 / size.max(Vec2::splat(0.001));
+        fit.x.min(fit.y)
+    }
+
+    /// Get the point the camera is looking at, before any shake.
+    pub fn position(&self) -> Vec2 {
+        self.position
+    }
+
+    /// Jumps straight to `target`, for when a run starts.
+    pub fn snap(&mut self, target: Vec2, zoom: f32) {
+        self.position = target;
+        self.look_ahead = vec2(0.0, 0.0);
+        self.zoom = zoom;
+        self.trauma = 0.0;
+    }
+
+    /// Shakes the camera, `amount` from 0.0 to 1.0 adds up with shake that hasn't worn off yet.
+    pub fn shake(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).min(1.0);
+    }
+
+    /// Moves towards `target`, leading it by `velocity`, and keeps the view inside the map.
+    pub fn update(&mut self, target: Vec2, velocity: Vec2, zoom: f32, map_size: Vec2, delta: f32) {
+        self.time += delta;
+
+        let mut look_ahead = velocity * LOOK_AHEAD_TIME;
+        if look_ahead.length() > MAX_LOOK_AHEAD {
+            look_ahead = look_ahead.normalize() * MAX_LOOK_AHEAD;
+        }
+        // framerate independent easing, the same fraction of the gap closes every second
+        let follow = 1.0 - (-FOLLOW_SPEED * delta).exp();
+        self.look_ahead += (look_ahead - self.look_ahead) * follow;
+        self.position += (target + self.look_ahead - self.position) * follow;
+        self.zoom += (zoom - self.zoom) * (1.0 - (-ZOOM_SPEED * delta).exp());
+
+        self.view.zoom = FollowCamera::zoom_for_screen(self.zoom);
+        let half_view = vec2(1.0 / self.view.zoom.x, 1.0 / self.view.zoom.y.abs());
+        let clamp = |center: Vec2| {
+            vec2(
+                FollowCamera::clamp_axis(center.x, half_view.x, map_size.x),
+                FollowCamera::clamp_axis(center.y, half_view.y, map_size.y),
+            )
+        };
+        self.position = clamp(self.position);
+
+        self.trauma = (self.trauma - SHAKE_DECAY * delta).max(0.0);
+        let strength = self.trauma * self.trauma * MAX_SHAKE;
+        // a few out of step waves look random enough and never jump between frames
+        let shake = vec2(
+            (self.time * 47.0).sin() + (self.time * 31.0).cos() * 0.5,
+            (self.time * 53.0).cos() + (self.time * 37.0).sin() * 0.5,
+        ) * strength
+            / 1.5;
+
+        // clamped again so a shake at the edge of the map doesn't show past it
+        self.view.offset = -clamp(self.position + shake) * self.view.zoom;
+    }
+
+    /// Keeps a view `half_view` wide on either side of `center` between 0.0 and `size`,
+    /// centering the map instead when it is smaller than the view.
+    fn clamp_axis(center: f32, half_view: f32, size: f32) -> f32 {
+        if half_view * 2.0 >= size {
+            size / 2.0
+        } else {
+            center.clamp(half_view, size - half_view)
+        }
+    }
+
+    /// Get a reference to the macroquad camera to draw the world with.
+    pub fn view(&self) -> &Camera2D {
+        &self.view
+    }
+}
@@ -0,0 +1,282 @@
+//! The campaign, a run of storms to get through one after another.
+//!
+//! Levels are read from `campaign.json`. Each starts from the city map and the
+//! rules and changes what it needs, then lists its objectives. Surviving is
+//! always one of them, and a level that isn't survived earns nothing, every
+//! other objective met on top earns a star. The best stars of every level are
+//! saved, clearing a level unlocks the next.
+
+use macroquad::math::Rect;
+use serde_json::{json, Value};
+
+use crate::{
+    clock,
+    game::{Game, HitBox, Level, Rules},
+    storage,
+};
+
+/// The stars earned in every level are saved under this key.
+const PROGRESS_KEY: &str = "campaign";
+
+/// Most objectives a level can have, one star each.
+pub const MAX_STARS: u32 = 3;
+
+/// Something to do in a level.
+pub enum Objective {
+    /// Keep the generator going for this many seconds, the level ends when it's done.
+    Survive(f32),
+    /// Keep this share of the boxes working on average.
+    Uptime(f32),
+    /// Have every box in the area working when the level ends.
+    District { name: String, area: Rect },
+}
+impl Objective {
+    fn from_json(value: &Value) -> Result<Self, String> {
+        let number = |key: &str| value[key].as_f64().map(|n| n as f32).ok_or(format!("{} is missing", key));
+        match value["type"].as_str() {
+            Some("survive") => Ok(Objective::Survive(number("seconds")?)),
+            Some("uptime") => Ok(Objective::Uptime(number("percent")? / 100.0)),
+            Some("district") => Ok(Objective::District {
+                name: value["name"].as_str().ok_or("name is missing")?.to_string(),
+                area: Rect::new(number("x")?, number("y")?, number("w")?, number("h")?),
+            }),
+            Some(other) => Err(format!("{} is not a kind of objective", other)),
+            None => Err("type is missing".to_string()),
+        }
+    }
+
+    /// Gives what has to be done, shown before the level starts.
+    pub fn describe(&self) -> String {
+        match self {
+            Objective::Survive(seconds) => format!("Survive the storm for {}", clock(*seconds)),
+            Objective::Uptime(share) => format!("Keep {:.0}% of the city powered", share * 100.0),
+            Objective::District { name, .. } => format!("End with all of {} powered", name),
+        }
+    }
+
+    /// Gives how it's going, shown while playing.
+    pub fn progress(&self, run: &CampaignRun, game: &Game) -> String {
+        match self {
+            Objective::Survive(seconds) => format!("Survive {} / {}", clock(run.time), clock(*seconds)),
+            Objective::Uptime(share) => {
+                format!("City powered {:.0}% (need {:.0}%)", run.uptime() * 100.0, share * 100.0)
+            }
+            Objective::District { name, area } => {
+                let (working, total) = district_boxes(game, area);
+                format!("{} powered {}/{}", name, working, total)
+            }
+        }
+    }
+
+    /// Gives whether the objective is met at this point of `run`.
+    pub fn met(&self, run: &CampaignRun, game: &Game) -> bool {
+        match self {
+            Objective::Survive(seconds) => run.time >= *seconds,
+            Objective::Uptime(share) => run.uptime() >= *share,
+            Objective::District { area, .. } => {
+                let (working, total) = district_boxes(game, area);
+                working == total
+            }
+        }
+    }
+}
+
+/// Gives how many boxes in `area` are working, and how many there are.
+fn district_boxes(game: &Game, area: &Rect) -> (usize, usize) {
+    let boxes: Vec<_> = game
+        .electrical_boxes()
+        .iter()
+        .filter(|ebox| area.contains(ebox.hit_box().point()))
+        .collect();
+    let working = boxes.iter().filter(|ebox| !*ebox.broken()).count();
+    (working, boxes.len())
+}
+
+/// One level of the campaign.
+pub struct CampaignLevel {
+    pub name: String,
+    pub description: String,
+    pub level: Level,
+    pub rules: Rules,
+    pub objectives: Vec<Objective>,
+}
+impl CampaignLevel {
+    /// Reads a level, the map and rules it doesn't give are `level` and `rules`.
+    fn from_json(value: &Value, level: &Level, rules: &Rules) -> Result<Self, String> {
+        let name = value["name"].as_str().ok_or("name is missing")?.to_string();
+        let description = value["description"].as_str().unwrap_or_default().to_string();
+        let level = match &value["map"] {
+            Value::Null => level.clone(),
+            map => Level::from_json(map).map_err(|e| format!("map: {}", e))?,
+        };
+        let rules = rules.with_changes(&value["rules"]);
+
+        let mut objectives = vec![];
+        for (i, objective) in value["objectives"].as_array().ok_or("objectives is not a list")?.iter().enumerate() {
+            objectives.push(Objective::from_json(objective).map_err(|e| format!("objective {}: {}", i, e))?);
+        }
+        if !objectives.iter().any(|objective| matches!(objective, Objective::Survive(_))) {
+            return Err("needs a survive objective".to_string());
+        }
+        if objectives.len() > MAX_STARS as usize {
+            return Err(format!("can't have more than {} objectives", MAX_STARS));
+        }
+
+        Ok(Self {
+            name,
+            description,
+            level,
+            rules,
+            objectives,
+        })
+    }
+
+    /// Gives how many seconds the level lasts, the longest it has to be survived.
+    pub fn duration(&self) -> f32 {
+        self.objectives
+            .iter()
+            .filter_map(|objective| match objective {
+                Objective::Survive(seconds) => Some(*seconds),
+                _ => None,
+            })
+            .fold(0.0, f32::max)
+    }
+}
+
+/// Every level of the campaign and the best stars earned in each.
+pub struct Campaign {
+    levels: Vec<CampaignLevel>,
+    stars: Vec<u32>,
+}
+impl Campaign {
+    /// The campaign built into the game, on the city `level` and `rules`.
+    pub fn embedded(level: &Level, rules: &Rules) -> Self {
+        let campaign = serde_json::from_slice(include_bytes!("../campaign.json")).unwrap();
+        Campaign::from_json(&campaign, level, rules).unwrap()
+    }
+
+    /// Reads the campaign and the stars saved for it, levels build on `level` and `rules`.
+    pub fn from_json(campaign: &Value, level: &Level, rules: &Rules) -> Result<Self, String> {
+        let mut levels = vec![];
+        for (i, value) in campaign["levels"].as_array().ok_or("levels is not a list")?.iter().enumerate() {
+            levels.push(CampaignLevel::from_json(value, level, rules).map_err(|e| format!("level {}: {}", i, e))?);
+        }
+
+        // stars are saved by name so adding or moving levels keeps them
+        let saved: Value = storage::load(PROGRESS_KEY)
+            .and_then(|saved| serde_json::from_str(&saved).ok())
+            .unwrap_or_default();
+        let stars = levels
+            .iter()
+            .map(|level| saved["stars"][&level.name].as_u64().map_or(0, |n| (n as u32).min(MAX_STARS)))
+            .collect();
+
+        Ok(Self { levels, stars })
+    }
+
+    fn save(&self) {
+        let stars: serde_json::Map<String, Value> = self
+            .levels
+            .iter()
+            .zip(&self.stars)
+            .map(|(level, stars)| (level.name.clone(), json!(stars)))
+            .collect();
+        storage::save(PROGRESS_KEY, &json!({ "stars": stars }).to_string());
+    }
+
+    /// Get a reference to the campaign's levels.
+    pub fn levels(&self) -> &[CampaignLevel] {
+        self.levels.as_ref()
+    }
+
+    /// Gives the most stars earned in the level at `index`.
+    pub fn stars(&self, index: usize) -> u32 {
+        self.stars[index]
+    }
+
+    /// Gives whether the level at `index` can be played, the first always can.
+    pub fn unlocked(&self, index: usize) -> bool {
+        index == 0 || self.stars[index - 1] > 0
+    }
+
+    /// Saves the stars earned in the level at `index` if they beat the last best.
+    pub fn record(&mut self, index: usize, stars: u32) {
+        if stars > self.stars[index] {
+            self.stars[index] = stars;
+            self.save();
+        }
+    }
+}
+
+/// Keeps track of a level being played.
+pub struct CampaignRun {
+    level: usize,
+    time: f32,
+    /// Share of the boxes working, added up over every second played.
+    powered: f32,
+    /// Which objectives were met, once the level is over.
+    result: Option<Vec<bool>>,
+    survived: bool,
+}
+impl CampaignRun {
+    pub fn new(level: usize) -> Self {
+        Self {
+            level,
+            time: 0.0,
+            powered: 0.0,
+            result: None,
+            survived: false,
+        }
+    }
+
+    pub fn update(&mut self, game: &Game, delta: f32) {
+        let total = game.electrical_boxes().len().max(1) as f32;
+        self.time += delta;
+        self.powered += game.get_working_boxes() as f32 / total * delta;
+    }
+
+    /// Gives the share of the boxes that have been working on average.
+    pub fn uptime(&self) -> f32 {
+        if self.time > 0.0 {
+            self.powered / self.time
+        } else {
+            1.0
+        }
+    }
+
+    /// Gives whether the level has been survived for long enough to end.
+    pub fn cleared(&self, level: &CampaignLevel) -> bool {
+        self.time >= level.duration()
+    }
+
+    /// Ends the level, checking off every objective, and gives the stars earned.
+    pub fn finish(&mut self, level: &CampaignLevel, game: &Game) -> u32 {
+        let result: Vec<bool> = level.objectives.iter().map(|objective| objective.met(self, game)).collect();
+        self.result = Some(result);
+        self.survived = self.cleared(level);
+        self.stars()
+    }
+
+    /// Gives the stars earned, none until the level is over or if it wasn't survived.
+    pub fn stars(&self) -> u32 {
+        match &self.result {
+            Some(result) if self.survived => result.iter().filter(|met| **met).count() as u32,
+            _ => 0,
+        }
+    }
+
+    /// Get whether the level was survived, once it's over.
+    pub fn survived(&self) -> bool {
+        self.survived
+    }
+
+    /// Get the index of the level being played.
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    /// Get which objectives were met, once the level is over.
+    pub fn result(&self) -> Option<&[bool]> {
+        self.result.as_deref()
+    }
+}
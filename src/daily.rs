@@ -0,0 +1,368 @@
+//! The daily challenge, one storm a day that everyone plays the same.
+//!
+//! The seed, the map and a couple of twists on the rules all come from the
+//! date, counted in UTC so the day changes at the same moment everywhere.
+//! Each day can be tried a few times. Every attempt is saved with its replay,
+//! and `--verify-daily [date]` plays the replays back to check they were run
+//! on that day's storm and scored what was saved.
+
+use serde_json::{json, Value};
+
+use crate::{
+    campaign::Campaign,
+    game::{Level, Rules},
+    replay::Recording,
+    rng::Rng,
+    storage,
+};
+
+/// Results of every day played are saved under this key.
+const RESULTS_KEY: &str = "daily";
+
+/// Times each day's challenge can be tried.
+pub const MAX_ATTEMPTS: u32 = 3;
+
+/// Twists on the rules to make each day play differently.
+const MODIFIERS_PER_DAY: usize = 2;
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+/// A twist on the rules, a couple are picked for every day.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Modifier {
+    Downpour,
+    RestlessSky,
+    CalmSpells,
+    LeakyTank,
+    SkeletonCrew,
+    FullCrew,
+    LightVan,
+}
+impl Modifier {
+    pub const ALL: [Modifier; 7] = [
+        Modifier::Downpour,
+        Modifier::RestlessSky,
+        Modifier::CalmSpells,
+        Modifier::LeakyTank,
+        Modifier::SkeletonCrew,
+        Modifier::FullCrew,
+        Modifier::LightVan,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Modifier::Downpour => "Downpour",
+            Modifier::RestlessSky => "Restless sky",
+            Modifier::CalmSpells => "Calm spells",
+            Modifier::LeakyTank => "Leaky tank",
+            Modifier::SkeletonCrew => "Skeleton crew",
+            Modifier::FullCrew => "Full crew",
+            Modifier::LightVan => "Light van",
+        }
+    }
+
+    /// Gives what the twist does, for the challenge screen.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Modifier::Downpour => "puddles form twice as often and last longer",
+            Modifier::RestlessSky => "boxes break more often",
+            Modifier::CalmSpells => "boxes break less often",
+            Modifier::LeakyTank => "the generator starts with less fuel",
+            Modifier::SkeletonCrew => "one helper fewer at the van",
+            Modifier::FullCrew => "one more helper at the van",
+            Modifier::LightVan => "two repair kits fewer can be carried",
+        }
+    }
+
+    fn apply(&self, rules: &mut Rules) {
+        match self {
+            Modifier::Downpour => {
+                rules.puddle_interval = (rules.puddle_interval.0 * 0.5, rules.puddle_interval.1 * 0.5);
+                rules.puddle_lifetime *= 1.5;
+            }
+            Modifier::RestlessSky => {
+                rules.break_interval = (rules.break_interval.0 * 0.75, rules.break_interval.1 * 0.75);
+            }
+            Modifier::CalmSpells => {
+                rules.break_interval = (rules.break_interval.0 * 1.25, rules.break_interval.1 * 1.25);
+            }
+            Modifier::LeakyTank => rules.generator_fuel *= 0.7,
+            Modifier::SkeletonCrew => rules.crew_size = rules.crew_size.saturating_sub(1),
+            Modifier::FullCrew => rules.crew_size += 1,
+            Modifier::LightVan => rules.max_repair_kits = rules.max_repair_kits.saturating_sub(2).max(1),
+        }
+    }
+}
+
+/// Gives the maps a day can be played on, the city and any campaign level with a map of its own.
+///
+/// A map several levels share is only given once, named after the first of them.
+pub fn daily_maps(level: &Level, campaign: &Campaign) -> Vec<(String, Level)> {
+    let mut maps = vec![("The city".to_string(), level.clone())];
+    let mut seen = vec![level.to_json()];
+    for campaign_level in campaign.levels() {
+        let map = campaign_level.level.to_json();
+        if !seen.contains(&map) {
+            seen.push(map);
+            maps.push((campaign_level.name.clone(), campaign_level.level.clone()));
+        }
+    }
+    maps
+}
+
+/// Gives the day it is in UTC, counted from the first of January 1970.
+pub fn today() -> i64 {
+    (macroquad::miniquad::date::now() / SECONDS_PER_DAY).floor() as i64
+}
+
+/// Gives `day` as a year, month and day like 2022-07-30.
+pub fn date_text(day: i64) -> String {
+    // from Howard Hinnant's date algorithms
+    let z = day + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day_of_month = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day_of_month)
+}
+
+/// Reads a date like 2022-07-30 back into the day `date_text` gave it for.
+pub fn parse_date(text: &str) -> Option<i64> {
+    let mut parts = text.split('-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+    if parts.next().is_some() || !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+        return None;
+    }
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146097 + day_of_era - 719468)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// How one attempt went.
+pub struct DailyResult {
+    pub attempt: u32,
+    pub score: f32,
+    pub time_survived: f32,
+}
+
+/// One day's storm.
+pub struct DailyChallenge {
+    pub date: String,
+    pub seed: u64,
+    pub map_name: String,
+    pub level: Level,
+    pub rules: Rules,
+    pub modifiers: Vec<Modifier>,
+}
+impl DailyChallenge {
+    /// Works out the storm for `day` from the `maps` it can be played on and the city `rules`.
+    pub fn new(day: i64, maps: &[(String, Level)], rules: &Rules) -> Self {
+        let date = date_text(day);
+        let mut rng = Rng::new(day as u64);
+        let seed = (rng.next_u32() as u64) << 32 | rng.next_u32() as u64;
+
+        let (map_name, level) = maps[rng.next_u32() as usize % maps.len()].clone();
+
+        let mut modifiers = vec![];
+        while modifiers.len() < MODIFIERS_PER_DAY {
+            let modifier = Modifier::ALL[rng.next_u32() as usize % Modifier::ALL.len()];
+            // twists that undo each other make a dull day
+            let clashes = |other: &Modifier| {
+                matches!(
+                    (modifier, *other),
+                    (Modifier::RestlessSky, Modifier::CalmSpells)
+                        | (Modifier::CalmSpells, Modifier::RestlessSky)
+                        | (Modifier::SkeletonCrew, Modifier::FullCrew)
+                        | (Modifier::FullCrew, Modifier::SkeletonCrew)
+                )
+            };
+            if !modifiers.contains(&modifier) && !modifiers.iter().any(clashes) {
+                modifiers.push(modifier);
+            }
+        }
+        let mut rules = *rules;
+        for modifier in &modifiers {
+            modifier.apply(&mut rules);
+        }
+
+        Self {
+            date,
+            seed,
+            map_name,
+            level,
+            rules,
+            modifiers,
+        }
+    }
+
+    /// Gives the key the replay of an attempt is saved under.
+    pub fn replay_key(&self, attempt: u32) -> String {
+        format!("daily-{}-{}", self.date, attempt)
+    }
+
+    fn load_all() -> Value {
+        storage::load(RESULTS_KEY)
+            .and_then(|saved| serde_json::from_str(&saved).ok())
+            .filter(Value::is_object)
+            .unwrap_or_else(|| json!({}))
+    }
+
+    /// Gives how many attempts have been started today.
+    pub fn attempts(&self) -> u32 {
+        DailyChallenge::load_all()[&self.date]["attempts"].as_u64().unwrap_or(0) as u32
+    }
+
+    /// Gives how every finished attempt went, in the order they were played.
+    pub fn results(&self) -> Vec<DailyResult> {
+        let saved = DailyChallenge::load_all();
+        let results = saved[&self.date]["results"].as_array().cloned().unwrap_or_default();
+        results
+            .iter()
+            .filter_map(|result| {
+                Some(DailyResult {
+                    attempt: result["attempt"].as_u64()? as u32,
+                    score: result["score"].as_f64()? as f32,
+                    time_survived: result["time_survived"].as_f64()? as f32,
+                })
+            })
+            .collect()
+    }
+
+    /// Gives the best finished attempt.
+    pub fn best(&self) -> Option<DailyResult> {
+        self.results().into_iter().reduce(|best, result| if result.score > best.score { result } else { best })
+    }
+
+    /// Uses up an attempt, giving its number, or nothing when none are left.
+    ///
+    /// The attempt counts from the start so quitting a bad run doesn't give it back.
+    pub fn start_attempt(&self) -> Option<u32> {
+        let mut saved = DailyChallenge::load_all();
+        let attempts = saved[&self.date]["attempts"].as_u64().unwrap_or(0) as u32;
+        if attempts >= MAX_ATTEMPTS {
+            return None;
+        }
+        saved[&self.date]["attempts"] = json!(attempts + 1);
+        storage::save(RESULTS_KEY, &saved.to_string());
+        Some(attempts + 1)
+    }
+
+    /// Saves how an attempt went along with its replay.
+    pub fn record(&self, attempt: u32, score: f32, time_survived: f32, recording: &Recording) {
+        recording.save_as(&self.replay_key(attempt));
+        let mut saved = DailyChallenge::load_all();
+        let result = json!({ "attempt": attempt, "score": score, "time_survived": time_survived });
+        match saved[&self.date]["results"].as_array_mut() {
+            Some(results) => results.push(result),
+            None => saved[&self.date]["results"] = json!([result]),
+        }
+        storage::save(RESULTS_KEY, &saved.to_string());
+    }
+
+    /// Checks the replay of an attempt was played on this storm and scores what was saved for it.
+    pub fn verify(&self, result: &DailyResult) -> Result<(), String> {
+        let recording = Recording::load_saved(&self.replay_key(result.attempt))?;
+        if recording.seed() != self.seed || recording.players() != 1 {
+            return Err("was played from another seed".to_string());
+        }
+        if recording.level().to_json() != self.level.to_json() {
+            return Err("was played on another map".to_string());
+        }
+        if recording.rules().to_json() != self.rules.to_json() {
+            return Err("was played with other rules".to_string());
+        }
+        let outcome = recording.outcome();
+        if outcome.score != result.score || outcome.time_survived != result.time_survived {
+            return Err(format!(
+                "replays to {} points in {:.2}s, not the {} points in {:.2}s saved",
+                outcome.score, outcome.time_survived, result.score, result.time_survived
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Gives the date to check if the game was started with `--verify-daily [date]`,
+/// with no date today is checked.
+pub fn verify_arg() -> Option<Option<String>> {
+    let args: Vec<String> = std::env::args().collect();
+    let verify = args.iter().position(|arg| arg == "--verify-daily")?;
+    Some(args.get(verify + 1).filter(|date| !date.starts_with("--")).cloned())
+}
+
+/// Plays back every saved attempt of a day, worked out from the city `level`,
+/// `rules` and `campaign`, and prints whether it holds up.
+pub fn run_verify(date: Option<&str>, level: &Level, rules: &Rules, campaign: &Campaign) {
+    let day = match date {
+        Some(date) => parse_date(date).unwrap_or_else(|| {
+            eprintln!("{} is not a date like 2022-07-30", date);
+            std::process::exit(1);
+        }),
+        None => today(),
+    };
+    let challenge = DailyChallenge::new(day, &daily_maps(level, campaign), rules);
+
+    let results = challenge.results();
+    if results.is_empty() {
+        println!("no attempts of the {} challenge are saved", challenge.date);
+        return;
+    }
+    let mut failed = false;
+    for result in &results {
+        let status = match challenge.verify(result) {
+            Ok(()) => "verified".to_string(),
+            Err(e) => {
+                failed = true;
+                format!("FAILED, {}", e)
+            }
+        };
+        println!(
+            "{} attempt {}: {} points in {:.2}s, {}",
+            challenge.date, result.attempt, result.score, result.time_survived, status
+        );
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_read_back_as_the_same_day() {
+        assert_eq!(date_text(0), "1970-01-01");
+        // every day from 1970 to past 2500
+        for day in 0..200_000 {
+            assert_eq!(parse_date(&date_text(day)), Some(day), "{}", date_text(day));
+        }
+    }
+
+    #[test]
+    fn days_past_the_end_of_the_month_are_not_dates() {
+        assert_eq!(parse_date("2026-02-31"), None);
+        assert_eq!(parse_date("2026-04-31"), None);
+        assert_eq!(parse_date("2023-02-29"), None);
+        assert_eq!(parse_date("1900-02-29"), None);
+        assert!(parse_date("2024-02-29").is_some());
+        assert!(parse_date("2000-02-29").is_some());
+        assert!(parse_date("2026-12-31").is_some());
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::VecDeque;
+use std::{collections::VecDeque, rc::Rc};
 
 mod generator;
 pub use generator::*;
@@ -18,30 +18,47 @@ pub use puddle::*;
 mod random_timer;
 pub use random_timer::*;
 
+mod level;
+pub use level::*;
+
+mod rules;
+pub use rules::*;
+
+mod navigation;
+pub use navigation::*;
+
+mod crew;
+pub use crew::*;
+
+mod snapshot;
+
+/// Things that happened in the game, players are given by index.
+#[derive(Clone)]
 pub enum GameEvent {
-    Restock,
-    FixEBox(ElectricalBox),
+    Restock(usize),
+    FixEBox(usize, ElectricalBox),
+    /// A crew member, by index, fixed a box.
+    CrewFixEBox(usize, ElectricalBox),
     DestroyEBox(ElectricalBox),
 }
 
-use macroquad::{
-    math::{vec2, Rect, Vec2},
-    rand::gen_range,
-};
+use macroquad::math::{vec2, Rect, Vec2};
 
-use crate::PIXELS_PER_UNIT;
+use crate::{rng::Rng, PIXELS_PER_UNIT};
 
 pub trait HitBox {
     fn hit_box(&self) -> &Rect;
 }
 
+/// A run of the game, cloned to go back to how it was at some point.
+#[derive(Clone)]
 pub struct Game {
     generator: Generator,
-    player: Player,
+    players: Vec<Player>,
+    crew: Vec<Electrician>,
 
     walls: Vec<Wall>,
 
-    number_of_repair_kits: u32,
     max_number_of_repair_kits: u32,
 
     electrical_boxes: Vec<ElectricalBox>,
@@ -52,142 +69,261 @@ pub struct Game {
 
     restock: Rect,
 
+    /// Shared between copies of the game, it only changes with the map.
+    navigation: Rc<NavGrid>,
+
+    rules: Rules,
+    /// Every dice roll of the run, so the same seed plays out the same way.
+    rng: Rng,
+
     map_width: f32,
     map_height: f32,
 
     event_queue: VecDeque<GameEvent>,
 }
 impl Game {
-    pub fn load() -> Self {
-        let map: serde_json::Value =
-            // serde_json::from_reader(std::fs::File::open("map.json").unwrap()).unwrap();
-            serde_json::from_slice(include_bytes!("../map.json")).unwrap();
-
-        let generator = Generator::new(1.0, 0.1, true);
-
-        let player = &map["player"];
-        let player = Player::new(Rect::new(
-            player["x"].as_f64().unwrap() as f32,
-            player["y"].as_f64().unwrap() as f32,
-            6.0 / PIXELS_PER_UNIT,
-            6.0 / PIXELS_PER_UNIT,
-        ));
+    /// Starts a run for `player_count` players sharing the city, `seed` decides
+    /// when and where boxes break and puddles form.
+    pub fn new(level: &Level, rules: &Rules, seed: u64, player_count: usize) -> Self {
+        let mut rng = Rng::new(seed);
+        let generator = Generator::new(rules.generator_fuel, rules.generator_efficiency, true);
 
-        let van = &map["van"];
-        let restock = Rect::new(
-            van["x"].as_f64().unwrap() as f32 - 1.0,
-            van["y"].as_f64().unwrap() as f32 - 1.0,
-            van["w"].as_f64().unwrap() as f32 + 2.0,
-            van["h"].as_f64().unwrap() as f32 + 2.0,
-        );
+        let (walls, restock, electrical_boxes) = Game::build_level(level);
+        let navigation = Rc::new(Game::build_navigation(level.size, &walls, &electrical_boxes));
+        let players = Game::spawn_players(level, rules, player_count, &navigation);
+        let crew = Game::spawn_crew(level, rules, &navigation);
 
-        let mut walls = vec![Wall::new(Rect::new(
-            van["x"].as_f64().unwrap() as f32,
-            van["y"].as_f64().unwrap() as f32,
-            van["w"].as_f64().unwrap() as f32,
-            van["h"].as_f64().unwrap() as f32,
-        ))];
-        for wall in map["walls"].as_array().unwrap() {
-            walls.push(Wall::new(Rect::new(
-                wall["x"].as_f64().unwrap() as f32,
-                wall["y"].as_f64().unwrap() as f32,
-                wall["w"].as_f64().unwrap() as f32,
-                wall["h"].as_f64().unwrap() as f32,
-            )));
-        }
-
-        let mut electrical_boxes = vec![];
-        for ebox in map["electrical_boxes"].as_array().unwrap() {
-            electrical_boxes.push(ElectricalBox::new(Rect::new(
-                ebox["x"].as_f64().unwrap() as f32,
-                ebox["y"].as_f64().unwrap() as f32,
-                10.0 / PIXELS_PER_UNIT,
-                16.0 / PIXELS_PER_UNIT,
-            )));
-        }
-        let break_timer = RandomTimer::new(3.0, 5.0);
+        let break_timer = RandomTimer::new(rules.break_interval.0, rules.break_interval.1, &mut rng);
 
         let puddles = vec![];
-        let puddle_timer = RandomTimer::new(0.1, 1.0);
+        let puddle_timer = RandomTimer::new(rules.puddle_interval.0, rules.puddle_interval.1, &mut rng);
 
-        let max_number_of_repair_kits = 5;
-        let number_of_repair_kits = max_number_of_repair_kits;
+        let max_number_of_repair_kits = rules.max_repair_kits;
 
-        let map_width = 1600.0 / PIXELS_PER_UNIT;
-        let map_height = 800.0 / PIXELS_PER_UNIT;
+        let map_width = level.size.x;
+        let map_height = level.size.y;
 
         let event_queue = VecDeque::new();
 
         Self {
             generator,
-            player,
+            players,
+            crew,
             walls,
             restock,
+            navigation,
             max_number_of_repair_kits,
-            number_of_repair_kits,
             electrical_boxes,
             break_timer,
             puddles,
             puddle_timer,
+            rules: *rules,
+            rng,
             map_width,
             map_height,
             event_queue,
         }
     }
 
+    fn build_level(level: &Level) -> (Vec<Wall>, Rect, Vec<ElectricalBox>) {
+        let van = level.van;
+        let restock = Rect::new(van.x - 1.0, van.y - 1.0, van.w + 2.0, van.h + 2.0);
+
+        let mut walls = vec![Wall::new(van)];
+        for wall in &level.walls {
+            walls.push(Wall::new(*wall));
+        }
+
+        let mut electrical_boxes = vec![];
+        for ebox in &level.electrical_boxes {
+            electrical_boxes.push(ElectricalBox::new(Rect::new(
+                ebox.x,
+                ebox.y,
+                10.0 / PIXELS_PER_UNIT,
+                16.0 / PIXELS_PER_UNIT,
+            )));
+        }
+
+        (walls, restock, electrical_boxes)
+    }
+
+    /// Builds the navigation grid around everything nobody can walk through.
+    fn build_navigation(size: Vec2, walls: &[Wall], electrical_boxes: &[ElectricalBox]) -> NavGrid {
+        let obstacles: Vec<Rect> = walls
+            .iter()
+            .map(|wall| *wall.hit_box())
+            .chain(electrical_boxes.iter().map(|ebox| *ebox.hit_box()))
+            .collect();
+        NavGrid::new(size, &obstacles)
+    }
+
+    /// Puts the first player on the map's spawn and the others just beside them.
+    fn spawn_players(level: &Level, rules: &Rules, count: usize, navigation: &NavGrid) -> Vec<Player> {
+        let size = 6.0 / PIXELS_PER_UNIT;
+        let mut players = vec![];
+        for i in 0..count.max(1) {
+            let spawn = level.player + vec2(i as f32 * size * 2.0, 0.0);
+            let corner = if i == 0 {
+                spawn
+            } else {
+                let center = spawn + vec2(size, size) / 2.0;
+                navigation.nearest_open(center, size).unwrap_or(center) - vec2(size, size) / 2.0
+            };
+            players.push(Player::new(
+                Rect::new(corner.x, corner.y, size, size),
+                rules.max_repair_kits,
+            ));
+        }
+        players
+    }
+
+    /// Lines the crew up beside the van.
+    fn spawn_crew(level: &Level, rules: &Rules, navigation: &NavGrid) -> Vec<Electrician> {
+        let size = 6.0 / PIXELS_PER_UNIT;
+        let van = level.van;
+        let mut crew = vec![];
+        for i in 0..rules.crew_size {
+            let spread = (i as f32 - (rules.crew_size as f32 - 1.0) / 2.0) * size * 2.0;
+            let beside_van = vec2(van.x + van.w / 2.0 + spread, van.y - size);
+            let center = navigation.nearest_open(beside_van, size).unwrap_or(beside_van);
+            crew.push(Electrician::new(
+                Rect::new(center.x - size / 2.0, center.y - size / 2.0, size, size),
+                rules.crew_repair_kits,
+            ));
+        }
+        crew
+    }
+
+    /// Swaps in a changed map and rules without restarting the run.
+    ///
+    /// Boxes that didn't move stay broken or fixed, players stay put unless
+    /// they would end up inside something, then they go back to the spawn.
+    pub fn reload(&mut self, level: &Level, rules: &Rules) {
+        let (walls, restock, mut electrical_boxes) = Game::build_level(level);
+        for ebox in &mut electrical_boxes {
+            if let Some(old) = self
+                .electrical_boxes
+                .iter()
+                .find(|old| old.hit_box().point() == ebox.hit_box().point())
+            {
+                *ebox.broken_mut() = *old.broken();
+            }
+        }
+        self.walls = walls;
+        self.restock = restock;
+        self.map_width = level.size.x;
+        self.map_height = level.size.y;
+        self.electrical_boxes = electrical_boxes;
+        self.navigation = Rc::new(Game::build_navigation(level.size, &self.walls, &self.electrical_boxes));
+
+        // box indices may mean other boxes now, so everyone heads back to the van
+        if self.crew.len() != rules.crew_size as usize {
+            self.crew = Game::spawn_crew(level, rules, &self.navigation);
+        }
+        for member in &mut self.crew {
+            member.set_task(CrewTask::Restock);
+            *member.repair_kits_mut() = (*member.repair_kits()).min(rules.crew_repair_kits);
+        }
+
+        let blocked = |hit_box: &Rect, game: &Game| {
+            game.walls.iter().any(|wall| overlaps(hit_box, wall.hit_box()))
+                || game
+                    .electrical_boxes
+                    .iter()
+                    .any(|ebox| overlaps(hit_box, ebox.hit_box()))
+        };
+        for i in 0..self.players.len() {
+            if blocked(self.players[i].hit_box(), self) {
+                let spawn = level.player;
+                self.players[i].hit_box_mut().move_to(spawn);
+            }
+        }
+        let puddles = std::mem::take(&mut self.puddles);
+        self.puddles = puddles
+            .into_iter()
+            .filter(|puddle| !blocked(puddle.hit_box(), self))
+            .collect();
+
+        self.generator = Generator::new(
+            self.generator.feul(),
+            rules.generator_efficiency,
+            self.generator.running(),
+        );
+        self.break_timer = RandomTimer::new(rules.break_interval.0, rules.break_interval.1, &mut self.rng);
+        self.puddle_timer = RandomTimer::new(rules.puddle_interval.0, rules.puddle_interval.1, &mut self.rng);
+        self.max_number_of_repair_kits = rules.max_repair_kits;
+        for player in &mut self.players {
+            *player.repair_kits_mut() = (*player.repair_kits()).min(rules.max_repair_kits);
+        }
+        self.rules = *rules;
+    }
+
     pub fn update(&mut self, delta: f32) {
         self.puddle_timer.update(delta);
 
         self.fix_eboxes();
-        if self.get_working_boxes() < self.electrical_boxes.len() / 2 {
-            *self.generator.running_mut() = true;
-        } else {
-            *self.generator.running_mut() = false;
-        }
+        *self.generator.running_mut() = self.get_working_boxes() < self.electrical_boxes.len() / 2;
 
         self.generator.update(delta);
 
         self.try_restock();
         self.update_puddles(delta);
-        self.player.update_pos(self.which_drag(), delta);
-        self.map_collisions();
-        self.player_collisions();
+        for i in 0..self.players.len() {
+            self.step_player(i, delta);
+        }
+        self.update_crew(delta);
 
         self.break_timer.update(delta);
         if self.break_timer.is_active() {
             self.break_random_ebox();
 
-            self.break_timer.reset();
+            self.break_timer.reset(&mut self.rng);
         }
     }
 
+    /// Moves only the player at `index`, for clients guessing where they are
+    /// before the server says so.
+    pub fn step_player(&mut self, index: usize, delta: f32) {
+        let drag = self.drag_at(self.players[index].hit_box());
+        self.players[index].update_pos(drag, delta);
+        let hit_box = self.resolve_collisions(*self.players[index].hit_box());
+        *self.players[index].hit_box_mut() = hit_box;
+    }
+
     fn fix_eboxes(&mut self) {
-        if self.number_of_repair_kits > 0 {
+        for p in 0..self.players.len() {
+            let player = &mut self.players[p];
             for i in 0..self.electrical_boxes.len() {
+                if *player.repair_kits() == 0 {
+                    break;
+                }
                 let ebox = &mut self.electrical_boxes[i];
-                if ebox.fix_hit_box().overlaps(self.player.hit_box()) && *ebox.broken() {
+                if ebox.fix_hit_box().overlaps(player.hit_box()) && *ebox.broken() {
                     *ebox.broken_mut() = false;
-                    self.number_of_repair_kits -= 1;
+                    *player.repair_kits_mut() -= 1;
                     let ebox = *ebox;
-                    self.add_event(GameEvent::FixEBox(ebox));
+                    self.event_queue.push_back(GameEvent::FixEBox(p, ebox));
                 }
             }
         }
     }
 
     fn try_restock(&mut self) {
-        if self.player.hit_box().overlaps(&self.restock)
-            && self.number_of_repair_kits < self.max_number_of_repair_kits
-        {
-            self.add_event(GameEvent::Restock);
-            self.number_of_repair_kits = self.max_number_of_repair_kits;
+        for p in 0..self.players.len() {
+            let player = &mut self.players[p];
+            if player.hit_box().overlaps(&self.restock)
+                && *player.repair_kits() < self.max_number_of_repair_kits
+            {
+                *player.repair_kits_mut() = self.max_number_of_repair_kits;
+                self.add_event(GameEvent::Restock(p));
+            }
         }
     }
 
     fn update_puddles(&mut self, delta: f32) {
         if self.puddle_timer.is_active() {
             self.spawn_puddle();
-            self.puddle_timer.reset()
+            self.puddle_timer.reset(&mut self.rng)
         }
         for puddle in &mut self.puddles {
             puddle.update(delta);
@@ -210,53 +346,162 @@ impl Game {
         // });
     }
 
-    fn player_collisions(&mut self) {
-        for i in 0..self.walls().len() {
-            if let Some(v) = aabb_collision(self.player.hit_box(), self.walls()[i].hit_box()) {
-                self.player.hit_box_mut().move_to(v);
-            }
+    /// Gives `hit_box` pushed back inside the map and out of walls and electrical boxes.
+    fn resolve_collisions(&self, mut hit_box: Rect) -> Rect {
+        if hit_box.left() < 0.0 {
+            hit_box.move_to(vec2(0.0, hit_box.y));
+        }
+        if hit_box.top() < 0.0 {
+            hit_box.move_to(vec2(hit_box.x, 0.0));
+        }
+        if hit_box.right() > self.map_width {
+            hit_box.move_to(vec2(self.map_width - hit_box.w, hit_box.y));
+        }
+        if hit_box.bottom() > self.map_height {
+            hit_box.move_to(vec2(hit_box.x, self.map_height - hit_box.h));
         }
 
-        for i in 0..self.electrical_boxes().len() {
-            if let Some(v) =
-                aabb_collision(self.player.hit_box(), self.electrical_boxes()[i].hit_box())
-            {
-                self.player.hit_box_mut().move_to(v);
+        for wall in &self.walls {
+            if let Some(v) = aabb_collision(&hit_box, wall.hit_box()) {
+                hit_box.move_to(v);
+            }
+        }
+        for ebox in &self.electrical_boxes {
+            if let Some(v) = aabb_collision(&hit_box, ebox.hit_box()) {
+                hit_box.move_to(v);
             }
         }
+        hit_box
     }
 
-    fn map_collisions(&mut self) {
-        if self.player.hit_box().left() < 0.0 {
-            let x = 0.0;
-            let y = self.player.hit_box().y;
-            self.player.hit_box_mut().move_to(vec2(x, y));
+    fn update_crew(&mut self, delta: f32) {
+        for i in 0..self.crew.len() {
+            if self.crew[i].needs_path(delta) {
+                let from = rect_center(self.crew[i].hit_box());
+                let target = match self.crew[i].task() {
+                    CrewTask::Idle => None,
+                    CrewTask::Repair(index) => self.electrical_boxes.get(index).map(|ebox| rect_center(ebox.hit_box())),
+                    CrewTask::Restock => Some(rect_center(&self.restock)),
+                };
+                let size = self.crew[i].hit_box().w;
+                let path = target
+                    .and_then(|target| self.navigation.find_path(from, target, size))
+                    .unwrap_or_default();
+                self.crew[i].set_path(path);
+            }
+
+            let drag = self.drag_at(self.crew[i].hit_box());
+            let member = &mut self.crew[i];
+            member.follow_path();
+            member.update_pos(drag, delta);
+            let hit_box = self.resolve_collisions(*self.crew[i].hit_box());
+            *self.crew[i].hit_box_mut() = hit_box;
+
+            self.crew_work(i);
         }
-        if self.player.hit_box().top() < 0.0 {
-            let x = self.player.hit_box().x;
-            let y = 0.0;
-            self.player.hit_box_mut().move_to(vec2(x, y));
+    }
+
+    /// Fixes the crew member's box or fills up their kits once they get there.
+    fn crew_work(&mut self, i: usize) {
+        let member = &mut self.crew[i];
+        match member.task() {
+            CrewTask::Repair(index) => {
+                let ebox = &mut self.electrical_boxes[index];
+                if !*ebox.broken() || *member.repair_kits() == 0 {
+                    // someone else got there first
+                    member.set_task(CrewTask::Restock);
+                } else if ebox.fix_hit_box().overlaps(member.hit_box()) {
+                    *ebox.broken_mut() = false;
+                    *member.repair_kits_mut() -= 1;
+                    member.set_task(CrewTask::Restock);
+                    let ebox = *ebox;
+                    self.add_event(GameEvent::CrewFixEBox(i, ebox));
+                }
+            }
+            CrewTask::Restock => {
+                if member.hit_box().overlaps(&self.restock) {
+                    *member.repair_kits_mut() = self.rules.crew_repair_kits;
+                    member.set_task(CrewTask::Idle);
+                }
+            }
+            CrewTask::Idle => {}
         }
-        if self.player.hit_box().right() > self.map_width {
-            let x = self.map_width - self.player.hit_box().w;
-            let y = self.player.hit_box().y;
-            self.player.hit_box_mut().move_to(vec2(x, y));
+    }
+
+    /// Sends the free crew member with the shortest walk to fix the box at `index`.
+    ///
+    /// Returns false if the box isn't broken, someone is already on their way
+    /// to it or nobody with a repair kit is free.
+    pub fn dispatch(&mut self, index: usize) -> bool {
+        let Some(ebox) = self.electrical_boxes.get(index) else {
+            return false;
+        };
+        if !*ebox.broken() || self.crew.iter().any(|member| member.task() == CrewTask::Repair(index)) {
+            return false;
         }
-        if self.player.hit_box().bottom() > self.map_height {
-            let x = self.player.hit_box().x;
-            let y = self.map_height - self.player.hit_box().h;
-            self.player.hit_box_mut().move_to(vec2(x, y));
+
+        let target = rect_center(ebox.hit_box());
+        let closest = self
+            .crew
+            .iter()
+            .enumerate()
+            .filter(|(_, member)| {
+                !matches!(member.task(), CrewTask::Repair(_)) && *member.repair_kits() > 0
+            })
+            .filter_map(|(i, member)| {
+                let from = rect_center(member.hit_box());
+                let path = self.navigation.find_path(from, target, member.hit_box().w)?;
+                Some((i, path_length(from, &path)))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+
+        match closest {
+            Some((i, _)) => {
+                self.crew[i].set_task(CrewTask::Repair(index));
+                true
+            }
+            None => false,
         }
     }
 
+    /// Gives the index of the broken box closest to `point` that no one has been sent to yet.
+    pub fn closest_unassigned_box(&self, point: Vec2) -> Option<usize> {
+        self.electrical_boxes
+            .iter()
+            .enumerate()
+            .filter(|(i, ebox)| {
+                *ebox.broken() && !self.crew.iter().any(|member| member.task() == CrewTask::Repair(*i))
+            })
+            .min_by(|(_, a), (_, b)| {
+                let a = rect_center(a.hit_box()).distance(point);
+                let b = rect_center(b.hit_box()).distance(point);
+                a.total_cmp(&b)
+            })
+            .map(|(i, _)| i)
+    }
+
     fn spawn_puddle(&mut self) {
-        let mut hit_box = Rect::new(
-            gen_range(0.0, self.map_width - 1.0),
-            gen_range(0.0, self.map_height - 1.0),
+        let hit_box = Rect::new(
+            self.rng.range(0.0, self.map_width - 1.0),
+            self.rng.range(0.0, self.map_height - 1.0),
             1.0,
             1.0,
         );
+        self.place_puddle(hit_box);
+    }
 
+    /// Makes a puddle centred on `point`, for the sandbox.
+    pub fn spawn_puddle_at(&mut self, point: Vec2) {
+        self.place_puddle(Rect::new(point.x - 0.5, point.y - 0.5, 1.0, 1.0));
+    }
+
+    /// Dries up every puddle.
+    pub fn clear_puddles(&mut self) {
+        self.puddles.clear();
+    }
+
+    /// Adds a puddle at `hit_box`, moved out of any wall or box it's in.
+    fn place_puddle(&mut self, mut hit_box: Rect) {
         for wall in &self.walls {
             if let Some(v) = aabb_collision(&hit_box, wall.hit_box()) {
                 hit_box.move_to(v);
@@ -270,8 +515,8 @@ impl Game {
 
         self.puddles.push(Puddle::new(
             hit_box,
-            60.0,
-            gen_range(0.0, std::f32::consts::TAU),
+            self.rules.puddle_lifetime,
+            self.rng.range(0.0, std::f32::consts::TAU),
         ));
     }
 
@@ -280,14 +525,44 @@ impl Game {
         &self.generator
     }
 
-    /// Get a reference to the game's player.
-    pub fn player(&self) -> &Player {
-        &self.player
+    /// Get a mutable reference to the game's generator.
+    pub fn generator_mut(&mut self) -> &mut Generator {
+        &mut self.generator
+    }
+
+    /// Get a reference to the game's rules.
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    /// Fills every player's and crew member's repair kits.
+    pub fn restock_everyone(&mut self) {
+        for player in &mut self.players {
+            *player.repair_kits_mut() = self.max_number_of_repair_kits;
+        }
+        for member in &mut self.crew {
+            *member.repair_kits_mut() = self.rules.crew_repair_kits;
+        }
+    }
+
+    /// Get a reference to the game's players.
+    pub fn players(&self) -> &[Player] {
+        self.players.as_ref()
+    }
+
+    /// Get a reference to the game's helper electricians.
+    pub fn crew(&self) -> &[Electrician] {
+        self.crew.as_ref()
+    }
+
+    /// Get a mutable reference to the game's helper electricians.
+    pub fn crew_mut(&mut self) -> &mut [Electrician] {
+        self.crew.as_mut()
     }
 
-    /// Get a mutable reference to the games's player.
-    pub fn player_mut(&mut self) -> &mut Player {
-        &mut self.player
+    /// Get a mutable reference to one of the games's players.
+    pub fn player_mut(&mut self, index: usize) -> &mut Player {
+        &mut self.players[index]
     }
 
     /// Get a reference to the game's walls.
@@ -295,9 +570,31 @@ impl Game {
         self.walls.as_ref()
     }
 
-    /// Get a reference to the game's number of repair kits.
-    pub fn number_of_repair_kits(&self) -> &u32 {
-        &self.number_of_repair_kits
+    /// Get a reference to the game's restock area around the van.
+    pub fn restock(&self) -> &Rect {
+        &self.restock
+    }
+
+    /// Get the game's map width in units.
+    pub fn map_width(&self) -> f32 {
+        self.map_width
+    }
+
+    /// Get the game's map height in units.
+    pub fn map_height(&self) -> f32 {
+        self.map_height
+    }
+
+    /// Finds a way around the walls and electrical boxes for the centre of an
+    /// agent `agent_size` units across, see `NavGrid::find_path`.
+    pub fn find_path(&self, from: Vec2, to: Vec2, agent_size: f32) -> Option<Vec<Vec2>> {
+        self.navigation.find_path(from, to, agent_size)
+    }
+
+    /// Finds the way to whichever of `targets` is the shortest walk from `from`,
+    /// giving its index and the path, see `NavGrid::find_nearest_path`.
+    pub fn find_nearest_path(&self, from: Vec2, targets: &[Vec2], agent_size: f32) -> Option<(usize, Vec<Vec2>)> {
+        self.navigation.find_nearest_path(from, targets, agent_size)
     }
 
     /// Get a reference to  the game's max number of repair kits.
@@ -326,12 +623,18 @@ impl Game {
         self.puddles.as_ref()
     }
 
-    fn which_drag(&self) -> f32 {
-        for puddle in self.puddles() {
-            if puddle.hit_box().overlaps(self.player.hit_box()) {
-                // puddle drag
-                return 0.5;
-            }
+    /// Returns true while the player at `index` is standing in a puddle.
+    pub fn player_in_puddle(&self, index: usize) -> bool {
+        self.puddles()
+            .iter()
+            .any(|puddle| puddle.hit_box().overlaps(self.players[index].hit_box()))
+    }
+
+    /// Gives the drag for anyone standing at `hit_box`, puddles slow everyone down.
+    fn drag_at(&self, hit_box: &Rect) -> f32 {
+        if self.puddles().iter().any(|puddle| puddle.hit_box().overlaps(hit_box)) {
+            // puddle drag
+            return 0.5;
         }
         // defualt drag
         0.75
@@ -345,6 +648,34 @@ impl Game {
         self.event_queue.pop_front()
     }
 
+    /// Breaks the box at `index` as if lightning struck it, for scripted storms.
+    pub fn break_ebox(&mut self, index: usize) {
+        let Some(ebox) = self.electrical_boxes.get_mut(index) else {
+            return;
+        };
+        if *ebox.broken() {
+            return;
+        }
+        *ebox.broken_mut() = true;
+        let ebox = *ebox;
+        self.add_event(GameEvent::DestroyEBox(ebox));
+    }
+
+    /// Fixes the box at `index` without using up anyone's kit, for the sandbox.
+    pub fn fix_ebox(&mut self, index: usize) {
+        if let Some(ebox) = self.electrical_boxes.get_mut(index) {
+            *ebox.broken_mut() = false;
+        }
+    }
+
+    /// Changes how often boxes break and puddles form, starting both timers over.
+    pub fn set_intervals(&mut self, break_interval: (f32, f32), puddle_interval: (f32, f32)) {
+        self.break_timer = RandomTimer::new(break_interval.0, break_interval.1, &mut self.rng);
+        self.puddle_timer = RandomTimer::new(puddle_interval.0, puddle_interval.1, &mut self.rng);
+        self.rules.break_interval = break_interval;
+        self.rules.puddle_interval = puddle_interval;
+    }
+
     fn break_random_ebox(&mut self) {
         let mut active_boxes: Vec<&mut ElectricalBox> =
             Vec::with_capacity(self.electrical_boxes.len());
@@ -359,7 +690,7 @@ impl Game {
             return;
         }
 
-        let index = gen_range(0, active_boxes.len() - 1);
+        let index = self.rng.next_u32() as usize % active_boxes.len();
 
         *active_boxes[index].broken_mut() = true;
 
@@ -392,6 +723,10 @@ fn aabb_collision(first: &Rect, other: &Rect) -> Option<Vec2> {
     }
 }
 
+fn rect_center(rect: &Rect) -> Vec2 {
+    vec2(rect.x + rect.w / 2.0, rect.y + rect.h / 2.0)
+}
+
 pub fn overlaps(first: &Rect, other: &Rect) -> bool {
     first.left() < other.right()
         && first.right() > other.left()
@@ -0,0 +1,138 @@
+use macroquad::math::{vec2, Rect, Vec2};
+
+use super::HitBox;
+
+/// Velocity a crew member adds every update while walking, a bit less than the player.
+const CREW_SPEED: f32 = 1.6;
+
+/// A waypoint counts as reached once a crew member is this close to it.
+const WAYPOINT_RADIUS: f32 = 0.3;
+
+/// Seconds between working out the way again, in case a crew member got pushed off it.
+const REPATH_INTERVAL: f32 = 1.0;
+
+/// What a crew member is busy with.
+#[derive(Clone, Copy, PartialEq)]
+pub enum CrewTask {
+    /// Waiting where they are for the player to send them somewhere.
+    Idle,
+    /// Heading to the electrical box at this index to fix it.
+    Repair(usize),
+    /// Walking back to the van to fill up on repair kits.
+    Restock,
+}
+
+/// A helper electrician who walks to the boxes the player sends them to.
+#[derive(Clone)]
+pub struct Electrician {
+    hit_box: Rect,
+    vel: Vec2,
+    repair_kits: u32,
+    task: CrewTask,
+    /// Points left to walk through, the last one is where the task is done.
+    path: Vec<Vec2>,
+    repath_timer: f32,
+}
+impl Electrician {
+    pub fn new(hit_box: Rect, repair_kits: u32) -> Self {
+        let vel = vec2(0.0, 0.0);
+        Self {
+            hit_box,
+            vel,
+            repair_kits,
+            task: CrewTask::Idle,
+            path: Vec::new(),
+            repath_timer: 0.0,
+        }
+    }
+
+    /// Get a mutable reference to the electrician's hit box.
+    pub fn hit_box_mut(&mut self) -> &mut Rect {
+        &mut self.hit_box
+    }
+
+    /// Get the electrician's velocity.
+    pub fn vel(&self) -> Vec2 {
+        self.vel
+    }
+
+    /// Get a mutable reference to the electrician's velocity.
+    pub fn vel_mut(&mut self) -> &mut Vec2 {
+        &mut self.vel
+    }
+
+    /// Get a reference to the electrician's number of repair kits.
+    pub fn repair_kits(&self) -> &u32 {
+        &self.repair_kits
+    }
+
+    /// Get a mutable reference to the electrician's number of repair kits.
+    pub fn repair_kits_mut(&mut self) -> &mut u32 {
+        &mut self.repair_kits
+    }
+
+    /// Get the electrician's task.
+    pub fn task(&self) -> CrewTask {
+        self.task
+    }
+
+    /// Gives the electrician a new job, the way there is worked out on the next update.
+    pub fn set_task(&mut self, task: CrewTask) {
+        self.task = task;
+        self.path.clear();
+        self.repath_timer = 0.0;
+    }
+
+    /// Returns true when it is time to work out the way to the task again.
+    pub fn needs_path(&mut self, delta: f32) -> bool {
+        self.repath_timer -= delta;
+        if self.repath_timer <= 0.0 {
+            self.repath_timer = REPATH_INTERVAL;
+            return true;
+        }
+        false
+    }
+
+    pub fn set_path(&mut self, path: Vec<Vec2>) {
+        self.path = path;
+    }
+
+    /// Get a reference to the points the electrician still has to walk through.
+    pub fn path(&self) -> &[Vec2] {
+        self.path.as_ref()
+    }
+
+    /// Walks towards the next point on the path, skipping the ones already reached.
+    pub fn follow_path(&mut self) {
+        let center = vec2(
+            self.hit_box.x + self.hit_box.w / 2.0,
+            self.hit_box.y + self.hit_box.h / 2.0,
+        );
+        while let Some(next) = self.path.first() {
+            if center.distance(*next) < WAYPOINT_RADIUS {
+                self.path.remove(0);
+            } else {
+                break;
+            }
+        }
+        if let Some(next) = self.path.first() {
+            let direction = *next - center;
+            if direction.length() > 0.0 {
+                self.vel += direction.normalize() * CREW_SPEED;
+            }
+        }
+    }
+
+    /// Adds velocity to the position with drag, the same as the player.
+    pub fn update_pos(&mut self, drag: f32, delta: f32) {
+        self.vel *= drag;
+        self.hit_box = self.hit_box.offset(self.vel * delta);
+    }
+}
+
+impl HitBox for Electrician {
+    /// Get a reference to the electrician's hit box.
+    fn hit_box(&self) -> &Rect {
+        &self.hit_box
+    }
+}
@@ -1,3 +1,4 @@
+#[derive(Clone)]
 pub struct Generator {
     feul: f32,
     efficiency: f32,
@@ -22,6 +23,16 @@ impl Generator {
         self.feul
     }
 
+    /// Get a mutable reference to the generator's feul.
+    pub fn feul_mut(&mut self) -> &mut f32 {
+        &mut self.feul
+    }
+
+    /// Get the generator's running.
+    pub fn running(&self) -> bool {
+        self.running
+    }
+
     /// Get a mutable reference to the generator's running.
     pub fn running_mut(&mut self) -> &mut bool {
         &mut self.running
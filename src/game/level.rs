@@ -0,0 +1,84 @@
+use macroquad::math::{vec2, Rect, Vec2};
+use serde_json::json;
+
+use crate::PIXELS_PER_UNIT;
+
+/// The layout of a map as described by `map.json`.
+#[derive(Clone)]
+pub struct Level {
+    /// Width and height of the map in world units.
+    pub size: Vec2,
+    pub player: Vec2,
+    pub van: Rect,
+    pub walls: Vec<Rect>,
+    pub electrical_boxes: Vec<Vec2>,
+}
+impl Level {
+    /// The map built into the game.
+    pub fn embedded() -> Self {
+        let map = serde_json::from_slice(include_bytes!("../../map.json")).unwrap();
+        Level::from_json(&map).unwrap()
+    }
+
+    /// Reads a map, saying which entry is wrong instead of panicking so a
+    /// half edited map can't take the game down.
+    pub fn from_json(map: &serde_json::Value) -> Result<Self, String> {
+        // maps from before the size was stored are all as big as the city picture
+        let size = vec2(
+            map["width"].as_f64().map_or(1600.0 / PIXELS_PER_UNIT, |w| w as f32),
+            map["height"].as_f64().map_or(800.0 / PIXELS_PER_UNIT, |h| h as f32),
+        );
+        let player = read_point(&map["player"]).ok_or("player needs an x and y")?;
+        let van = read_rect(&map["van"]).ok_or("van needs an x, y, w and h")?;
+
+        let mut walls = vec![];
+        for (i, wall) in map["walls"].as_array().ok_or("walls is not a list")?.iter().enumerate() {
+            walls.push(read_rect(wall).ok_or(format!("wall {} needs an x, y, w and h", i))?);
+        }
+
+        let mut electrical_boxes = vec![];
+        for (i, ebox) in map["electrical_boxes"]
+            .as_array()
+            .ok_or("electrical_boxes is not a list")?
+            .iter()
+            .enumerate()
+        {
+            electrical_boxes.push(read_point(ebox).ok_or(format!("electrical box {} needs an x and y", i))?);
+        }
+
+        Ok(Self {
+            size,
+            player,
+            van,
+            walls,
+            electrical_boxes,
+        })
+    }
+
+    /// Gives the map in the same form `from_json` reads.
+    pub fn to_json(&self) -> serde_json::Value {
+        let rect = |rect: &Rect| json!({ "x": rect.x, "y": rect.y, "w": rect.w, "h": rect.h });
+        let point = |point: &Vec2| json!({ "x": point.x, "y": point.y });
+        json!({
+            "width": self.size.x,
+            "height": self.size.y,
+            "player": point(&self.player),
+            "van": rect(&self.van),
+            "walls": self.walls.iter().map(rect).collect::<Vec<_>>(),
+            "electrical_boxes": self.electrical_boxes.iter().map(point).collect::<Vec<_>>(),
+        })
+    }
+}
+
+fn read_point(value: &serde_json::Value) -> Option<Vec2> {
+    Some(vec2(value["x"].as_f64()? as f32, value["y"].as_f64()? as f32))
+}
+
+fn read_rect(value: &serde_json::Value) -> Option<Rect> {
+    Some(Rect::new(
+        value["x"].as_f64()? as f32,
+        value["y"].as_f64()? as f32,
+        value["w"].as_f64()? as f32,
+        value["h"].as_f64()? as f32,
+    ))
+}
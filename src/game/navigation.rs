@@ -0,0 +1,394 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashSet, VecDeque},
+};
+
+use macroquad::math::{vec2, Rect, Vec2};
+
+/// Size of a navigation cell in world units, small enough for the narrowest alleys.
+const CELL_SIZE: f32 = 0.5;
+
+/// A grid over the map saying how far every cell is from the nearest obstacle.
+///
+/// Storing the distance instead of just blocked or free lets agents of any size
+/// share one grid, a bigger agent simply needs cells further from the walls.
+#[derive(Clone)]
+pub struct NavGrid {
+    width: usize,
+    height: usize,
+    /// Chebyshev distance in cells to the nearest blocked cell or the map edge,
+    /// 0 for blocked cells.
+    clearance: Vec<u32>,
+}
+impl NavGrid {
+    /// Builds the grid for a map `size` units big with `obstacles` in it.
+    pub fn new(size: Vec2, obstacles: &[Rect]) -> Self {
+        let width = (size.x / CELL_SIZE).ceil().max(1.0) as usize;
+        let height = (size.y / CELL_SIZE).ceil().max(1.0) as usize;
+
+        let mut clearance = vec![u32::MAX; width * height];
+        let mut queue = VecDeque::new();
+        for obstacle in obstacles {
+            let left = (obstacle.left() / CELL_SIZE).floor().max(0.0) as usize;
+            let top = (obstacle.top() / CELL_SIZE).floor().max(0.0) as usize;
+            let right = ((obstacle.right() / CELL_SIZE).ceil() as usize).min(width);
+            let bottom = ((obstacle.bottom() / CELL_SIZE).ceil() as usize).min(height);
+            for y in top..bottom {
+                for x in left..right {
+                    if clearance[y * width + x] != 0 {
+                        clearance[y * width + x] = 0;
+                        queue.push_back((x, y));
+                    }
+                }
+            }
+        }
+
+        // spread outwards from every obstacle at once, the map edge counts as one
+        // cell past the last row and column
+        for y in 0..height {
+            for x in 0..width {
+                let edge = x.min(y).min(width - 1 - x).min(height - 1 - y) as u32 + 1;
+                let cell = &mut clearance[y * width + x];
+                if edge < *cell {
+                    *cell = edge;
+                }
+            }
+        }
+        while let Some((x, y)) = queue.pop_front() {
+            let next = clearance[y * width + x] + 1;
+            for (nx, ny) in NavGrid::neighbours(x, y, width, height) {
+                let cell = &mut clearance[ny * width + nx];
+                if next < *cell {
+                    *cell = next;
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+
+        Self {
+            width,
+            height,
+            clearance,
+        }
+    }
+
+    /// Gives the eight cells around `(x, y)` that are inside the grid.
+    fn neighbours(x: usize, y: usize, width: usize, height: usize) -> impl Iterator<Item = (usize, usize)> {
+        (-1..=1_i32)
+            .flat_map(|dy| (-1..=1_i32).map(move |dx| (dx, dy)))
+            .filter(|&(dx, dy)| dx != 0 || dy != 0)
+            .filter_map(move |(dx, dy)| {
+                let nx = x as i32 + dx;
+                let ny = y as i32 + dy;
+                if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+                    None
+                } else {
+                    Some((nx as usize, ny as usize))
+                }
+            })
+    }
+
+    /// Gives the clearance a cell needs for an agent `agent_size` units across to stand in it.
+    fn needed_clearance(agent_size: f32) -> u32 {
+        // a blocked cell `c` cells away has its near edge `c - 0.5` cells from our centre
+        (agent_size / 2.0 / CELL_SIZE + 0.5).ceil().max(1.0) as u32
+    }
+
+    fn cell_at(&self, point: Vec2) -> (usize, usize) {
+        let x = (point.x / CELL_SIZE).floor().clamp(0.0, (self.width - 1) as f32);
+        let y = (point.y / CELL_SIZE).floor().clamp(0.0, (self.height - 1) as f32);
+        (x as usize, y as usize)
+    }
+
+    fn centre_of(&self, (x, y): (usize, usize)) -> Vec2 {
+        vec2(x as f32 + 0.5, y as f32 + 0.5) * CELL_SIZE
+    }
+
+    fn walkable(&self, (x, y): (usize, usize), needed: u32) -> bool {
+        self.clearance[y * self.width + x] >= needed
+    }
+
+    /// Finds the walkable cells closest to `cell`, all as many steps away, searching outwards.
+    fn nearest_walkable(&self, cell: (usize, usize), needed: u32) -> Vec<(usize, usize)> {
+        if self.walkable(cell, needed) {
+            return vec![cell];
+        }
+        // usually only a few cells are looked at, too few to be worth a whole grid
+        let mut seen = HashSet::from([cell]);
+        let mut ring = vec![cell];
+        while !ring.is_empty() {
+            let mut next_ring = vec![];
+            for (x, y) in ring {
+                for next in NavGrid::neighbours(x, y, self.width, self.height) {
+                    if seen.insert(next) {
+                        next_ring.push(next);
+                    }
+                }
+            }
+            let open: Vec<(usize, usize)> =
+                next_ring.iter().copied().filter(|next| self.walkable(*next, needed)).collect();
+            if !open.is_empty() {
+                return open;
+            }
+            ring = next_ring;
+        }
+        vec![]
+    }
+
+    /// Gives the closest spot to `point` where an agent `agent_size` units across fits,
+    /// `point` itself if it already does.
+    pub fn nearest_open(&self, point: Vec2, agent_size: f32) -> Option<Vec2> {
+        let cell = self.cell_at(point);
+        let open = *self.nearest_walkable(cell, NavGrid::needed_clearance(agent_size)).first()?;
+        if open == cell {
+            Some(point)
+        } else {
+            Some(self.centre_of(open))
+        }
+    }
+
+    /// Finds a path for the centre of an agent `agent_size` units across from
+    /// `from` to `to`, as a list of points ending at `to`.
+    ///
+    /// If `to` is inside something, like an electrical box, the path ends at the
+    /// closest spot the agent can reach instead. Gives `None` when there is no way there.
+    pub fn find_path(&self, from: Vec2, to: Vec2, agent_size: f32) -> Option<Vec<Vec2>> {
+        self.find_nearest_path(from, &[to], agent_size).map(|(_, path)| path)
+    }
+
+    /// Finds the shortest walk from `from` to whichever of `targets` is closest
+    /// to get to, with one search however many targets there are.
+    ///
+    /// Gives the index of that target and the path there, like `find_path`.
+    pub fn find_nearest_path(&self, from: Vec2, targets: &[Vec2], agent_size: f32) -> Option<(usize, Vec<Vec2>)> {
+        let needed = NavGrid::needed_clearance(agent_size);
+        // agents pushed against a wall can stand in a cell that is a little too tight
+        let start = *self.nearest_walkable(self.cell_at(from), needed).first()?;
+        // a target inside something can be reached at any of the closest cells around it
+        let goals: Vec<Vec<(usize, usize)>> = targets
+            .iter()
+            .map(|to| self.nearest_walkable(self.cell_at(*to), needed))
+            .collect();
+        if goals.iter().all(Vec::is_empty) {
+            return None;
+        }
+
+        let index = |(x, y): (usize, usize)| y * self.width + x;
+        // with several goals this is a plain Dijkstra flood, the first goal reached is the closest
+        let heuristic = |(x, y): (usize, usize)| match goals.as_slice() {
+            [goal] => goal
+                .iter()
+                .map(|goal| {
+                    let dx = (x as f32 - goal.0 as f32).abs();
+                    let dy = (y as f32 - goal.1 as f32).abs();
+                    // octile distance, diagonal steps cost the square root of two
+                    dx.max(dy) + (std::f32::consts::SQRT_2 - 1.0) * dx.min(dy)
+                })
+                .fold(f32::INFINITY, f32::min),
+            _ => 0.0,
+        };
+
+        let mut cost = vec![f32::INFINITY; self.width * self.height];
+        let mut came_from = vec![usize::MAX; self.width * self.height];
+        let mut open = BinaryHeap::new();
+        cost[index(start)] = 0.0;
+        open.push(OpenCell {
+            estimate: heuristic(start),
+            cell: start,
+        });
+
+        let mut reached = None;
+        while let Some(OpenCell { estimate, cell }) = open.pop() {
+            if let Some(target) = goals.iter().position(|goal| goal.contains(&cell)) {
+                reached = Some((target, cell));
+                break;
+            }
+            let here = cost[index(cell)];
+            if estimate > here + heuristic(cell) {
+                // a cheaper way here was found after this one was queued
+                continue;
+            }
+            for next in NavGrid::neighbours(cell.0, cell.1, self.width, self.height) {
+                if !self.walkable(next, needed) {
+                    continue;
+                }
+                let diagonal = next.0 != cell.0 && next.1 != cell.1;
+                // don't cut corners, both sides of a diagonal step have to be free
+                if diagonal && (!self.walkable((next.0, cell.1), needed) || !self.walkable((cell.0, next.1), needed)) {
+                    continue;
+                }
+                let step = if diagonal { std::f32::consts::SQRT_2 } else { 1.0 };
+                let next_cost = here + step;
+                if next_cost < cost[index(next)] {
+                    cost[index(next)] = next_cost;
+                    came_from[index(next)] = index(cell);
+                    open.push(OpenCell {
+                        estimate: next_cost + heuristic(next),
+                        cell: next,
+                    });
+                }
+            }
+        }
+
+        let (target, goal) = reached?;
+
+        let mut cells = vec![goal];
+        let mut current = index(goal);
+        while current != index(start) {
+            current = came_from[current];
+            cells.push((current % self.width, current / self.width));
+        }
+        cells.reverse();
+
+        let mut path = self.smooth(&cells, needed);
+        // end exactly where asked if that spot is reachable, otherwise at the closest cell
+        let to = targets[target];
+        let end = if goal == self.cell_at(to) { to } else { self.centre_of(goal) };
+        match path.last_mut() {
+            Some(last) => *last = end,
+            None => path.push(end),
+        }
+        Some((target, path))
+    }
+
+    /// Drops the cells of a path that can be skipped by walking straight past them.
+    fn smooth(&self, cells: &[(usize, usize)], needed: u32) -> Vec<Vec2> {
+        let mut path = Vec::new();
+        let mut anchor = 0;
+        let mut i = 1;
+        while i < cells.len() {
+            if !self.clear_line(cells[anchor], cells[i], needed) {
+                anchor = i - 1;
+                path.push(self.centre_of(cells[anchor]));
+            }
+            i += 1;
+        }
+        path.push(self.centre_of(cells[cells.len() - 1]));
+        path
+    }
+
+    /// Returns true if every cell on the line between `from` and `to` is walkable.
+    fn clear_line(&self, from: (usize, usize), to: (usize, usize), needed: u32) -> bool {
+        let start = self.centre_of(from);
+        let offset = self.centre_of(to) - start;
+        // sample at a quarter cell so the line can't slip through a corner
+        let steps = (offset.length() / CELL_SIZE * 4.0).ceil().max(1.0) as usize;
+        (0..=steps).all(|step| {
+            let point = start + offset * (step as f32 / steps as f32);
+            self.walkable(self.cell_at(point), needed)
+        })
+    }
+}
+
+/// A cell waiting to be explored, ordered so the heap gives the lowest estimate first.
+struct OpenCell {
+    estimate: f32,
+    cell: (usize, usize),
+}
+impl PartialEq for OpenCell {
+    fn eq(&self, other: &Self) -> bool {
+        self.estimate == other.estimate
+    }
+}
+impl Eq for OpenCell {}
+impl PartialOrd for OpenCell {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for OpenCell {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+/// Gives how far it is to walk along `path` starting at `from`.
+pub fn path_length(from: Vec2, path: &[Vec2]) -> f32 {
+    let mut length = 0.0;
+    let mut previous = from;
+    for point in path {
+        length += previous.distance(*point);
+        previous = *point;
+    }
+    length
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AGENT: f32 = 0.5;
+
+    /// Returns true if an agent walking `path` from `from` stays clear of every
+    /// obstacle, give or take the half a cell the grid can't tell apart.
+    fn stays_clear(from: Vec2, path: &[Vec2], obstacles: &[Rect]) -> bool {
+        let mut previous = from;
+        path.iter().all(|point| {
+            let clear = (0..=100).all(|step| {
+                let at = previous.lerp(*point, step as f32 / 100.0);
+                obstacles.iter().all(|obstacle| {
+                    let reach = AGENT / 2.0 - CELL_SIZE / 2.0;
+                    let grown =
+                        Rect::new(obstacle.x - reach, obstacle.y - reach, obstacle.w + reach * 2.0, obstacle.h + reach * 2.0);
+                    // a point exactly on the edge only touches it
+                    !(at.x > grown.left() && at.x < grown.right() && at.y > grown.top() && at.y < grown.bottom())
+                })
+            });
+            previous = *point;
+            clear
+        })
+    }
+
+    #[test]
+    fn a_blocked_corridor_has_no_path() {
+        let grid = NavGrid::new(vec2(10.0, 4.0), &[Rect::new(4.0, 0.0, 2.0, 4.0)]);
+        assert!(grid.find_path(vec2(1.0, 2.0), vec2(8.0, 2.0), AGENT).is_none());
+    }
+
+    #[test]
+    fn diagonals_dont_cut_corners() {
+        // two blocks touching only at a corner leave no way between them
+        let touching = [Rect::new(0.0, 0.0, 5.0, 5.0), Rect::new(5.0, 5.0, 5.0, 5.0)];
+        let grid = NavGrid::new(vec2(10.0, 10.0), &touching);
+        assert!(grid.find_path(vec2(1.0, 8.0), vec2(8.0, 1.0), 0.1).is_none());
+
+        // the way around a corner keeps the agent off it
+        let corner = [Rect::new(2.0, 2.0, 6.0, 6.0)];
+        let grid = NavGrid::new(vec2(10.0, 10.0), &corner);
+        let from = vec2(1.0, 1.0);
+        let path = grid.find_path(from, vec2(9.0, 9.0), AGENT).unwrap();
+        assert!(stays_clear(from, &path, &corner));
+        assert_eq!(*path.last().unwrap(), vec2(9.0, 9.0));
+    }
+
+    #[test]
+    fn a_target_inside_a_box_ends_at_the_closest_cell_reached() {
+        let ebox = Rect::new(5.0, 5.0, 1.0, 1.0);
+        let grid = NavGrid::new(vec2(10.0, 10.0), &[ebox]);
+        let from = vec2(1.0, 5.5);
+        let to = vec2(5.5, 5.5);
+        let path = grid.find_path(from, to, AGENT).unwrap();
+        let end = *path.last().unwrap();
+        assert!(stays_clear(from, &path, &[ebox]));
+        // beside the box, on the side the walk comes from
+        assert!(end.x < to.x && end.distance(to) <= CELL_SIZE * 2.0);
+    }
+
+    #[test]
+    fn the_nearest_target_is_the_shortest_walk_away() {
+        // the first target is close in a straight line but behind a long wall
+        let wall = [Rect::new(2.0, 0.0, 1.0, 9.0)];
+        let grid = NavGrid::new(vec2(20.0, 12.0), &wall);
+        let from = vec2(1.0, 1.0);
+        let targets = [vec2(4.0, 1.0), vec2(1.0, 8.0)];
+        let (index, path) = grid.find_nearest_path(from, &targets, AGENT).unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(*path.last().unwrap(), targets[1]);
+
+        // on its own the first one is still found, the long way round
+        let (index, path) = grid.find_nearest_path(from, &targets[..1], AGENT).unwrap();
+        assert_eq!(index, 0);
+        assert!(path_length(from, &path) > 15.0);
+        assert!(stays_clear(from, &path, &wall));
+    }
+}
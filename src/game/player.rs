@@ -2,14 +2,20 @@ use macroquad::math::{vec2, Rect, Vec2};
 
 use super::HitBox;
 
+#[derive(Clone)]
 pub struct Player {
     hit_box: Rect,
     vel: Vec2,
+    repair_kits: u32,
 }
 impl Player {
-    pub fn new(hit_box: Rect) -> Self {
+    pub fn new(hit_box: Rect, repair_kits: u32) -> Self {
         let vel = vec2(0.0, 0.0);
-        Self { hit_box, vel }
+        Self {
+            hit_box,
+            vel,
+            repair_kits,
+        }
     }
 
     /// Get a mutable reference to the player's hit box.
@@ -23,6 +29,26 @@ impl Player {
         self.hit_box = self.hit_box.offset(self.vel * delta);
     }
 
+    /// Get the player's velocity.
+    pub fn vel(&self) -> Vec2 {
+        self.vel
+    }
+
+    /// Get a mutable reference to the player's velocity.
+    pub fn vel_mut(&mut self) -> &mut Vec2 {
+        &mut self.vel
+    }
+
+    /// Get a reference to the player's number of repair kits.
+    pub fn repair_kits(&self) -> &u32 {
+        &self.repair_kits
+    }
+
+    /// Get a mutable reference to the player's number of repair kits.
+    pub fn repair_kits_mut(&mut self) -> &mut u32 {
+        &mut self.repair_kits
+    }
+
     /// adds current velocity with another velocity
     pub fn add_velocity(&mut self, velocity: Vec2) {
         self.vel += velocity;
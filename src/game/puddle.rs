@@ -2,6 +2,7 @@ use macroquad::math::Rect;
 
 use super::HitBox;
 
+#[derive(Clone)]
 pub struct Puddle {
     hit_box: Rect,
     time_left: f32,
@@ -1,13 +1,14 @@
-use macroquad::rand::gen_range;
+use crate::rng::Rng;
 
+#[derive(Clone)]
 pub struct RandomTimer {
     min_time: f32,
     max_time: f32,
     time_left: f32,
 }
 impl RandomTimer {
-    pub fn new(min_time: f32, max_time: f32) -> Self {
-        let time_left = gen_range(min_time, max_time);
+    pub fn new(min_time: f32, max_time: f32, rng: &mut Rng) -> Self {
+        let time_left = rng.range(min_time, max_time);
         Self {
             min_time,
             max_time,
@@ -19,8 +20,8 @@ impl RandomTimer {
         self.time_left -= delta;
     }
 
-    pub fn reset(&mut self) {
-        self.time_left = gen_range(self.min_time, self.max_time);
+    pub fn reset(&mut self, rng: &mut Rng) {
+        self.time_left = rng.range(self.min_time, self.max_time);
     }
 
     /// Returns bool if current time excides the random time
@@ -0,0 +1,125 @@
+/// Tuning numbers for a run, read from `rules.json`.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Rules {
+    pub generator_fuel: f32,
+    /// Fuel burned per second while the generator runs.
+    pub generator_efficiency: f32,
+    /// Seconds between boxes breaking, picked at random in this range.
+    pub break_interval: (f32, f32),
+    /// Seconds between puddles forming, picked at random in this range.
+    pub puddle_interval: (f32, f32),
+    pub puddle_lifetime: f32,
+    pub max_repair_kits: u32,
+    /// How many helper electricians wait at the van on normal difficulty.
+    pub crew_size: u32,
+    /// Repair kits each helper can carry.
+    pub crew_repair_kits: u32,
+}
+impl Rules {
+    /// The rules built into the game.
+    pub fn embedded() -> Self {
+        Rules::from_json(&serde_json::from_slice(include_bytes!("../../rules.json")).unwrap())
+    }
+
+    /// Reads the rules, anything missing keeps its default.
+    pub fn from_json(rules: &serde_json::Value) -> Self {
+        let default = Rules::default();
+        let number = |key: &str, default: f32| rules[key].as_f64().map_or(default, |n| n as f32);
+        let range = |key: &str, default: (f32, f32)| {
+            (
+                rules[key][0].as_f64().map_or(default.0, |n| n as f32),
+                rules[key][1].as_f64().map_or(default.1, |n| n as f32),
+            )
+        };
+
+        let count = |key: &str, default: u32| rules[key].as_u64().map_or(default, |n| n as u32);
+
+        Self {
+            generator_fuel: number("generator_fuel", default.generator_fuel),
+            generator_efficiency: number("generator_efficiency", default.generator_efficiency),
+            break_interval: range("break_interval", default.break_interval),
+            puddle_interval: range("puddle_interval", default.puddle_interval),
+            puddle_lifetime: number("puddle_lifetime", default.puddle_lifetime),
+            max_repair_kits: count("max_repair_kits", default.max_repair_kits),
+            crew_size: count("crew_size", default.crew_size),
+            crew_repair_kits: count("crew_repair_kits", default.crew_repair_kits),
+        }
+    }
+
+    /// Gives these rules with whatever `changes` sets instead, in the form `from_json` reads.
+    pub fn with_changes(self, changes: &serde_json::Value) -> Self {
+        let mut rules = self.to_json();
+        if let (Some(rules), Some(changes)) = (rules.as_object_mut(), changes.as_object()) {
+            for (key, change) in changes {
+                rules.insert(key.clone(), change.clone());
+            }
+        }
+        Rules::from_json(&rules)
+    }
+
+    /// Gives the rules in the same form `from_json` reads.
+    pub fn to_json(self) -> serde_json::Value {
+        serde_json::json!({
+            "generator_fuel": self.generator_fuel,
+            "generator_efficiency": self.generator_efficiency,
+            "break_interval": [self.break_interval.0, self.break_interval.1],
+            "puddle_interval": [self.puddle_interval.0, self.puddle_interval.1],
+            "puddle_lifetime": self.puddle_lifetime,
+            "max_repair_kits": self.max_repair_kits,
+            "crew_size": self.crew_size,
+            "crew_repair_kits": self.crew_repair_kits,
+        })
+    }
+}
+/// How hard endless runs are, picked in the settings.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Difficulty::ALL.into_iter().find(|difficulty| difficulty.name() == name)
+    }
+
+    /// Gives `rules` made easier or harder: an easy storm breaks boxes less often
+    /// and brings one more helper, a hard one breaks them more often with one fewer.
+    pub fn apply(&self, rules: Rules) -> Rules {
+        let (interval, crew_size) = match self {
+            Difficulty::Easy => (1.25, rules.crew_size + 1),
+            Difficulty::Normal => (1.0, rules.crew_size),
+            Difficulty::Hard => (0.8, rules.crew_size.saturating_sub(1)),
+        };
+        Rules {
+            break_interval: (rules.break_interval.0 * interval, rules.break_interval.1 * interval),
+            crew_size,
+            ..rules
+        }
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            generator_fuel: 1.0,
+            generator_efficiency: 0.1,
+            break_interval: (3.0, 5.0),
+            puddle_interval: (0.1, 1.0),
+            puddle_lifetime: 60.0,
+            max_repair_kits: 5,
+            crew_size: 2,
+            crew_repair_kits: 2,
+        }
+    }
+}
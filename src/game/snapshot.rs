@@ -0,0 +1,183 @@
+//! The state of a run as json, sent from the server to everyone playing over the network.
+//!
+//! Only what changes during a run is sent, clients build the same walls and
+//! boxes from the map the server hands them when they join.
+
+use macroquad::math::{vec2, Rect, Vec2};
+use serde_json::{json, Value};
+
+use super::{CrewTask, ElectricalBox, Game, GameEvent, Generator, HitBox, Puddle};
+
+impl Game {
+    /// Gives everything a client needs to show the run as it is now.
+    pub fn snapshot(&self) -> Value {
+        let players: Vec<Value> = self
+            .players
+            .iter()
+            .map(|player| {
+                json!({
+                    "pos": point_json(player.hit_box().point()),
+                    "vel": point_json(player.vel()),
+                    "kits": player.repair_kits(),
+                })
+            })
+            .collect();
+        let crew: Vec<Value> = self
+            .crew
+            .iter()
+            .map(|member| {
+                json!({
+                    "pos": point_json(member.hit_box().point()),
+                    "vel": point_json(member.vel()),
+                    "kits": member.repair_kits(),
+                    "task": task_json(member.task()),
+                    "path": member.path().iter().map(|point| point_json(*point)).collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        let puddles: Vec<Value> = self
+            .puddles
+            .iter()
+            .map(|puddle| {
+                let hit_box = puddle.hit_box();
+                json!({
+                    "rect": [hit_box.x, hit_box.y, hit_box.w, hit_box.h],
+                    "time_left": puddle.time_left(),
+                    "rotation": puddle.rotation,
+                })
+            })
+            .collect();
+
+        json!({
+            "fuel": self.generator.feul(),
+            "generator_running": self.generator.running(),
+            "players": players,
+            "crew": crew,
+            "broken": self.electrical_boxes.iter().map(|ebox| *ebox.broken()).collect::<Vec<_>>(),
+            "puddles": puddles,
+        })
+    }
+
+    /// Takes on the state in a server `snapshot`, then queues its `events` as
+    /// if they had happened here so they are heard and seen the same way.
+    ///
+    /// Anything missing or out of range is left as it was.
+    pub fn apply_snapshot(&mut self, snapshot: &Value, events: &[Value]) {
+        if let Some(fuel) = snapshot["fuel"].as_f64() {
+            let running = snapshot["generator_running"].as_bool().unwrap_or(self.generator.running());
+            self.generator = Generator::new(fuel as f32, self.rules.generator_efficiency, running);
+        }
+
+        for (player, value) in self.players.iter_mut().zip(array(&snapshot["players"])) {
+            if let Some(pos) = read_point(&value["pos"]) {
+                player.hit_box_mut().move_to(pos);
+            }
+            if let Some(vel) = read_point(&value["vel"]) {
+                *player.vel_mut() = vel;
+            }
+            if let Some(kits) = value["kits"].as_u64() {
+                *player.repair_kits_mut() = kits as u32;
+            }
+        }
+
+        for (member, value) in self.crew.iter_mut().zip(array(&snapshot["crew"])) {
+            if let Some(pos) = read_point(&value["pos"]) {
+                member.hit_box_mut().move_to(pos);
+            }
+            if let Some(vel) = read_point(&value["vel"]) {
+                *member.vel_mut() = vel;
+            }
+            if let Some(kits) = value["kits"].as_u64() {
+                *member.repair_kits_mut() = kits as u32;
+            }
+            if let Some(task) = read_task(&value["task"]) {
+                member.set_task(task);
+            }
+            member.set_path(array(&value["path"]).iter().filter_map(read_point).collect());
+        }
+
+        for (ebox, broken) in self.electrical_boxes.iter_mut().zip(array(&snapshot["broken"])) {
+            if let Some(broken) = broken.as_bool() {
+                *ebox.broken_mut() = broken;
+            }
+        }
+
+        if let Some(puddles) = snapshot["puddles"].as_array() {
+            self.puddles = puddles
+                .iter()
+                .filter_map(|value| {
+                    let rect = array(&value["rect"]);
+                    let number = |i: usize| rect.get(i)?.as_f64().map(|n| n as f32);
+                    Some(Puddle::new(
+                        Rect::new(number(0)?, number(1)?, number(2)?, number(3)?),
+                        value["time_left"].as_f64()? as f32,
+                        value["rotation"].as_f64()? as f32,
+                    ))
+                })
+                .collect();
+        }
+
+        for event in events {
+            if let Some(event) = self.event_from_json(event) {
+                self.add_event(event);
+            }
+        }
+    }
+
+    /// Gives an event as json, boxes are sent by their index in the map.
+    pub fn event_json(&self, event: &GameEvent) -> Value {
+        let box_index = |ebox: &ElectricalBox| {
+            self.electrical_boxes
+                .iter()
+                .position(|other| other.hit_box().point() == ebox.hit_box().point())
+        };
+        match event {
+            GameEvent::Restock(player) => json!({ "type": "restock", "player": player }),
+            GameEvent::FixEBox(player, ebox) => json!({ "type": "fix", "player": player, "box": box_index(ebox) }),
+            GameEvent::CrewFixEBox(member, ebox) => {
+                json!({ "type": "crew_fix", "member": member, "box": box_index(ebox) })
+            }
+            GameEvent::DestroyEBox(ebox) => json!({ "type": "destroy", "box": box_index(ebox) }),
+        }
+    }
+
+    fn event_from_json(&self, event: &Value) -> Option<GameEvent> {
+        let index = |key: &str| event[key].as_u64().map(|n| n as usize);
+        let ebox = || self.electrical_boxes.get(index("box")?).copied();
+        match event["type"].as_str()? {
+            "restock" => Some(GameEvent::Restock(index("player")?)),
+            "fix" => Some(GameEvent::FixEBox(index("player")?, ebox()?)),
+            "crew_fix" => Some(GameEvent::CrewFixEBox(index("member")?, ebox()?)),
+            "destroy" => Some(GameEvent::DestroyEBox(ebox()?)),
+            _ => None,
+        }
+    }
+}
+
+fn array(value: &Value) -> &[Value] {
+    value.as_array().map_or(&[], |array| array.as_slice())
+}
+
+fn point_json(point: Vec2) -> Value {
+    json!([point.x, point.y])
+}
+
+fn read_point(value: &Value) -> Option<Vec2> {
+    Some(vec2(value[0].as_f64()? as f32, value[1].as_f64()? as f32))
+}
+
+fn task_json(task: CrewTask) -> Value {
+    match task {
+        CrewTask::Idle => json!("idle"),
+        CrewTask::Restock => json!("restock"),
+        CrewTask::Repair(index) => json!(index),
+    }
+}
+
+fn read_task(value: &Value) -> Option<CrewTask> {
+    match value.as_str() {
+        Some("idle") => Some(CrewTask::Idle),
+        Some("restock") => Some(CrewTask::Restock),
+        _ => value.as_u64().map(|index| CrewTask::Repair(index as usize)),
+    }
+}
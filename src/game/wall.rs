@@ -2,6 +2,7 @@ use macroquad::math::Rect;
 
 use super::HitBox;
 
+#[derive(Clone)]
 pub struct Wall {
     hit_box: Rect,
 }
@@ -0,0 +1,221 @@
+//! Controller support through gilrs.
+//!
+//! gilrs can't talk to the browser from a macroquad wasm build, so there the
+//! gamepads are simply never connected.
+
+use crate::input::ActionState;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use gilrs::ff::{BaseEffect, BaseEffectType, Effect, EffectBuilder, Replay, Ticks};
+    use gilrs::{Axis, Button, EventType, Gamepad, GamepadId, Gilrs};
+    use macroquad::math::vec2;
+
+    use super::ActionState;
+    use crate::input::Action;
+
+    /// How far the stick has to be pushed before the player moves.
+    const STICK_DEAD_ZONE: f32 = 0.2;
+
+    /// How far past the dead zone the stick has to be pushed to run instead of walk.
+    const RUN_TILT: f32 = 0.8;
+
+    const BUTTONS: [(Button, Action); 17] = [
+        (Button::DPadUp, Action::MoveUp),
+        (Button::DPadDown, Action::MoveDown),
+        (Button::DPadLeft, Action::MoveLeft),
+        (Button::DPadRight, Action::MoveRight),
+        (Button::RightTrigger2, Action::Sprint),
+        (Button::RightTrigger, Action::Sprint),
+        (Button::North, Action::ToggleMinimap),
+        (Button::West, Action::Dispatch),
+        (Button::Start, Action::Pause),
+        (Button::East, Action::Pause),
+        (Button::South, Action::Confirm),
+        // replays have nobody to move, so the same buttons play them back
+        (Button::South, Action::PlayPause),
+        (Button::LeftTrigger, Action::SkipBack),
+        (Button::RightTrigger, Action::SkipForward),
+        (Button::LeftTrigger2, Action::Slower),
+        (Button::RightTrigger2, Action::Faster),
+        (Button::Select, Action::FreeCamera),
+    ];
+
+    /// Co-op players sharing the keyboard, gamepads go to the players after them.
+    const KEYBOARD_PLAYERS: usize = 2;
+
+    pub struct Gamepads {
+        gilrs: Option<Gilrs>,
+        active: Option<GamepadId>,
+        /// Gamepads in the order they joined, the first is the first player after the keyboard's.
+        seats: Vec<GamepadId>,
+        /// Buttons pressed this frame and on which gamepad, for telling co-op players apart.
+        pressed: Vec<(GamepadId, Button)>,
+        /// Rumble effects that are still playing with their time left, they stop when dropped.
+        effects: Vec<(Effect, f32)>,
+    }
+    impl Gamepads {
+        pub fn new() -> Self {
+            Self {
+                gilrs: Gilrs::new().ok(),
+                active: None,
+                seats: Vec::new(),
+                pressed: Vec::new(),
+                effects: Vec::new(),
+            }
+        }
+
+        /// Adds the buttons and stick of the last used gamepad to the actions.
+        pub fn update(&mut self, actions: &mut ActionState, delta: f32) {
+            self.effects.retain_mut(|(_, time_left)| {
+                *time_left -= delta;
+                *time_left > 0.0
+            });
+
+            let gilrs = match &mut self.gilrs {
+                Some(gilrs) => gilrs,
+                None => return,
+            };
+
+            self.pressed.clear();
+            while let Some(event) = gilrs.next_event() {
+                self.active = Some(event.id);
+                if let EventType::ButtonPressed(button, _) = event.event {
+                    self.pressed.push((event.id, button));
+                    for (bound, action) in BUTTONS {
+                        if bound == button {
+                            actions.press(action);
+                        }
+                    }
+                }
+            }
+
+            for (id, _) in gilrs.gamepads() {
+                if self.seats.contains(&id) {
+                    continue;
+                }
+                // a new gamepad takes the seat of one that was unplugged, nobody else's changes
+                match self.seats.iter().position(|seat| gilrs.connected_gamepad(*seat).is_none()) {
+                    Some(seat) => self.seats[seat] = id,
+                    None => self.seats.push(id),
+                }
+            }
+
+            let gamepad = match self.active {
+                Some(id) => gilrs.gamepad(id),
+                None => return,
+            };
+            if !gamepad.is_connected() {
+                self.active = None;
+                return;
+            }
+            read_held(gamepad, actions);
+        }
+
+        /// Adds the gamepad for co-op player `slot` to their actions. The players
+        /// on the keyboard have none, the gamepads go to the players after them in
+        /// the order they joined.
+        ///
+        /// Call after `update`, which reads this frame's button presses.
+        pub fn update_player(&self, slot: usize, actions: &mut ActionState) {
+            let gilrs = match &self.gilrs {
+                Some(gilrs) => gilrs,
+                None => return,
+            };
+            let seat = match slot.checked_sub(KEYBOARD_PLAYERS) {
+                Some(seat) => seat,
+                None => return,
+            };
+            let id = match self.seats.get(seat) {
+                Some(id) => *id,
+                None => return,
+            };
+            let gamepad = match gilrs.connected_gamepad(id) {
+                Some(gamepad) => gamepad,
+                None => return,
+            };
+
+            for (pressed_id, button) in &self.pressed {
+                for (bound, action) in BUTTONS {
+                    if *pressed_id == id && bound == *button {
+                        actions.press(action);
+                    }
+                }
+            }
+            read_held(gamepad, actions);
+        }
+
+        /// Shakes the active gamepad, `strength` goes from 0.0 to 1.0.
+        pub fn rumble(&mut self, strength: f32, duration: f32) {
+            let (gilrs, id) = match (&mut self.gilrs, self.active) {
+                (Some(gilrs), Some(id)) => (gilrs, id),
+                _ => return,
+            };
+            if !gilrs.gamepad(id).is_ff_supported() {
+                return;
+            }
+
+            let magnitude = (strength.clamp(0.0, 1.0) * u16::MAX as f32) as u16;
+            let effect = EffectBuilder::new()
+                .add_effect(BaseEffect {
+                    kind: BaseEffectType::Strong { magnitude },
+                    scheduling: Replay {
+                        play_for: Ticks::from_ms((duration * 1000.0) as u32),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .gamepads(&[id])
+                .finish(gilrs);
+
+            if let Ok(effect) = effect {
+                if effect.play().is_ok() {
+                    self.effects.push((effect, duration));
+                }
+            }
+        }
+    }
+
+    /// Adds the buttons held and the stick of `gamepad` to the actions.
+    fn read_held(gamepad: Gamepad, actions: &mut ActionState) {
+        for (button, action) in BUTTONS {
+            if gamepad.is_pressed(button) {
+                actions.hold(action);
+            }
+        }
+
+        let stick = vec2(
+            gamepad.value(Axis::LeftStickX),
+            gamepad.value(Axis::LeftStickY),
+        );
+        if stick.length() > STICK_DEAD_ZONE {
+            // measured from the edge of the dead zone, so walking starts slow instead of at a fifth of the speed
+            let tilt = ((stick.length() - STICK_DEAD_ZONE) / (1.0 - STICK_DEAD_ZONE)).min(1.0);
+            if tilt >= RUN_TILT {
+                actions.set_analog_movement(stick.normalize());
+                actions.hold(Action::Sprint);
+            } else {
+                actions.set_analog_movement(stick.normalize() * tilt / RUN_TILT);
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::Gamepads;
+
+#[cfg(target_arch = "wasm32")]
+pub struct Gamepads;
+
+#[cfg(target_arch = "wasm32")]
+impl Gamepads {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn update(&mut self, _actions: &mut ActionState, _delta: f32) {}
+
+    pub fn update_player(&self, _slot: usize, _actions: &mut ActionState) {}
+
+    pub fn rumble(&mut self, _strength: f32, _duration: f32) {}
+}
@@ -0,0 +1,129 @@
+//! Development mode, started with `--dev`, that reloads files as they are saved.
+
+pub const MAP_PATH: &str = "map.json";
+pub const RULES_PATH: &str = "rules.json";
+pub const CAMPAIGN_PATH: &str = "campaign.json";
+
+/// What has to be rebuilt after files changed.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Reload {
+    /// The map, the rules or the campaign.
+    Level,
+    Assets,
+}
+
+/// Returns true if the game was started with `--dev`.
+pub fn dev_mode() -> bool {
+    std::env::args().any(|arg| arg == "--dev")
+}
+
+/// Reads and parses a json file from disk.
+pub fn read_json(path: &str) -> Result<serde_json::Value, String> {
+    let text = read(path)?;
+    serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read(path: &str) -> Result<String, String> {
+    Err(format!("{}: can't read files in the browser", path))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod watcher {
+    use std::{collections::HashMap, path::PathBuf, time::SystemTime};
+
+    use super::{Reload, CAMPAIGN_PATH, MAP_PATH, RULES_PATH};
+
+    /// Checking the disk every frame is wasteful, a few times a second feels instant.
+    const CHECK_INTERVAL: f32 = 0.5;
+
+    /// Watches the map, the rules, the campaign and everything in `assets` for changes.
+    pub struct HotReload {
+        modified: HashMap<PathBuf, SystemTime>,
+        time_until_check: f32,
+    }
+    impl HotReload {
+        pub fn new() -> Self {
+            let mut hot_reload = Self {
+                modified: HashMap::new(),
+                time_until_check: CHECK_INTERVAL,
+            };
+            hot_reload.modified = hot_reload.scan();
+            hot_reload
+        }
+
+        fn scan(&self) -> HashMap<PathBuf, SystemTime> {
+            let mut paths = vec![
+                PathBuf::from(MAP_PATH),
+                PathBuf::from(RULES_PATH),
+                PathBuf::from(CAMPAIGN_PATH),
+            ];
+            if let Ok(entries) = std::fs::read_dir("assets") {
+                paths.extend(entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()));
+            }
+
+            paths
+                .into_iter()
+                .filter_map(|path| {
+                    let modified = std::fs::metadata(&path).ok()?.modified().ok()?;
+                    Some((path, modified))
+                })
+                .collect()
+        }
+
+        /// Gives what needs reloading since the last call, checking every so often.
+        pub fn poll(&mut self, delta: f32) -> Vec<Reload> {
+            self.time_until_check -= delta;
+            if self.time_until_check > 0.0 {
+                return vec![];
+            }
+            self.time_until_check = CHECK_INTERVAL;
+
+            let modified = self.scan();
+            let mut reloads = vec![];
+            let changed = modified
+                .iter()
+                .filter(|(path, time)| self.modified.get(*path) != Some(time))
+                .map(|(path, _)| path)
+                // deleted files count too, the loader will say they are missing
+                .chain(self.modified.keys().filter(|path| !modified.contains_key(*path)));
+            for path in changed {
+                let reload = if path.starts_with("assets") {
+                    Reload::Assets
+                } else {
+                    Reload::Level
+                };
+                if !reloads.contains(&reload) {
+                    reloads.push(reload);
+                }
+            }
+
+            self.modified = modified;
+            reloads
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod watcher {
+    use super::Reload;
+
+    /// The browser can't see the files, so there is nothing to watch.
+    pub struct HotReload;
+    impl HotReload {
+        pub fn new() -> Self {
+            Self
+        }
+
+        pub fn poll(&mut self, _delta: f32) -> Vec<Reload> {
+            vec![]
+        }
+    }
+}
+
+pub use watcher::HotReload;
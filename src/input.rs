@@ -0,0 +1,375 @@
+use macroquad::prelude::*;
+use serde_json::{json, Map, Value};
+
+/// Everything the player can ask the game to do, independent of which key does it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Sprint,
+    ToggleMinimap,
+    ZoomIn,
+    ZoomOut,
+    Dispatch,
+    Pause,
+    Confirm,
+    PlayPause,
+    Slower,
+    Faster,
+    SkipBack,
+    SkipForward,
+    FreeCamera,
+}
+impl Action {
+    pub const ALL: [Action; 17] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Sprint,
+        Action::ToggleMinimap,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::Dispatch,
+        Action::Pause,
+        Action::Confirm,
+        Action::PlayPause,
+        Action::Slower,
+        Action::Faster,
+        Action::SkipBack,
+        Action::SkipForward,
+        Action::FreeCamera,
+    ];
+
+    /// Gives the name shown in the controls menu.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveUp => "Move up",
+            Action::MoveDown => "Move down",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::Sprint => "Sprint",
+            Action::ToggleMinimap => "Toggle map",
+            Action::ZoomIn => "Zoom in",
+            Action::ZoomOut => "Zoom out",
+            Action::Dispatch => "Send helper",
+            Action::Pause => "Pause / Back",
+            Action::Confirm => "Confirm",
+            Action::PlayPause => "Replay: play / pause",
+            Action::Slower => "Replay: slower",
+            Action::Faster => "Replay: faster",
+            Action::SkipBack => "Replay: skip back",
+            Action::SkipForward => "Replay: skip forward",
+            Action::FreeCamera => "Replay: free camera",
+        }
+    }
+
+    fn default_keys(&self) -> Vec<KeyCode> {
+        match self {
+            Action::MoveUp => vec![KeyCode::W, KeyCode::Up],
+            Action::MoveDown => vec![KeyCode::S, KeyCode::Down],
+            Action::MoveLeft => vec![KeyCode::A, KeyCode::Left],
+            Action::MoveRight => vec![KeyCode::D, KeyCode::Right],
+            Action::Sprint => vec![KeyCode::LeftShift],
+            Action::ToggleMinimap => vec![KeyCode::M],
+            Action::ZoomIn => vec![KeyCode::Equal],
+            Action::ZoomOut => vec![KeyCode::Minus],
+            Action::Dispatch => vec![KeyCode::E],
+            Action::Pause => vec![KeyCode::Escape],
+            Action::Confirm => vec![KeyCode::Enter],
+            Action::PlayPause => vec![KeyCode::Space],
+            Action::Slower => vec![KeyCode::Comma],
+            Action::Faster => vec![KeyCode::Period],
+            Action::SkipBack => vec![KeyCode::LeftBracket],
+            Action::SkipForward => vec![KeyCode::RightBracket],
+            Action::FreeCamera => vec![KeyCode::F],
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// Keys that can be bound to an action.
+#[rustfmt::skip]
+const BINDABLE_KEYS: &[KeyCode] = &[
+    KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G,
+    KeyCode::H, KeyCode::I, KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N,
+    KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R, KeyCode::S, KeyCode::T, KeyCode::U,
+    KeyCode::V, KeyCode::W, KeyCode::X, KeyCode::Y, KeyCode::Z,
+    KeyCode::Key0, KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4,
+    KeyCode::Key5, KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9,
+    KeyCode::Kp0, KeyCode::Kp1, KeyCode::Kp2, KeyCode::Kp3, KeyCode::Kp4,
+    KeyCode::Kp5, KeyCode::Kp6, KeyCode::Kp7, KeyCode::Kp8, KeyCode::Kp9,
+    KeyCode::KpEnter,
+    KeyCode::Up, KeyCode::Down, KeyCode::Left, KeyCode::Right,
+    KeyCode::Space, KeyCode::Enter, KeyCode::Escape, KeyCode::Tab, KeyCode::Backspace,
+    KeyCode::LeftShift, KeyCode::RightShift, KeyCode::LeftControl, KeyCode::RightControl,
+    KeyCode::LeftAlt, KeyCode::RightAlt,
+    KeyCode::Comma, KeyCode::Period, KeyCode::Slash, KeyCode::Semicolon,
+    KeyCode::Apostrophe, KeyCode::Minus, KeyCode::Equal,
+    KeyCode::LeftBracket, KeyCode::RightBracket, KeyCode::Backslash,
+];
+
+pub fn key_name(key: KeyCode) -> String {
+    format!("{:?}", key)
+}
+
+fn key_from_name(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS
+        .iter()
+        .copied()
+        .find(|key| key_name(*key) == name)
+}
+
+/// Which keys trigger which action.
+pub struct Bindings {
+    keys: Vec<Vec<KeyCode>>,
+}
+impl Bindings {
+    /// Get a reference to the keys bound to an action.
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        &self.keys[action.index()]
+    }
+
+    /// Makes `key` the main key for `action`, keeping its other keys, and gives
+    /// whether `key` can be bound at all.
+    ///
+    /// If another action used the key it gets this action's old key instead, so
+    /// no action is ever left without a key.
+    pub fn bind(&mut self, action: Action, key: KeyCode) -> bool {
+        if !BINDABLE_KEYS.contains(&key) {
+            return false;
+        }
+        let old_key = self.keys[action.index()].first().copied();
+
+        for other in Action::ALL {
+            if other == action {
+                continue;
+            }
+            let keys = &mut self.keys[other.index()];
+            if let Some(position) = keys.iter().position(|k| *k == key) {
+                keys.remove(position);
+                if keys.is_empty() {
+                    if let Some(old_key) = old_key {
+                        keys.push(old_key);
+                    }
+                }
+            }
+        }
+
+        let keys = &mut self.keys[action.index()];
+        match keys.iter().position(|k| *k == key) {
+            // already one of its other keys, it swaps places with the main one
+            Some(position) => keys.swap(0, position),
+            None if keys.is_empty() => keys.push(key),
+            None => keys[0] = key,
+        }
+        true
+    }
+
+    /// The keys of the second player sharing the keyboard in co-op, they can't be rebound.
+    pub fn second_keyboard() -> Self {
+        let keys = Action::ALL
+            .iter()
+            .map(|action| match action {
+                Action::MoveUp => vec![KeyCode::Up],
+                Action::MoveDown => vec![KeyCode::Down],
+                Action::MoveLeft => vec![KeyCode::Left],
+                Action::MoveRight => vec![KeyCode::Right],
+                Action::Sprint => vec![KeyCode::RightShift],
+                Action::Dispatch => vec![KeyCode::RightControl],
+                _ => vec![],
+            })
+            .collect();
+        Self { keys }
+    }
+
+    /// Gives every key bound to any action.
+    pub fn all_keys(&self) -> Vec<KeyCode> {
+        self.keys.iter().flatten().copied().collect()
+    }
+
+    /// Gives a short description of the keys bound to an action, like `W / Up`.
+    pub fn describe(&self, action: Action) -> String {
+        let names: Vec<String> = self.keys(action).iter().map(|k| key_name(*k)).collect();
+        names.join(" / ")
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut map = Map::new();
+        for action in Action::ALL {
+            let names: Vec<String> = self.keys(action).iter().map(|k| key_name(*k)).collect();
+            map.insert(format!("{:?}", action), json!(names));
+        }
+        Value::Object(map)
+    }
+
+    /// Reads bindings saved by `to_json`, keeping the default for any action that is missing.
+    pub fn from_json(value: &Value) -> Self {
+        let mut bindings = Bindings::default();
+        for action in Action::ALL {
+            if let Some(names) = value[format!("{:?}", action)].as_array() {
+                let keys: Vec<KeyCode> = names
+                    .iter()
+                    .filter_map(|name| name.as_str())
+                    .filter_map(key_from_name)
+                    .collect();
+                if !keys.is_empty() {
+                    bindings.keys[action.index()] = keys;
+                }
+            }
+        }
+        bindings
+    }
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let keys = Action::ALL.iter().map(|a| a.default_keys()).collect();
+        Self { keys }
+    }
+}
+
+/// What a player pressed that changes the game, for sending over the network and
+/// recording replays.
+///
+/// Players are always driven through `actions` so a replay moves them exactly
+/// the same way, down to the last bit.
+#[derive(Clone, Copy, Default)]
+pub struct PlayerInput {
+    pub movement: Vec2,
+    pub sprint: bool,
+    pub dispatch: bool,
+    /// A broken box picked on the map to send a helper to.
+    pub target: Option<usize>,
+}
+impl PlayerInput {
+    pub fn from_actions(actions: &ActionState) -> Self {
+        Self {
+            movement: actions.movement(),
+            sprint: actions.is_down(Action::Sprint),
+            dispatch: actions.is_pressed(Action::Dispatch),
+            target: actions.dispatch_target(),
+        }
+    }
+
+    /// Gives actions that press what this input pressed.
+    pub fn actions(&self) -> ActionState {
+        let mut actions = ActionState::default();
+        actions.set_analog_movement(self.movement);
+        if self.sprint {
+            actions.hold(Action::Sprint);
+        }
+        if self.dispatch {
+            actions.press(Action::Dispatch);
+        }
+        actions.set_dispatch_target(self.target);
+        actions
+    }
+}
+
+/// The actions the player is doing this frame.
+#[derive(Clone, Default)]
+pub struct ActionState {
+    down: [bool; Action::ALL.len()],
+    pressed: [bool; Action::ALL.len()],
+    analog_movement: Vec2,
+    /// A broken box picked on the map this frame to send a helper to.
+    dispatch_target: Option<usize>,
+}
+impl ActionState {
+    /// Reads the keyboard through the player's bindings.
+    pub fn from_keyboard(bindings: &Bindings) -> Self {
+        ActionState::from_keyboard_without(bindings, &[])
+    }
+
+    /// Reads the keyboard through the bindings, ignoring `taken` keys that
+    /// belong to someone else sharing the keyboard.
+    pub fn from_keyboard_without(bindings: &Bindings, taken: &[KeyCode]) -> Self {
+        let mut state = ActionState::default();
+        for action in Action::ALL {
+            for key in bindings.keys(action).iter().filter(|key| !taken.contains(key)) {
+                if is_key_down(*key) {
+                    state.down[action.index()] = true;
+                }
+                if is_key_pressed(*key) {
+                    state.pressed[action.index()] = true;
+                }
+            }
+        }
+        state
+    }
+
+    /// Returns true while the action is held.
+    pub fn is_down(&self, action: Action) -> bool {
+        self.down[action.index()]
+    }
+
+    /// Returns true on the frame the action started.
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.pressed[action.index()]
+    }
+
+    /// Marks the action as held this frame.
+    pub fn hold(&mut self, action: Action) {
+        self.down[action.index()] = true;
+    }
+
+    /// Marks the action as started this frame.
+    pub fn press(&mut self, action: Action) {
+        self.pressed[action.index()] = true;
+        self.down[action.index()] = true;
+    }
+
+    /// Sets the box picked on the map to send a helper to this frame.
+    pub fn set_dispatch_target(&mut self, target: Option<usize>) {
+        self.dispatch_target = target;
+    }
+
+    /// Get the box picked on the map to send a helper to this frame.
+    pub fn dispatch_target(&self) -> Option<usize> {
+        self.dispatch_target
+    }
+
+    /// Sets movement from an analog source like a stick, its length is how fast to go.
+    pub fn set_analog_movement(&mut self, movement: Vec2) {
+        self.analog_movement = movement;
+    }
+
+    /// Gives the direction the player wants to move in with a length of at most 1.0.
+    ///
+    /// Digital movement is always full speed, analog movement keeps its strength.
+    pub fn movement(&self) -> Vec2 {
+        let digital = self.digital_movement();
+        if digital != Vec2::ZERO {
+            return digital.normalize();
+        }
+
+        if self.analog_movement.length() > 1.0 {
+            self.analog_movement.normalize()
+        } else {
+            self.analog_movement
+        }
+    }
+
+    fn digital_movement(&self) -> Vec2 {
+        let mut movement = vec2(0.0, 0.0);
+        if self.is_down(Action::MoveRight) {
+            movement.x += 1.0;
+        }
+        if self.is_down(Action::MoveLeft) {
+            movement.x -= 1.0;
+        }
+        if self.is_down(Action::MoveUp) {
+            movement.y += 1.0;
+        }
+        if self.is_down(Action::MoveDown) {
+            movement.y -= 1.0;
+        }
+        movement
+    }
+}
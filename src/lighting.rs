@@ -0,0 +1,225 @@
+use macroquad::miniquad::{BlendFactor, BlendState, BlendValue, Equation, PipelineParams};
+use macroquad::prelude::*;
+
+/// The light map is rendered at a fraction of the screen size, it is blurry anyway.
+const RESOLUTION_SCALE: f32 = 0.5;
+
+/// Half the width of the flashlight beam in radians.
+const CONE_HALF_ANGLE: f32 = 0.5;
+
+const VERTEX_SHADER: &str = "#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+attribute vec4 color0;
+
+varying lowp vec2 uv;
+varying lowp vec4 color;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+    color = color0 / 255.0;
+    uv = texcoord;
+}
+";
+
+const FRAGMENT_SHADER: &str = "#version 100
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+
+void main() {
+    gl_FragColor = color * texture2D(Texture, uv);
+}
+";
+
+pub enum LightShape {
+    /// Shines equally in every direction.
+    Glow,
+    /// Shines in a beam towards the angle in radians.
+    Cone(f32),
+}
+
+pub struct Light {
+    pub position: Vec2,
+    pub radius: f32,
+    pub colour: Color,
+    pub shape: LightShape,
+}
+
+/// Darkens the scene everywhere except where lights shine.
+///
+/// Lights are added together into an off-screen texture that starts at the
+/// ambient colour, which is then multiplied over everything drawn so far.
+pub struct LightMap {
+    target: RenderTarget,
+    glow: Texture2D,
+    cone: Texture2D,
+    additive: Material,
+    multiply: Material,
+}
+impl LightMap {
+    pub fn new() -> Self {
+        let additive = load_material(
+            VERTEX_SHADER,
+            FRAGMENT_SHADER,
+            MaterialParams {
+                pipeline_params: PipelineParams {
+                    color_blend: Some(BlendState::new(
+                        Equation::Add,
+                        BlendFactor::Value(BlendValue::SourceAlpha),
+                        BlendFactor::One,
+                    )),
+                    ..PipelineParams::default()
+                },
+                ..MaterialParams::default()
+            },
+        )
+        .unwrap();
+
+        let multiply = load_material(
+            VERTEX_SHADER,
+            FRAGMENT_SHADER,
+            MaterialParams {
+                pipeline_params: PipelineParams {
+                    color_blend: Some(BlendState::new(
+                        Equation::Add,
+                        BlendFactor::Value(BlendValue::DestinationColor),
+                        BlendFactor::Zero,
+                    )),
+                    ..PipelineParams::default()
+                },
+                ..MaterialParams::default()
+            },
+        )
+        .unwrap();
+
+        Self {
+            target: LightMap::new_target(),
+            glow: LightMap::gen_glow_texture(64),
+            cone: LightMap::gen_cone_texture(64),
+            additive,
+            multiply,
+        }
+    }
+
+    fn new_target() -> RenderTarget {
+        let target = render_target(
+            (screen_width() * RESOLUTION_SCALE).max(1.0) as u32,
+            (screen_height() * RESOLUTION_SCALE).max(1.0) as u32,
+        );
+        target.texture.set_filter(FilterMode::Linear);
+        target
+    }
+
+    /// Makes a white circle that fades out towards its edge.
+    fn gen_glow_texture(size: u16) -> Texture2D {
+        let mut image = Image::gen_image_color(size, size, BLACK);
+        let center = vec2(size as f32, size as f32) / 2.0;
+
+        for y in 0..size as u32 {
+            for x in 0..size as u32 {
+                let distance = vec2(x as f32 + 0.5, y as f32 + 0.5).distance(center) / center.x;
+                let intensity = (1.0 - distance).max(0.0).powi(2);
+                image.set_pixel(x, y, Color::new(intensity, intensity, intensity, 1.0));
+            }
+        }
+
+        Texture2D::from_image(&image)
+    }
+
+    /// Makes a beam starting at the middle of the left edge and pointing right.
+    fn gen_cone_texture(size: u16) -> Texture2D {
+        let mut image = Image::gen_image_color(size, size, BLACK);
+        let origin = vec2(0.0, size as f32 / 2.0);
+
+        for y in 0..size as u32 {
+            for x in 0..size as u32 {
+                let offset = vec2(x as f32 + 0.5, y as f32 + 0.5) - origin;
+                let distance = offset.length() / size as f32;
+                let angle = offset.y.atan2(offset.x).abs();
+
+                let falloff = (1.0 - distance).max(0.0).powf(1.5);
+                let edge = (1.0 - angle / CONE_HALF_ANGLE).clamp(0.0, 1.0).sqrt();
+                let intensity = falloff * edge;
+                image.set_pixel(x, y, Color::new(intensity, intensity, intensity, 1.0));
+            }
+        }
+
+        Texture2D::from_image(&image)
+    }
+
+    /// Draws the lights over what the world camera has already drawn.
+    pub fn draw(&mut self, camera: &Camera2D, ambient: Color, lights: &[Light]) {
+        let width = (screen_width() * RESOLUTION_SCALE).max(1.0) as u32;
+        let height = (screen_height() * RESOLUTION_SCALE).max(1.0) as u32;
+        if self.target.texture.width() as u32 != width
+            || self.target.texture.height() as u32 != height
+        {
+            self.target.delete();
+            self.target = LightMap::new_target();
+        }
+
+        let light_camera = Camera2D {
+            render_target: Some(self.target),
+            ..*camera
+        };
+        set_camera(&light_camera);
+        clear_background(ambient);
+
+        gl_use_material(self.additive);
+        for light in lights {
+            self.draw_light(light);
+        }
+        gl_use_default_material();
+
+        set_camera(camera);
+
+        let view_min = camera.screen_to_world(vec2(0.0, screen_height()));
+        let view_max = camera.screen_to_world(vec2(screen_width(), 0.0));
+        let draw_param = DrawTextureParams {
+            dest_size: Some(view_max - view_min),
+            ..DrawTextureParams::default()
+        };
+
+        gl_use_material(self.multiply);
+        draw_texture_ex(self.target.texture, view_min.x, view_min.y, WHITE, draw_param);
+        gl_use_default_material();
+    }
+
+    fn draw_light(&self, light: &Light) {
+        match light.shape {
+            LightShape::Glow => {
+                let draw_param = DrawTextureParams {
+                    dest_size: Some(vec2(light.radius, light.radius) * 2.0),
+                    ..DrawTextureParams::default()
+                };
+                draw_texture_ex(
+                    self.glow,
+                    light.position.x - light.radius,
+                    light.position.y - light.radius,
+                    light.colour,
+                    draw_param,
+                );
+            }
+            LightShape::Cone(angle) => {
+                let draw_param = DrawTextureParams {
+                    dest_size: Some(vec2(light.radius, light.radius)),
+                    rotation: angle,
+                    pivot: Some(light.position),
+                    ..DrawTextureParams::default()
+                };
+                draw_texture_ex(
+                    self.cone,
+                    light.position.x,
+                    light.position.y - light.radius / 2.0,
+                    light.colour,
+                    draw_param,
+                );
+            }
+        }
+    }
+}
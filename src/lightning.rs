@@ -1,9 +1,56 @@
 use macroquad::math::{vec2, Vec2};
-use macroquad::rand::gen_range;
+
+use crate::rng::Rng;
+
+/// Shape of the bolts a `Lightning` makes.
+#[derive(Clone, Copy)]
+pub struct BoltConfig {
+    /// How far above the strike the bolt starts.
+    pub height: f32,
+    /// How far the channel wanders sideways, as a fraction of each segment's length.
+    pub jaggedness: f32,
+    /// How many times the channel is split in half, every level doubles the segments.
+    pub detail: u32,
+    /// Chance for each point of a channel to fork.
+    pub branch_chance: f32,
+    /// How many forks deep branches can split again.
+    pub max_branch_depth: u32,
+    pub thickness: f32,
+}
+impl Default for BoltConfig {
+    fn default() -> Self {
+        Self {
+            height: 50.0,
+            jaggedness: 0.25,
+            detail: 7,
+            branch_chance: 0.06,
+            max_branch_depth: 2,
+            thickness: 0.15,
+        }
+    }
+}
+
+/// The sound of a strike still on its way to the listener.
+pub struct Thunder {
+    pub origin: Vec2,
+    pub time_left: f32,
+}
+
+/// One connected line of the bolt.
+pub struct Channel {
+    pub points: Vec<Vec2>,
+    pub thickness: f32,
+}
 
 pub struct Lightning {
-    origin: Vec2,
-    points: Vec<Vec2>,
+    config: BoltConfig,
+    rng: Rng,
+
+    /// The channel from the sky to the strike, stays the same for the whole flash.
+    main_channel: Channel,
+    /// Forks that die off before reaching the ground, regrown every flicker.
+    branches: Vec<Channel>,
+
     max_duration: f32,
     current_duration: f32,
 
@@ -11,61 +58,123 @@ pub struct Lightning {
     time_between_change: f32,
 }
 impl Lightning {
-    pub fn new(origin: Vec2, max_duration: f32) -> Self {
-        let points = Lightning::gen_lightning_points(origin);
+    /// Makes a bolt whose shape only depends on `config` and the state of `rng`,
+    /// so the same seed always draws the same bolt.
+    pub fn with_config(origin: Vec2, max_duration: f32, config: BoltConfig, mut rng: Rng) -> Self {
+        let main_channel = Lightning::gen_main_channel(&mut rng, origin, &config);
+        let branches = Lightning::gen_branches(&mut rng, &main_channel, &config);
 
         let current_duration = 0.0;
 
         let time_until_change = 0.0;
-        let time_between_change = 0.012;
+        let time_between_change = 0.05;
         Self {
-            points,
+            config,
+            rng,
+
+            main_channel,
+            branches,
+
             max_duration,
             current_duration,
-            origin,
 
             time_until_change,
             time_between_change,
         }
     }
 
-    pub fn gen_lightning_points(origin: Vec2) -> Vec<Vec2> {
-        let mut bottom_point = origin;
-        let mut top_point = origin;
-
-        let mut points = vec![origin];
-        while top_point.y < origin.y + 50.0 {
-            let off_y = gen_range(0.2, 1.0);
-            let off_x = gen_range(-0.5, 0.5);
-
-            let offset = vec2(off_x, off_y);
-
-            top_point = bottom_point + offset;
+    fn gen_main_channel(rng: &mut Rng, origin: Vec2, config: &BoltConfig) -> Channel {
+        let sky = origin + vec2(rng.range(-0.2, 0.2) * config.height, config.height);
+        Channel {
+            points: Lightning::displace(rng, sky, origin, config.jaggedness, config.detail),
+            thickness: config.thickness,
+        }
+    }
 
-            points.push(top_point);
-            bottom_point = top_point;
+    /// Splits the line from `start` to `end` in half over and over, pushing every
+    /// new midpoint sideways by a random amount.
+    fn displace(rng: &mut Rng, start: Vec2, end: Vec2, jaggedness: f32, detail: u32) -> Vec<Vec2> {
+        let mut points = vec![start, end];
+
+        for _ in 0..detail {
+            let mut next = Vec::with_capacity(points.len() * 2);
+            for pair in points.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                let along = b - a;
+                let normal = vec2(-along.y, along.x).normalize_or_zero();
+                let offset = rng.range(-1.0, 1.0) * jaggedness * along.length();
+
+                next.push(a);
+                next.push((a + b) / 2.0 + normal * offset);
+            }
+            next.push(end);
+            points = next;
         }
 
         points
     }
 
-    pub fn points(&self) -> &Vec<Vec2> {
-        &self.points
+    fn gen_branches(rng: &mut Rng, main_channel: &Channel, config: &BoltConfig) -> Vec<Channel> {
+        let mut branches = Vec::new();
+        Lightning::gen_forks(rng, main_channel, config, 1, &mut branches);
+        branches
     }
 
-    pub fn new_points(&mut self) {
-        if !self.points.is_empty() {
-            let points = Lightning::gen_lightning_points(self.origin);
-            self.points = points;
+    /// Grows forks off `parent`, each heading roughly the same way as the parent
+    /// but thinner, shorter and gone before it reaches the ground.
+    fn gen_forks(
+        rng: &mut Rng,
+        parent: &Channel,
+        config: &BoltConfig,
+        depth: u32,
+        branches: &mut Vec<Channel>,
+    ) {
+        if depth > config.max_branch_depth || parent.points.len() < 3 {
+            return;
+        }
+
+        let end = *parent.points.last().unwrap();
+        for i in 1..parent.points.len() - 1 {
+            if !rng.chance(config.branch_chance) {
+                continue;
+            }
+
+            let start = parent.points[i];
+            let heading = end - start;
+            let turn = rng.range(0.3, 0.9) * if rng.chance(0.5) { 1.0 } else { -1.0 };
+            let (sin, cos) = turn.sin_cos();
+            let direction = vec2(
+                heading.x * cos - heading.y * sin,
+                heading.x * sin + heading.y * cos,
+            );
+            let branch_end = start + direction * rng.range(0.15, 0.45);
+
+            let branch = Channel {
+                points: Lightning::displace(
+                    rng,
+                    start,
+                    branch_end,
+                    config.jaggedness,
+                    config.detail.saturating_sub(depth + 1),
+                ),
+                thickness: parent.thickness * 0.5,
+            };
+            Lightning::gen_forks(rng, &branch, config, depth + 1, branches);
+            branches.push(branch);
         }
     }
 
+    /// Gives the main channel followed by every branch.
+    pub fn channels(&self) -> impl Iterator<Item = &Channel> {
+        std::iter::once(&self.main_channel).chain(self.branches.iter())
+    }
+
     pub fn update(&mut self, delta: f32) {
         self.current_duration += delta;
         self.time_until_change += delta;
         if self.time_until_change >= self.time_between_change {
             self.time_until_change = 0.0;
-            self.new_points();
+            self.branches = Lightning::gen_branches(&mut self.rng, &self.main_channel, &self.config);
         }
     }
 
@@ -1,284 +1,899 @@
 use macroquad::{
-    audio::{load_sound, play_sound, PlaySoundParams, Sound},
     prelude::*,
     rand::gen_range,
 };
 
+mod animation;
+use animation::*;
+
+mod browser;
+
+mod camera;
+use camera::*;
+
+mod assets;
+use assets::*;
+
+mod audio;
+use audio::*;
+
+mod bot;
+
+mod campaign;
+use campaign::*;
+
+mod daily;
+use daily::*;
+
 mod game;
 use game::*;
 
+mod gamepad;
+use gamepad::*;
+
+mod hot_reload;
+use hot_reload::*;
+
+mod input;
+use input::*;
+
+mod lighting;
+use lighting::*;
+
 mod lightning;
 use lightning::*;
 
+mod menu;
+use menu::*;
+
+mod music;
+use music::*;
+
+mod net;
+use net::*;
+
+mod particles;
+use particles::*;
+
+mod replay;
+use replay::*;
+
+mod rng;
+use rng::*;
+
+mod sandbox;
+use sandbox::*;
+
+mod settings;
+use settings::*;
+
+mod soak;
+use soak::*;
+
+mod storage;
+
+mod touch;
+use touch::*;
+
+mod tutorial;
+use tutorial::*;
+
 pub const PIXELS_PER_UNIT: f32 = 16.0;
 
+/// Longest step the game takes in one frame, so a stall or a minimized window doesn't skip ahead.
+///
+/// Desktop windows get no focus events, a longer frame there means the window
+/// was minimized or moved and pauses the run.
+const MAX_FRAME_TIME: f32 = 0.25;
+
+/// How lit the city is with no lights around.
+const AMBIENT_LIGHT: Color = Color::new(0.22, 0.22, 0.4, 1.0);
+
+/// Rain drops per second at the height of the storm.
+const MAX_RAIN_RATE: f32 = 600.0;
+
+/// Lightning closer than this to the player shakes the controller.
+const RUMBLE_DISTANCE: f32 = 12.0;
+
+/// Lightning closer than this to the player shakes the screen.
+const SHAKE_DISTANCE: f32 = 10.0;
+
+/// World units thunder travels per second, slow enough to count the seconds after a flash.
+const SPEED_OF_SOUND: f32 = 20.0;
+
+/// Thunder from further away than this is only heard as a low rumble.
+const DISTANT_THUNDER: f32 = 25.0;
+
+/// Lightning closer than this to the player gives them a jolt.
+const SHOCK_DISTANCE: f32 = 3.0;
+
+/// Player speeds above these switch from idle to walking and from walking to running.
+const WALK_SPEED: f32 = 0.5;
+const RUN_SPEED: f32 = 7.0;
+
+/// Points for every box fixed, by the player or the crew.
+const REPAIR_SCORE: f32 = 200.0;
+
+/// Seconds between working out the way to the nearest broken box shown on the map.
+const ROUTE_INTERVAL: f32 = 0.5;
+
+/// How close to a broken box on the map a click has to be to pick it, in pixels.
+const MINIMAP_PICK_RADIUS: f32 = 12.0;
+
+/// Where the city picture has the van parked, a van anywhere else is drawn.
+const PICTURE_VAN: Rect = Rect {
+    x: 68.0,
+    y: 6.5,
+    w: 2.9,
+    h: 1.3,
+};
+
+/// Seconds after the last zoom step the settings are saved, so scrolling saves once.
+const ZOOM_SAVE_DELAY: f32 = 1.0;
+
+/// World units a second the free camera flies over the city at normal zoom.
+const FLY_SPEED: f32 = 25.0;
+
+/// Seconds the replay viewer skips back or ahead with the bracket keys.
+const REPLAY_SKIP: f32 = 5.0;
+
+#[derive(Clone, Copy, PartialEq)]
 enum GameState {
     Start,
     Running,
+    Paused,
+    Settings,
+    Controls,
     End,
+    /// Watching a recorded run.
+    Replay,
+    /// Picking a level of the campaign.
+    LevelSelect,
+    /// Looking over today's challenge.
+    Daily,
 }
 
-struct FootstepManager {
-    distance_traveled: f32,
-    distance_until_sound: f32,
+/// What can be picked on the title screen.
+#[derive(Clone, Copy)]
+enum StartChoice {
+    Tutorial,
+    Endless,
+    Campaign,
+    Daily,
+    Sandbox,
+    Settings,
 }
-impl FootstepManager {
-    fn new(distance_until_sound: f32) -> Self {
-        let distance_traveled = 0.0;
-        Self {
-            distance_traveled,
-            distance_until_sound,
-        }
-    }
 
-    fn update(&mut self, distance: f32) {
-        self.distance_traveled += distance.abs();
-    }
-
-    fn try_sound(&mut self, sound: &Sound) {
-        if self.distance_traveled >= self.distance_until_sound {
-            self.distance_traveled = 0.0;
+/// What can be picked on the daily challenge screen.
+#[derive(Clone, Copy)]
+enum DailyChoice {
+    Start,
+    WatchBest,
+    Back,
+}
 
-            let sound_param = PlaySoundParams {
-                ..PlaySoundParams::default()
-            };
-            play_sound(*sound, sound_param);
-        }
-    }
+/// What can be picked once a campaign level is over.
+#[derive(Clone, Copy)]
+enum LevelEndChoice {
+    /// Plays the level at the index.
+    Next(usize),
+    Retry,
+    WatchReplay,
+    LevelSelect,
 }
 
 struct Assets {
-    player_animation: Vec<Texture2D>,
-    electrical_box: Texture2D,
-    electrical_box_broken: Texture2D,
-    repair_kit: Texture2D,
-    generator: Texture2D,
-    puddle: Texture2D,
-    map: Texture2D,
-    lightning_sound: Sound,
-    walk_sound: Sound,
-    repair_sound: Sound,
-    restock_sound: Sound,
-
+    player: AnimationSet,
+    crew: AnimationSet,
+    electrical_box: AnimationSet,
+    generator: AnimationSet,
+    van: AnimationSet,
+    store: AssetStore,
 }
 impl Assets {
-    async fn load() -> Self {
-        let mut player_animation = Vec::new();
-
-        for i in 1..=2 {
-            let path = format!("assets/player_idle{}.png", i);
-            let frame = load_texture(&path).await.unwrap();
-            frame.set_filter(FilterMode::Nearest);
-            player_animation.push(frame);
+    async fn load() -> Result<Self, Vec<String>> {
+        let store = AssetStore::load(MANIFEST_PATH).await?;
+        let missing = store.missing(
+            &["map", "puddle", "repair_kit"],
+            &[("player", 2), ("electrical_box", 2), ("generator", 1)],
+            &["lightning", "repair", "restock", "thunder_rumble", "walk"],
+        );
+        if !missing.is_empty() {
+            return Err(missing);
         }
 
-        let electrical_box = load_texture("assets/electrical_box.png").await.unwrap();
-        electrical_box.set_filter(FilterMode::Nearest);
-
-        let electrical_box_broken = load_texture("assets/electrical_box_broken.png")
-            .await
-            .unwrap();
-        electrical_box_broken.set_filter(FilterMode::Nearest);
-
-        let repair_kit = load_texture("assets/repair_kit.png").await.unwrap();
-        repair_kit.set_filter(FilterMode::Nearest);
+        let idle1 = &store.frames("player")[0];
+        let idle2 = &store.frames("player")[1];
+        let shock_colour = Color::new(0.7, 0.85, 1.0, 1.0);
+        let player = AnimationSet::new(
+            SpriteSheet::from_frames(&[
+                idle1.clone(),
+                idle2.clone(),
+                tinted(idle1, shock_colour, 0.7),
+                tinted(idle2, shock_colour, 0.7),
+            ]),
+            vec![
+                Clip {
+                    name: "idle",
+                    frames: vec![0, 1],
+                    frames_per_second: 1.0,
+                    looping: true,
+                    events: vec![],
+                },
+                Clip {
+                    name: "walk",
+                    frames: vec![0, 1],
+                    frames_per_second: 3.0,
+                    looping: true,
+                    events: vec![(0, AnimationEvent::Footstep), (1, AnimationEvent::Footstep)],
+                },
+                Clip {
+                    name: "run",
+                    frames: vec![0, 1],
+                    frames_per_second: 4.5,
+                    looping: true,
+                    events: vec![(0, AnimationEvent::Footstep), (1, AnimationEvent::Footstep)],
+                },
+                Clip {
+                    name: "repair",
+                    frames: vec![0, 1, 0, 1, 0, 1],
+                    frames_per_second: 10.0,
+                    looping: false,
+                    events: vec![],
+                },
+                Clip {
+                    name: "shocked",
+                    frames: vec![2, 3, 2, 3, 2, 3, 2, 3],
+                    frames_per_second: 14.0,
+                    looping: false,
+                    events: vec![],
+                },
+            ],
+        );
 
-        let generator = load_texture("assets/generator.png").await.unwrap();
-        generator.set_filter(FilterMode::Nearest);
+        // the crew are the player in orange overalls
+        let crew_colour = Color::new(1.0, 0.55, 0.1, 1.0);
+        let crew = AnimationSet::new(
+            SpriteSheet::from_frames(&[tinted(idle1, crew_colour, 0.45), tinted(idle2, crew_colour, 0.45)]),
+            vec![
+                Clip {
+                    name: "idle",
+                    frames: vec![0, 1],
+                    frames_per_second: 1.0,
+                    looping: true,
+                    events: vec![],
+                },
+                Clip {
+                    name: "walk",
+                    frames: vec![0, 1],
+                    frames_per_second: 3.0,
+                    looping: true,
+                    events: vec![(0, AnimationEvent::Footstep), (1, AnimationEvent::Footstep)],
+                },
+                Clip {
+                    name: "repair",
+                    frames: vec![0, 1, 0, 1, 0, 1],
+                    frames_per_second: 10.0,
+                    looping: false,
+                    events: vec![],
+                },
+            ],
+        );
 
-        let puddle = load_texture("assets/puddle.png").await.unwrap();
-        puddle.set_filter(FilterMode::Nearest);
+        let working = &store.frames("electrical_box")[0];
+        let broken = &store.frames("electrical_box")[1];
+        let electrical_box = AnimationSet::new(
+            SpriteSheet::from_frames(&[
+                working.clone(),
+                broken.clone(),
+                tinted(broken, Color::new(1.0, 1.0, 0.7, 1.0), 0.5),
+            ]),
+            vec![
+                Clip {
+                    name: "working",
+                    frames: vec![0],
+                    frames_per_second: 0.0,
+                    looping: true,
+                    events: vec![],
+                },
+                Clip {
+                    name: "sparking",
+                    frames: vec![1, 2, 1, 1, 1],
+                    frames_per_second: 8.0,
+                    looping: true,
+                    events: vec![(1, AnimationEvent::Spark)],
+                },
+            ],
+        );
 
-        let map = load_texture("assets/map.png").await.unwrap();
-        map.set_filter(FilterMode::Nearest);
+        let generator = &store.frames("generator")[0];
+        let generator = AnimationSet::new(
+            SpriteSheet::from_frames(&[generator.clone(), shifted(generator, 1)]),
+            vec![
+                Clip {
+                    name: "off",
+                    frames: vec![0],
+                    frames_per_second: 0.0,
+                    looping: true,
+                    events: vec![],
+                },
+                Clip {
+                    name: "running",
+                    frames: vec![0, 1],
+                    frames_per_second: 14.0,
+                    looping: true,
+                    events: vec![],
+                },
+            ],
+        );
 
-        let lightning_sound = load_sound("assets/lightning.wav").await.unwrap();
-        let walk_sound = load_sound("assets/walk.wav").await.unwrap();
-        let repair_sound = load_sound("assets/repair.wav").await.unwrap();
-        let restock_sound = load_sound("assets/restock.wav").await.unwrap();
+        // the city's van is part of the map picture, so only its roof beacon is animated
+        let van = AnimationSet::new(
+            SpriteSheet::from_frames(&[
+                Image::gen_image_color(4, 3, Color::new(0.4, 0.25, 0.0, 1.0)),
+                Image::gen_image_color(4, 3, Color::new(1.0, 0.7, 0.1, 1.0)),
+            ]),
+            vec![
+                Clip {
+                    name: "parked",
+                    frames: vec![0],
+                    frames_per_second: 0.0,
+                    looping: true,
+                    events: vec![],
+                },
+                Clip {
+                    name: "beacon",
+                    frames: vec![0, 1],
+                    frames_per_second: 3.0,
+                    looping: true,
+                    events: vec![],
+                },
+            ],
+        );
 
-        Self {
-            player_animation,
+        Ok(Self {
+            player,
+            crew,
             electrical_box,
-            electrical_box_broken,
-            repair_kit,
             generator,
-            puddle,
-            map,
+            van,
+            store,
+        })
+    }
 
-            lightning_sound,
-            walk_sound,
-            repair_sound,
-            restock_sound,
-        }
+    fn texture(&self, key: &str) -> Texture2D {
+        self.store.texture(key)
     }
+
+    fn sound(&self, key: &str) -> &PannedSound {
+        self.store.sound(key)
+    }
+}
+
+/// Tints that tell co-op players apart, the first player looks as drawn.
+const PLAYER_COLOURS: [Color; 4] = [
+    WHITE,
+    Color::new(0.6, 0.8, 1.0, 1.0),
+    Color::new(0.6, 1.0, 0.6, 1.0),
+    Color::new(1.0, 0.65, 0.85, 1.0),
+];
+
+/// The shared co-op camera never zooms out further than this to fit everyone in.
+const MIN_SHARED_ZOOM: f32 = 0.35;
+
+/// Room left around the players when the shared camera frames them, in world units.
+const FRAMING_MARGIN: f32 = 6.0;
+
+/// What one player did during a run, shown on the end screen in co-op.
+#[derive(Default)]
+struct PlayerStats {
+    repairs: u32,
+    restocks: u32,
+    distance: f32,
 }
 
-struct AnimationManager {
-    frame_index: usize,
-    frames_per_second: f32,
-    time_since_last_frame: f32,
-    number_of_frames: usize,
+/// How a player is drawn and what they have done, everything else about them is in `Game`.
+struct PlayerView {
+    facing_left: bool,
+    facing: Vec2,
+    animator: Animator,
+    stats: PlayerStats,
 }
-impl AnimationManager {
-    fn new(frames_per_second: f32, animation: &Vec<Texture2D>) -> Self {
+impl PlayerView {
+    fn new() -> Self {
         Self {
-            frame_index: 0,
-            frames_per_second,
-            time_since_last_frame: 0.0,
-            number_of_frames: animation.len(),
+            facing_left: false,
+            facing: vec2(1.0, 0.0),
+            animator: Animator::new("idle"),
+            stats: PlayerStats::default(),
         }
     }
 
-    fn update(&mut self, delta: f32) {
-        self.time_since_last_frame += delta;
-
-        if self.time_since_last_frame >= self.frames_per_second {
-            self.time_since_last_frame = 0.0;
-            self.frame_index += 1;
-
-            if self.frame_index >= self.number_of_frames {
-                self.frame_index = 0;
-            }
+    /// Turns towards `direction`, keeping the last way faced when standing still.
+    fn face(&mut self, direction: Vec2) {
+        if direction.x > 0.0 {
+            self.facing_left = false;
+        } else if direction.x < 0.0 {
+            self.facing_left = true;
+        }
+        if direction != Vec2::ZERO {
+            self.facing = direction.normalize();
         }
     }
 }
 
 struct App {
     game: Game,
-    camera: Camera2D,
+    level: Level,
+    rules: Rules,
+    /// Only watches files when started with `--dev`.
+    hot_reload: Option<HotReload>,
+    camera: FollowCamera,
     assets: Assets,
-    player_facing_left: bool,
-    player_am: AnimationManager,
-    player_fm: FootstepManager,
+    players: Vec<PlayerView>,
+    box_animators: Vec<Animator>,
+    crew_animators: Vec<Animator>,
+    crew_facing_left: Vec<bool>,
+    generator_animator: Animator,
+    van_animator: Animator,
     lightnings: Vec<Lightning>,
+    thunder: Vec<Thunder>,
     lightning_timer: RandomTimer,
+    /// Shapes the bolts, seed it to get the same bolts every run.
+    bolt_rng: Rng,
 
     time_survived: f32,
     score: f32,
 
+    show_minimap: bool,
+    /// The walk to the closest broken electrical box, drawn on the map.
+    repair_route: Vec<Vec2>,
+    route_timer: f32,
+
+    settings: Settings,
+    /// Seconds until a zoom change is saved, while there is one waiting.
+    zoom_save_timer: Option<f32>,
+    pause_menu: Menu,
+    settings_menu: Menu,
+    settings_return: GameState,
+    controls_menu: Menu,
+    rebinding: Option<Action>,
+    /// The last key pressed while rebinding that can't be bound, told to the player.
+    unbindable_key: Option<KeyCode>,
+
+    actions: ActionState,
+    gamepads: Gamepads,
+    touch_controls: TouchControls,
+
+    light_map: LightMap,
+
+    particles: ParticleSystem,
+    sparks: ParticleSystem,
+    rain: Emitter,
+    splashes: Emitter,
+
+    audio: AudioManager,
+    music: MusicManager,
+
+    /// What has been pressed this run, saved as a replay when it ends.
+    recording: Option<Recording>,
+    /// The last run that ended, to watch from the end screen.
+    last_run: Option<Recording>,
+    replay: Option<ReplayViewer>,
+    /// Where the replay camera flies to, it follows the players when `None`.
+    free_camera: Option<Vec2>,
+    /// True while the replay timeline is being dragged.
+    scrubbing: bool,
+
+    campaign: Campaign,
+    /// The campaign level being played, kept until an endless run starts.
+    campaign_run: Option<CampaignRun>,
+    start_menu: Menu,
+    level_select: Menu,
+    level_end_menu: Menu,
+
+    daily_challenge: DailyChallenge,
+    /// The attempt at the daily challenge being played, kept until another run starts.
+    daily_attempt: Option<u32>,
+    daily_menu: Menu,
+
+    tutorial: Option<Tutorial>,
+
+    sandbox: Option<Sandbox>,
+
+    /// The server being played on, if any.
+    network: Option<NetClient>,
+    /// Why the server was lost, shown on the end screen.
+    connection_error: Option<String>,
+
     game_state: GameState,
 }
 impl App {
-    async fn new() -> App {
-        let game = Game::load();
+    /// Starts the game, playing on a server if `network` is given.
+    async fn new(network: Option<(NetClient, Level, Rules)>) -> Result<App, Vec<String>> {
+        let hot_reload = if dev_mode() { Some(HotReload::new()) } else { None };
+        let (network, level, rules) = match network {
+            // everyone on a server has to play the same map
+            Some((network, level, rules)) => (Some(network), level, rules),
+            None => {
+                let (level, rules) = App::local_level();
+                (None, level, rules)
+            }
+        };
+        let connection_error = None;
+        let settings = Settings::load();
+        let campaign = App::local_campaign(&level, &rules);
+        let campaign_run = None;
+        let start_menu = Menu::new();
+        let level_select = Menu::new();
+        let level_end_menu = Menu::new();
 
-        let scale = 0.1;
-        let camera = Camera2D {
-            zoom: vec2(1.0 * scale, screen_width() / screen_height() * scale),
-            ..Camera2D::default()
+        let daily_challenge = DailyChallenge::new(today(), &daily_maps(&level, &campaign), &rules);
+        let daily_attempt = None;
+        let daily_menu = Menu::new();
+
+        let tutorial = None;
+
+        let sandbox = None;
+
+        let player_count = match &network {
+            Some(network) => network.players(),
+            None => settings.players as usize,
+        };
+        let seed = new_seed();
+        let game = Game::new(&level, &rules, seed, player_count);
+        // runs on a server are the server's to keep
+        let recording = match network {
+            Some(_) => None,
+            None => Some(Recording::new(seed, player_count, &level, &rules)),
         };
-        set_camera(&camera);
+        let last_run = None;
+        let replay = None;
+        let free_camera = None;
+        let scrubbing = false;
 
-        let assets = Assets::load().await;
+        let local_player = network.as_ref().map_or(0, |network| network.player());
+        let camera = FollowCamera::new(rect_center(game.players()[local_player].hit_box()), settings.camera_zoom);
+        set_camera(camera.view());
 
-        let player_facing_left = false;
+        let assets = Assets::load().await?;
 
-        let player_am = AnimationManager::new(1.0, &assets.player_animation);
+        let players = game.players().iter().map(|_| PlayerView::new()).collect();
+        let box_animators = App::new_box_animators(&game);
+        let crew_animators = App::new_crew_animators(&game);
+        let crew_facing_left = vec![false; crew_animators.len()];
+        let generator_animator = Animator::new("running");
+        let van_animator = Animator::new("parked");
 
         let lightnings = Vec::new();
+        let thunder = Vec::new();
 
-        let lightning_timer = RandomTimer::new(0.1, 1.0);
-
-        let player_fm = FootstepManager::new(2.0);
+        let mut bolt_rng = Rng::from_global();
+        let lightning_timer = RandomTimer::new(0.1, 1.0, &mut bolt_rng);
 
         let time_survived = 0.0;
 
         let score = 0.0;
 
+        let show_minimap = false;
+        let repair_route = Vec::new();
+        let route_timer = 0.0;
+
+        let zoom_save_timer = None;
+        let pause_menu = Menu::new();
+        let settings_menu = Menu::adjustable();
+        let settings_return = GameState::Start;
+        let controls_menu = Menu::new();
+        let rebinding = None;
+        let unbindable_key = None;
+
+        let actions = ActionState::default();
+        let gamepads = Gamepads::new();
+        let touch_controls = TouchControls::new();
+
+        let light_map = LightMap::new();
+
+        let particles = ParticleSystem::new(2000);
+        let sparks = ParticleSystem::new(500);
+        let rain = Emitter::new(particles::rain(), 0.0);
+        let splashes = Emitter::new(particles::splash(), 80.0);
+
+        let audio = AudioManager::new();
+        let music = MusicManager::new().await;
+
         let game_state = GameState::Start;
-        Self {
+        Ok(Self {
             game,
+            level,
+            rules,
+            hot_reload,
             camera,
             assets,
-            player_facing_left,
-            player_am,
-            player_fm,
+            players,
+            box_animators,
+            crew_animators,
+            crew_facing_left,
+            generator_animator,
+            van_animator,
             lightnings,
+            thunder,
             lightning_timer,
+            bolt_rng,
 
             time_survived,
             score,
 
+            show_minimap,
+            repair_route,
+            route_timer,
+
+            settings,
+            pause_menu,
+            settings_menu,
+            zoom_save_timer,
+            settings_return,
+            controls_menu,
+            rebinding,
+            unbindable_key,
+
+            actions,
+            gamepads,
+            touch_controls,
+
+            light_map,
+
+            particles,
+            sparks,
+            rain,
+            splashes,
+
+            audio,
+            music,
+
+            recording,
+            last_run,
+            replay,
+            free_camera,
+            scrubbing,
+
+            campaign,
+            campaign_run,
+            start_menu,
+            level_select,
+            level_end_menu,
+
+            daily_challenge,
+            daily_attempt,
+            daily_menu,
+
+            tutorial,
+
+            sandbox,
+
+            network,
+            connection_error,
+
             game_state,
-        }
+        })
     }
 
-    fn draw(&self) {
+    fn draw(&mut self) {
         clear_background(BLACK);
 
         self.draw_map();
 
         self.draw_electical_boxes();
+        self.draw_van();
         self.draw_puddles();
-        self.draw_player();
+        self.draw_crew();
+        self.draw_players();
+        self.particles.draw();
+
+        let lights = self.lights();
+        self.light_map.draw(self.camera.view(), self.ambient_light(), &lights);
+
+        self.sparks.draw();
         self.draw_lightnings();
+        self.draw_tutorial_target();
+        self.draw_sandbox_cursor();
 
         self.draw_ui();
     }
 
     fn draw_map(&self) {
-        let texture = &self.assets.map;
+        let texture = &self.assets.texture("map");
+        let source = self.map_source(texture);
         let draw_param = DrawTextureParams {
-            dest_size: Some(vec2(
-                texture.width() / PIXELS_PER_UNIT,
-                texture.height() / PIXELS_PER_UNIT,
-            )),
+            dest_size: Some(source.size() / PIXELS_PER_UNIT),
+            source: Some(source),
             flip_y: true,
             ..DrawTextureParams::default()
         };
         draw_texture_ex(*texture, 0.0, 0.0, WHITE, draw_param);
     }
 
+    /// Gives the part of the city picture the map covers, in pixels. Maps start
+    /// at the picture's bottom left corner, the world's y axis points up.
+    fn map_source(&self, texture: &Texture2D) -> Rect {
+        let width = (self.game.map_width() * PIXELS_PER_UNIT).min(texture.width());
+        let height = (self.game.map_height() * PIXELS_PER_UNIT).min(texture.height());
+        Rect::new(0.0, texture.height() - height, width, height)
+    }
+
     fn draw_ui(&self) {
         set_default_camera();
 
         self.draw_generator_ui();
         self.draw_repair_kit_ui();
         self.draw_score();
+        self.draw_crew_ui();
+        if self.game_state != GameState::Replay {
+            self.draw_objectives_ui();
+            self.draw_tutorial_ui();
+            if let Some(sandbox) = &self.sandbox {
+                sandbox.draw(&self.game, self.settings.text_scale);
+            }
+        }
+
+        if self.show_minimap {
+            self.draw_minimap();
+        }
+        self.touch_controls.draw();
+        set_camera(self.camera.view());
+    }
 
-        let colour = if !self.lightnings.is_empty() {
-            let index = self.lightnings.len() - 1;
-            let mut a =
-                self.lightnings[index].max_duration() * self.lightnings[index].current_duration();
-            if a > 0.5 {
-                a = 0.5
+    /// Gives the light everything gets without a lamp nearby, brighter while lightning flashes.
+    fn ambient_light(&self) -> Color {
+        let mut flash = match self.lightnings.last() {
+            Some(lightning) => {
+                let a = lightning.max_duration() * lightning.current_duration();
+                1.0 - (a / 0.5).min(1.0)
             }
-            Color::new(0.0, 0.0, 0.2, a)
-        } else {
-            Color::new(0.0, 0.0, 0.2, 0.5)
+            None => 0.0,
         };
-        draw_rectangle(0.0, 0.0, screen_width(), screen_height(), colour);
-        set_camera(&self.camera);
+        if self.settings.reduce_flashing {
+            flash *= 0.3;
+        }
+
+        Color::new(
+            AMBIENT_LIGHT.r + (1.0 - AMBIENT_LIGHT.r) * flash,
+            AMBIENT_LIGHT.g + (1.0 - AMBIENT_LIGHT.g) * flash,
+            AMBIENT_LIGHT.b + (1.0 - AMBIENT_LIGHT.b) * flash,
+            1.0,
+        )
+    }
+
+    /// Gathers every light in the city: working boxes, the buildings they power
+    /// and the player's flashlight.
+    fn lights(&self) -> Vec<Light> {
+        let mut lights = Vec::new();
+        let eboxes = self.game.electrical_boxes();
+
+        for ebox in eboxes {
+            if !*ebox.broken() {
+                lights.push(Light {
+                    position: rect_center(ebox.hit_box()),
+                    radius: 5.0,
+                    colour: Color::new(1.0, 0.85, 0.5, 0.8),
+                    shape: LightShape::Glow,
+                });
+            }
+        }
+
+        // each building is fed by the closest electrical box
+        for wall in self.game.walls() {
+            let center = rect_center(wall.hit_box());
+            let closest = eboxes.iter().min_by(|a, b| {
+                let a = rect_center(a.hit_box()).distance(center);
+                let b = rect_center(b.hit_box()).distance(center);
+                a.partial_cmp(&b).unwrap()
+            });
+            if let Some(ebox) = closest {
+                if !*ebox.broken() {
+                    lights.push(Light {
+                        position: center,
+                        radius: wall.hit_box().w.max(wall.hit_box().h) * 0.75,
+                        colour: Color::new(1.0, 0.9, 0.6, 0.5),
+                        shape: LightShape::Glow,
+                    });
+                }
+            }
+        }
+
+        for (i, view) in self.players.iter().enumerate() {
+            let player = self.player_center(i);
+            lights.push(Light {
+                position: player,
+                radius: 1.5,
+                colour: Color::new(0.8, 0.8, 0.8, 0.6),
+                shape: LightShape::Glow,
+            });
+            lights.push(Light {
+                position: player,
+                radius: 8.0,
+                colour: Color::new(1.0, 1.0, 0.85, 0.9),
+                shape: LightShape::Cone(view.facing.y.atan2(view.facing.x)),
+            });
+        }
+
+        lights
     }
 
     fn update(&mut self, delta: f32) {
+        self.audio.set_listener(self.group_center());
+        self.audio.set_volume(self.settings.effective_sfx_volume());
+        self.audio.update(delta);
         self.game_events();
 
-        let scale = 0.1;
-        let aspect = screen_width()/screen_height();
-        self.camera.zoom = vec2(1.0 *scale, aspect*scale);
+        let tutorial_done = self.tutorial.as_ref().is_some_and(|tutorial| tutorial.step() == TutorialStep::Done);
+        if tutorial_done && self.actions.is_pressed(Action::Confirm) {
+            self.save_zoom();
+            self.leave_modes();
+            self.restart();
+            self.game_state = GameState::Start;
+            return;
+        }
+
+        if self.actions.is_pressed(Action::ToggleMinimap) {
+            self.show_minimap = !self.show_minimap;
+        }
+        self.zoom_input(delta);
+        self.update_repair_route(delta);
+        let target = self.minimap_pick();
+        self.actions.set_dispatch_target(target);
 
         self.lightning_timer.update(delta);
-        self.player_key_input();
+        if self.network.is_some() {
+            self.network_input(delta);
+        } else {
+            let inputs = self.player_input();
+            let before: Vec<Vec2> = (0..self.players.len()).map(|i| self.player_center(i)).collect();
+            self.game.update(delta);
+            if let Some(tutorial) = &mut self.tutorial {
+                tutorial.update(&mut self.game);
+            }
+            self.update_sandbox();
+            for (i, before) in before.into_iter().enumerate() {
+                let moved = self.player_center(i).distance(before);
+                self.players[i].stats.distance += moved;
+            }
+            if let Some(recording) = &mut self.recording {
+                recording.push(delta, inputs);
+            }
+        }
         self.update_animations(delta);
-        self.game.update(delta);
 
         self.update_lighnings(delta);
+        self.update_thunder(delta);
 
-        let player_center = vec2(
-            self.game.player().hit_box().x + self.game.player().hit_box().w / 2.0,
-            self.game.player().hit_box().y + self.game.player().hit_box().h / 2.0,
-        );
+        self.update_camera(delta);
 
-        self.camera.offset = -player_center * self.camera.zoom;
-        self.lock_camera();
+        self.update_particles(delta);
 
         if self.lightning_timer.is_active() {
-            self.lightning_timer.reset();
+            self.lightning_timer.reset(&mut self.bolt_rng);
         }
 
-        if self.game.generator().feul() <= 0.0 {
+        if let Some(run) = &mut self.campaign_run {
+            run.update(&self.game, delta);
+        }
+        let cleared = self
+            .campaign_run
+            .as_ref()
+            .is_some_and(|run| run.cleared(&self.campaign.levels()[run.level()]));
+        // the tutorial can't be lost, it waits for the player to finish
+        let ran_dry = self.game.generator().feul() <= 0.0 && self.tutorial.is_none();
+        if ran_dry || cleared {
+            if let Some(recording) = self.recording.take() {
+                recording.save();
+                if let Some(attempt) = self.daily_attempt {
+                    let score = self.score + self.get_timer_score();
+                    self.daily_challenge.record(attempt, score, self.time_survived, &recording);
+                }
+                self.last_run = Some(recording);
+            }
+            self.finish_level();
+            self.save_zoom();
             self.game_state = GameState::End;
             return;
         }
@@ -286,157 +901,586 @@ impl App {
         self.update_score(delta);
     }
 
+    /// Starts watching `recording` from the beginning.
+    fn watch(&mut self, recording: Recording) {
+        let mut replay = ReplayViewer::new(recording);
+        self.game = replay.start();
+        self.reset_run();
+        self.recording = None;
+        self.replay = Some(replay);
+        self.free_camera = None;
+        self.scrubbing = false;
+        self.game_state = GameState::Replay;
+    }
+
+    /// Leaves the replay for the title screen.
+    fn stop_watching(&mut self) {
+        self.save_zoom();
+        self.replay = None;
+        self.restart();
+        self.game_state = GameState::Start;
+    }
+
+    /// Plays the replay on and handles its controls, everything is shown the
+    /// same as when it was played.
+    fn update_replay(&mut self, delta: f32) {
+        if self.actions.is_pressed(Action::Pause) {
+            self.stop_watching();
+            return;
+        }
+        let Some(replay) = &mut self.replay else {
+            return;
+        };
+
+        if self.actions.is_pressed(Action::PlayPause) {
+            replay.toggle_pause(&mut self.game);
+        }
+        if self.actions.is_pressed(Action::Faster) {
+            replay.change_speed(2.0);
+        }
+        if self.actions.is_pressed(Action::Slower) {
+            replay.change_speed(0.5);
+        }
+        let mut seek_to = None;
+        if self.actions.is_pressed(Action::SkipBack) {
+            seek_to = Some(replay.time() - REPLAY_SKIP);
+        }
+        if self.actions.is_pressed(Action::SkipForward) {
+            seek_to = Some(replay.time() + REPLAY_SKIP);
+        }
+
+        let timeline = replay_timeline();
+        let (mouse_x, mouse_y) = mouse_position();
+        if is_mouse_button_pressed(MouseButton::Left) && timeline.contains(vec2(mouse_x, mouse_y)) {
+            self.scrubbing = true;
+        }
+        if !is_mouse_button_down(MouseButton::Left) {
+            self.scrubbing = false;
+        }
+        if self.scrubbing {
+            let along = ((mouse_x - timeline.x) / timeline.w).clamp(0.0, 1.0);
+            seek_to = Some(along * replay.duration());
+        }
+
+        match seek_to {
+            Some(time) => {
+                replay.seek(&mut self.game, time);
+                // whatever was flashing belongs to another moment
+                self.lightnings.clear();
+                self.thunder.clear();
+                self.sparks.clear();
+            }
+            None => replay.advance(&mut self.game, delta),
+        }
+        self.time_survived = replay.time();
+
+        if self.actions.is_pressed(Action::FreeCamera) {
+            self.free_camera = match self.free_camera {
+                Some(_) => None,
+                None => Some(self.camera.position()),
+            };
+        }
+        if self.actions.is_pressed(Action::ToggleMinimap) {
+            self.show_minimap = !self.show_minimap;
+        }
+        self.zoom_input(delta);
+
+        self.audio.set_listener(self.camera.position());
+        self.audio.set_volume(self.settings.effective_sfx_volume());
+        self.audio.update(delta);
+        self.game_events();
+        // the score is counted from the recording so it's right after jumping around
+        if let Some(replay) = &self.replay {
+            self.score = replay.score();
+        }
+
+        for (view, player) in self.players.iter_mut().zip(self.game.players()) {
+            if player.vel().length() > WALK_SPEED {
+                view.face(player.vel());
+            }
+        }
+        self.update_animations(delta);
+        self.lightning_timer.update(delta);
+        self.update_lighnings(delta);
+        self.update_thunder(delta);
+        self.update_particles(delta);
+        if self.lightning_timer.is_active() {
+            self.lightning_timer.reset(&mut self.bolt_rng);
+        }
+
+        match &mut self.free_camera {
+            Some(target) => {
+                let zoom = self.settings.camera_zoom;
+                *target += self.actions.movement() * FLY_SPEED / zoom * delta;
+                let map_size = vec2(self.game.map_width(), self.game.map_height());
+                *target = target.clamp(Vec2::ZERO, map_size);
+                self.camera.update(*target, Vec2::ZERO, zoom, map_size, delta);
+            }
+            None => self.update_camera(delta),
+        }
+    }
+
+    /// Draws the timeline with what happened along it, and the replay controls.
+    fn draw_replay_ui(&self) {
+        let Some(replay) = &self.replay else {
+            return;
+        };
+        set_default_camera();
+
+        let timeline = replay_timeline();
+        let duration = replay.duration().max(0.001);
+        draw_rectangle(timeline.x, timeline.y, timeline.w, timeline.h, DARKGRAY);
+        draw_rectangle(timeline.x, timeline.y, timeline.w * replay.time() / duration, timeline.h, GRAY);
+        for marker in replay.markers() {
+            let (colour, top, height) = match marker.kind {
+                MarkerKind::Fix => (GREEN, timeline.y - 6.0, 6.0),
+                MarkerKind::CrewFix => (ORANGE, timeline.y - 6.0, 6.0),
+                MarkerKind::Destroy => (RED, timeline.bottom(), 6.0),
+            };
+            let x = timeline.x + timeline.w * marker.time / duration;
+            draw_line(x, top, x, top + height, 1.0, colour);
+        }
+        let playhead = timeline.x + timeline.w * replay.time() / duration;
+        draw_line(playhead, timeline.y - 8.0, playhead, timeline.bottom() + 8.0, 2.0, WHITE);
+
+        let text_scale = self.settings.text_scale;
+        let state = if replay.paused() { "paused" } else { "playing" };
+        let status = format!(
+            "Replay {} / {}  {}x  {}",
+            clock(replay.time()),
+            clock(replay.duration()),
+            replay.speed(),
+            state
+        );
+        draw_text(&status, timeline.x, timeline.y - 16.0, 20.0 * text_scale, WHITE);
+        let camera = if self.free_camera.is_some() { "follow players" } else { "free camera" };
+        let help = format!(
+            "<{}> play/pause  <{} {}> speed  <{} {}> skip {}s  <{}> {}  drag the timeline to scrub  <{}> leave",
+            self.key_hint(Action::PlayPause),
+            self.key_hint(Action::Slower),
+            self.key_hint(Action::Faster),
+            self.key_hint(Action::SkipBack),
+            self.key_hint(Action::SkipForward),
+            REPLAY_SKIP,
+            self.key_hint(Action::FreeCamera),
+            camera,
+            self.key_hint(Action::Pause)
+        );
+        draw_text(&help, timeline.x, timeline.y - 16.0 - 22.0 * text_scale, 16.0 * text_scale, LIGHTGRAY);
+        set_camera(self.camera.view());
+    }
+
+    /// Follows the player, or in co-op frames everyone, zooming out as they spread apart.
+    fn update_camera(&mut self, delta: f32) {
+        let players: Vec<&Player> = self.local_players().iter().map(|i| &self.game.players()[*i]).collect();
+        let centers: Vec<Vec2> = players.iter().map(|player| rect_center(player.hit_box())).collect();
+        let min = centers.iter().fold(centers[0], |min, center| min.min(*center));
+        let max = centers.iter().fold(centers[0], |max, center| max.max(*center));
+        let velocity = players.iter().map(|player| player.vel()).fold(Vec2::ZERO, |sum, vel| sum + vel)
+            / players.len() as f32;
+
+        let mut zoom = self.settings.camera_zoom;
+        if players.len() > 1 {
+            let fit = FollowCamera::zoom_to_fit(max - min + Vec2::splat(FRAMING_MARGIN * 2.0));
+            zoom = zoom.min(fit).max(MIN_SHARED_ZOOM);
+        }
+
+        let map_size = vec2(self.game.map_width(), self.game.map_height());
+        self.camera
+            .update((min + max) / 2.0, velocity, zoom, map_size, delta);
+    }
+
     fn game_events(&mut self) {
         while let Some(event) = self.game.poll_event() {
+            if let Some(tutorial) = &mut self.tutorial {
+                tutorial.on_event(&event, &self.game);
+            }
             match event {
-                GameEvent::Restock => {
-                    let sound_params = PlaySoundParams{
-                        ..PlaySoundParams::default()
-                    };
-                    play_sound(self.assets.restock_sound, sound_params);                
+                GameEvent::Restock(player) => {
+                    let van = rect_center(self.game.restock());
+                    self.audio.play_at(self.assets.sound("restock"), van, 1.0);
+                    self.players[player].stats.restocks += 1;
                 }
-                GameEvent::FixEBox(_ebox) => {
-                    let sound_params = PlaySoundParams{
-                        ..PlaySoundParams::default()
-                    };
-                    play_sound(self.assets.repair_sound, sound_params);
-                    self.score += 200.0;
+                GameEvent::FixEBox(player, ebox) => {
+                    self.audio
+                        .play_at(self.assets.sound("repair"), rect_center(ebox.hit_box()), 1.0);
+                    self.score += REPAIR_SCORE;
+                    self.players[player].animator.play_once("repair");
+                    self.players[player].stats.repairs += 1;
+                    self.sparks
+                        .burst(&particles::repair_burst(), rect_center(ebox.hit_box()), 30);
+                }
+                GameEvent::CrewFixEBox(member, ebox) => {
+                    self.audio
+                        .play_at(self.assets.sound("repair"), rect_center(ebox.hit_box()), 1.0);
+                    self.score += REPAIR_SCORE;
+                    if let Some(animator) = self.crew_animators.get_mut(member) {
+                        animator.play_once("repair");
+                    }
+                    self.sparks
+                        .burst(&particles::repair_burst(), rect_center(ebox.hit_box()), 30);
                 }
                 GameEvent::DestroyEBox(ebox) => {
                     let x = ebox.hit_box().x + ebox.hit_box().w / 2.0;
                     let y = ebox.hit_box().y + ebox.hit_box().h / 2.0;
-                    self.lightnings.push(App::new_lightning(
-                        &self.assets.lightning_sound,
-                        vec2(x, y),
-                        1.0,
-                    ));
+                    self.strike_lightning(vec2(x, y));
+                    self.sparks.burst(&particles::sparks(), vec2(x, y), 40);
                 }
             }
         }
     }
 
+    /// Gives how hard the storm is raging from 0.0 to 1.0, it gets worse as boxes break.
+    fn storm_intensity(&self) -> f32 {
+        let total = self.game.electrical_boxes().len().max(1) as f32;
+        let broken = total - self.game.get_working_boxes() as f32;
+        0.4 + 0.6 * broken / total
+    }
+
+    fn update_particles(&mut self, delta: f32) {
+        // rain only needs to fall where the player can see it
+        let view_min = self.camera.view().screen_to_world(vec2(0.0, screen_height()));
+        let view_max = self.camera.view().screen_to_world(vec2(screen_width(), 0.0));
+        let view = Rect::new(
+            view_min.x,
+            view_min.y,
+            view_max.x - view_min.x,
+            view_max.y - view_min.y + 5.0,
+        );
+        self.rain.rate = MAX_RAIN_RATE * self.storm_intensity();
+        self.rain.update(&mut self.particles, view, delta);
+
+        for (i, player) in self.game.players().iter().enumerate() {
+            if self.game.player_in_puddle(i) && player.vel().length() > 0.5 {
+                let hit_box = player.hit_box();
+                let feet = Rect::new(hit_box.x, hit_box.y, hit_box.w, hit_box.h / 3.0);
+                self.splashes.update(&mut self.particles, feet, delta);
+            }
+        }
+
+        self.particles.update(delta);
+        self.sparks.update(delta);
+    }
+
+    fn new_box_animators(game: &Game) -> Vec<Animator> {
+        game.electrical_boxes()
+            .iter()
+            .map(|_| Animator::new("working"))
+            .collect()
+    }
+
+    fn new_crew_animators(game: &Game) -> Vec<Animator> {
+        game.crew().iter().map(|_| Animator::new("idle")).collect()
+    }
+
+    /// Picks every entity's clip from what it is doing in the game, then plays them.
     fn update_animations(&mut self, delta: f32) {
-        self.player_am.update(delta);
+        let mut events = Vec::new();
+
+        for (view, player) in self.players.iter_mut().zip(self.game.players()) {
+            let speed = player.vel().length();
+            view.animator.set(if speed > RUN_SPEED {
+                "run"
+            } else if speed > WALK_SPEED {
+                "walk"
+            } else {
+                "idle"
+            });
+            view.animator.update(&self.assets.player, delta, &mut events);
+            for event in events.drain(..) {
+                if event == AnimationEvent::Footstep {
+                    let feet = rect_center(player.hit_box());
+                    self.audio.play_at(self.assets.sound("walk"), feet, 1.0);
+                }
+            }
+        }
+
+        let crew = self.game.crew();
+        for ((animator, facing_left), member) in self.crew_animators.iter_mut().zip(&mut self.crew_facing_left).zip(crew) {
+            let vel = member.vel();
+            if vel.x.abs() > WALK_SPEED {
+                *facing_left = vel.x < 0.0;
+            }
+            animator.set(if vel.length() > WALK_SPEED { "walk" } else { "idle" });
+            animator.update(&self.assets.crew, delta, &mut events);
+            for event in events.drain(..) {
+                if event == AnimationEvent::Footstep {
+                    // quieter than the player's own steps so they don't crowd them out
+                    self.audio
+                        .play_at(self.assets.sound("walk"), rect_center(member.hit_box()), 0.5);
+                }
+            }
+        }
+
+        for (animator, ebox) in self.box_animators.iter_mut().zip(self.game.electrical_boxes()) {
+            animator.set(if *ebox.broken() { "sparking" } else { "working" });
+            animator.update(&self.assets.electrical_box, delta, &mut events);
+            for event in events.drain(..) {
+                if event == AnimationEvent::Spark {
+                    self.sparks
+                        .burst(&particles::sparks(), rect_center(ebox.hit_box()), 4);
+                }
+            }
+        }
+
+        let generator = self.game.generator();
+        self.generator_animator
+            .set(if generator.running() && generator.feul() > 0.0 {
+                "running"
+            } else {
+                "off"
+            });
+        self.generator_animator
+            .update(&self.assets.generator, delta, &mut events);
+
+        // flash the beacon while someone has kits to pick up
+        let max_kits = *self.game.max_number_of_repair_kits();
+        self.van_animator.set(
+            if self.game.players().iter().any(|player| *player.repair_kits() < max_kits) {
+                "beacon"
+            } else {
+                "parked"
+            },
+        );
+        self.van_animator.update(&self.assets.van, delta, &mut events);
     }
 
     fn update_lighnings(&mut self, delta: f32) {
         let mut i = 0;
 
         if self.lightning_timer.is_active() {
-            let x = gen_range(0.0, 1600.0 / PIXELS_PER_UNIT);
-            let y = gen_range(0.0, 800.0 / PIXELS_PER_UNIT);
-            self.lightnings.push(App::new_lightning(
-                &self.assets.lightning_sound,
-                vec2(x, y),
-                1.0,
-            ));
+            let x = gen_range(0.0, self.game.map_width());
+            let y = gen_range(0.0, self.game.map_height());
+            self.strike_lightning(vec2(x, y));
         }
 
         while i < self.lightnings.len() {
             self.lightnings[i].update(delta);
             if self.lightnings[i].current_duration() >= self.lightnings[i].max_duration() {
-                self.lightnings.remove(i as usize);
+                self.lightnings.remove(i);
                 continue;
             }
             i += 1
         }
     }
 
-    fn player_key_input(&mut self) {
-        let mut vel = vec2(0.0, 0.0);
-        let mut speed = 2.0;
+    /// Shakes the controller and the screen and jolts the player, the closer the strike the harder.
+    fn react_to_strike(&mut self, origin: Vec2) {
+        self.rumble_for_strike(origin);
+        self.shake_for_strike(origin);
+        self.shock_if_near(origin);
+    }
 
-        if is_key_down(KeyCode::LeftShift) {
-            speed *= 1.5
+    fn shake_for_strike(&mut self, origin: Vec2) {
+        if !self.settings.screen_shake {
+            return;
         }
-
-        if is_key_down(KeyCode::D) {
-            vel.x += 1.0;
-            self.player_facing_left = false;
+        let distance = self.nearest_player_distance(origin);
+        if distance < SHAKE_DISTANCE {
+            self.camera.shake(0.8 * (1.0 - distance / SHAKE_DISTANCE));
         }
-        if is_key_down(KeyCode::A) {
-            vel.x -= 1.0;
-            self.player_facing_left = true;
+    }
+
+    fn rumble_for_strike(&mut self, origin: Vec2) {
+        if !self.settings.rumble {
+            return;
         }
-        if is_key_down(KeyCode::W) {
-            vel.y += 1.0;
+        let distance = self.nearest_player_distance(origin);
+        if distance < RUMBLE_DISTANCE {
+            self.gamepads.rumble(1.0 - distance / RUMBLE_DISTANCE, 0.3);
         }
-        if is_key_down(KeyCode::S) {
-            vel.y -= 1.0;
+    }
+
+    /// Jolts players when lightning strikes right next to them.
+    fn shock_if_near(&mut self, origin: Vec2) {
+        for i in 0..self.players.len() {
+            if origin.distance(self.player_center(i)) < SHOCK_DISTANCE {
+                self.players[i].animator.play_once("shocked");
+            }
         }
+    }
 
-        let amount = vel.normalize_or_zero() * speed;
-        self.game.player_mut().add_velocity(amount);
+    fn player_center(&self, index: usize) -> Vec2 {
+        rect_center(self.game.players()[index].hit_box())
+    }
 
-        self.player_fm.update(amount.length() / PIXELS_PER_UNIT);
-        self.player_fm.try_sound(&self.assets.walk_sound);
+    /// Gives the players playing at this screen, everyone unless on a server.
+    fn local_players(&self) -> Vec<usize> {
+        match &self.network {
+            Some(network) => vec![network.player()],
+            None => (0..self.players.len()).collect(),
+        }
     }
 
-    fn draw_player(&self) {
-        let player = self.game.player();
+    /// Gives the middle of the players at this screen, where sounds are heard from.
+    fn group_center(&self) -> Vec2 {
+        let local = self.local_players();
+        let sum = local
+            .iter()
+            .fold(Vec2::ZERO, |sum, i| sum + self.player_center(*i));
+        sum / local.len() as f32
+    }
 
-        let texture = &self.assets.player_animation[self.player_am.frame_index];
+    fn nearest_player_distance(&self, point: Vec2) -> f32 {
+        self.local_players()
+            .into_iter()
+            .map(|i| self.player_center(i).distance(point))
+            .fold(f32::INFINITY, f32::min)
+    }
 
-        let draw_param = DrawTextureParams {
-            dest_size: Some(vec2(
-                texture.width() / PIXELS_PER_UNIT,
-                texture.height() / PIXELS_PER_UNIT,
-            )),
-            flip_x: self.player_facing_left,
-            flip_y: true,
-            ..DrawTextureParams::default()
-        };
+    /// Gives what each player is pressing, in co-op the keyboard is split
+    /// between the first two players and the others play with gamepads.
+    fn player_actions(&self) -> Vec<ActionState> {
+        if self.players.len() == 1 {
+            return vec![self.actions.clone()];
+        }
+        let second_keyboard = Bindings::second_keyboard();
+        (0..self.players.len())
+            .map(|i| {
+                let mut actions = match i {
+                    0 => {
+                        let mut actions =
+                            ActionState::from_keyboard_without(&self.settings.bindings, &second_keyboard.all_keys());
+                        actions.set_dispatch_target(self.actions.dispatch_target());
+                        actions
+                    }
+                    1 => ActionState::from_keyboard(&second_keyboard),
+                    _ => ActionState::default(),
+                };
+                self.gamepads.update_player(i, &mut actions);
+                actions
+            })
+            .collect()
+    }
 
-        let hit_box = player.hit_box();
-        draw_texture_ex(*texture, hit_box.x, hit_box.y, WHITE, draw_param);
+    /// Drives every player at this screen, giving what they pressed to record.
+    fn player_input(&mut self) -> Vec<PlayerInput> {
+        let mut inputs = vec![];
+        for (i, actions) in self.player_actions().iter().enumerate() {
+            self.players[i].face(actions.movement());
+            // the recording plays back through the same input, so it moves players exactly the same
+            let input = PlayerInput::from_actions(actions);
+            drive_player(&mut self.game, i, &input.actions());
+            inputs.push(input);
+        }
+        inputs
     }
 
-    fn draw_electical_box(&self, electrical_box: &ElectricalBox) {
-        let texture: &Texture2D = if *electrical_box.broken() {
-            &self.assets.electrical_box_broken
-        } else {
-            &self.assets.electrical_box
+    /// Sends what the local player pressed to the server, everyone else faces
+    /// the way the server moves them.
+    fn network_input(&mut self, delta: f32) {
+        let Some(network) = &mut self.network else {
+            return;
         };
-
-        let draw_param = DrawTextureParams {
-            dest_size: Some(vec2(
-                texture.width() / PIXELS_PER_UNIT,
-                texture.height() / PIXELS_PER_UNIT,
-            )),
-            flip_y: true,
-            ..DrawTextureParams::default()
+        let local = network.player();
+        if let Err(e) = network.send_input(&mut self.game, &self.actions, delta) {
+            self.lose_connection(e);
+            return;
+        }
+
+        for (i, view) in self.players.iter_mut().enumerate() {
+            if i == local {
+                view.face(self.actions.movement());
+            } else {
+                let vel = self.game.players()[i].vel();
+                if vel.length() > WALK_SPEED {
+                    view.face(vel);
+                }
+            }
+        }
+    }
+
+    /// Applies whatever the server sent, on every screen so the run is up to date when it shows.
+    fn poll_network(&mut self) {
+        let Some(network) = &mut self.network else {
+            return;
         };
+        match network.receive(&mut self.game, &self.level, &self.rules, get_time()) {
+            Ok(true) => {
+                self.reset_run();
+                if self.game_state == GameState::End {
+                    self.game_state = GameState::Running;
+                }
+            }
+            Ok(false) => {}
+            Err(e) => self.lose_connection(e),
+        }
+        // nobody is around to hear what happened while away from the city
+        if self.game_state != GameState::Running {
+            while self.game.poll_event().is_some() {}
+        }
+    }
+
+    /// Drops the server and ends the run, the next one is played offline.
+    fn lose_connection(&mut self, error: String) {
+        eprintln!("{}", error);
+        self.network = None;
+        self.connection_error = Some(error);
+        self.game_state = GameState::End;
+    }
+
+    fn draw_players(&self) {
+        let sheet = &self.assets.player.sheet;
+        for (i, (view, player)) in self.players.iter().zip(self.game.players()).enumerate() {
+            let frame = view.animator.frame(&self.assets.player);
+            let hit_box = player.hit_box();
+            sheet.draw(frame, vec2(hit_box.x, hit_box.y), view.facing_left, PLAYER_COLOURS[i % PLAYER_COLOURS.len()]);
+        }
+    }
+
+    fn draw_crew(&self) {
+        let crew = &self.assets.crew;
+        for ((member, animator), facing_left) in self.game.crew().iter().zip(&self.crew_animators).zip(&self.crew_facing_left) {
+            let hit_box = member.hit_box();
+            crew.sheet
+                .draw(animator.frame(crew), vec2(hit_box.x, hit_box.y), *facing_left, WHITE);
+        }
+    }
 
+    fn draw_electical_box(&self, electrical_box: &ElectricalBox, animator: &Animator) {
+        let boxes = &self.assets.electrical_box;
         let hit_box = electrical_box.hit_box();
-        draw_texture_ex(*texture, hit_box.x, hit_box.y, WHITE, draw_param);
+        boxes
+            .sheet
+            .draw(animator.frame(boxes), vec2(hit_box.x, hit_box.y), false, WHITE);
     }
 
     fn draw_electical_boxes(&self) {
-        for ebox in self.game.electrical_boxes() {
-            self.draw_electical_box(ebox);
+        for (ebox, animator) in self.game.electrical_boxes().iter().zip(&self.box_animators) {
+            self.draw_electical_box(ebox, animator);
         }
     }
 
-    fn draw_lightning(&self, lightning: &Lightning) {
-        let len = lightning.points().len();
+    fn draw_van(&self) {
+        let van = &self.assets.van;
+        // the restock area is the van grown by a unit on every side
+        let restock = self.game.restock();
+        let body = Rect::new(restock.x + 1.0, restock.y + 1.0, restock.w - 2.0, restock.h - 2.0);
+        if body.point().distance(PICTURE_VAN.point()) > 0.01 {
+            // white like the one in the picture, with its windscreen at the front
+            draw_rectangle(body.x, body.y, body.w, body.h, WHITE);
+            draw_rectangle(body.right() - 0.35, body.y + 0.2, 0.15, body.h - 0.4, Color::new(0.3, 0.4, 0.9, 1.0));
+        }
+        let size = van.sheet.frame_size();
+        let position = vec2(
+            restock.x + restock.w / 2.0 - size.x / 2.0,
+            restock.y + restock.h - 1.0 - size.y,
+        );
+        van.sheet
+            .draw(self.van_animator.frame(van), position, false, WHITE);
+    }
 
-        for (i, point) in lightning.points().iter().enumerate() {
-            if i + 1 == len {
-                break;
+    fn draw_lightning(&self, lightning: &Lightning) {
+        let fade = 1.0 - lightning.current_duration() / lightning.max_duration();
+
+        for channel in lightning.channels() {
+            for pair in channel.points.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                // wide faint glow first, then the bright core on top
+                draw_line(a.x, a.y, b.x, b.y, channel.thickness * 6.0, Color::new(0.3, 0.4, 1.0, 0.15 * fade));
+                draw_line(a.x, a.y, b.x, b.y, channel.thickness * 2.5, Color::new(0.5, 0.6, 1.0, 0.35 * fade));
+                draw_line(a.x, a.y, b.x, b.y, channel.thickness, Color::new(0.9, 0.95, 1.0, fade));
             }
-            let bottom_point = point;
-            let top_point = lightning.points()[i + 1];
-
-            draw_circle(lightning.points()[i].x, lightning.points()[i].y, 0.05, BLUE);
-
-            draw_line(
-                bottom_point.x,
-                bottom_point.y,
-                top_point.x,
-                top_point.y,
-                0.1,
-                BLUE,
-            );
         }
     }
 
@@ -447,7 +1491,7 @@ impl App {
     }
 
     fn draw_puddle(&self, puddle: &Puddle) {
-        let texture = &self.assets.puddle;
+        let texture = &self.assets.texture("puddle");
         let draw_param = DrawTextureParams {
             dest_size: Some(vec2(
                 texture.width() / PIXELS_PER_UNIT,
@@ -477,114 +1521,1415 @@ impl App {
             10.,
             YELLOW,
         );
-        draw_texture(self.assets.generator, 12.5, 12.5, WHITE);
+        let generator = &self.assets.generator;
+        generator
+            .sheet
+            .draw_screen(self.generator_animator.frame(generator), vec2(12.5, 12.5), 1.0);
     }
 
+    /// Draws a row of kits for every player, tinted in their colour when playing together.
     fn draw_repair_kit_ui(&self) {
         let width = (*self.game.max_number_of_repair_kits() as f32 + 1.0) * 25.0 + 5.0;
-        draw_rectangle(10., 40., width, 20., DARKGRAY);
+        let players = self.game.players();
+        for (row, player) in players.iter().enumerate() {
+            let y = 40.0 + row as f32 * 25.0;
+            draw_rectangle(10., y, width, 20., DARKGRAY);
+
+            for i in 1..=*player.repair_kits() {
+                let offset = i as f32 * 25.0;
+                draw_rectangle(15.0 + offset, y + 5.0, 20.0, 10., RED);
+            }
+            let tint = if players.len() > 1 { PLAYER_COLOURS[row % PLAYER_COLOURS.len()] } else { WHITE };
+            draw_texture(self.assets.texture("repair_kit"), 15.0, y + 5.0, tint)
+        }
+    }
 
-        for i in 1..=*self.game.number_of_repair_kits() {
-            let offset = i as f32 * 25.0;
-            draw_rectangle(15.0 + offset, 45.0, 20.0, 10., RED);
+    /// Gives where the fuel bar and the repair kit rows are on the screen.
+    fn hud_rects(&self) -> Vec<Rect> {
+        let kits_width = (*self.game.max_number_of_repair_kits() as f32 + 1.0) * 25.0 + 5.0;
+        let mut rects = vec![Rect::new(10.0, 10.0, 145.0, 20.0)];
+        for row in 0..self.game.players().len() {
+            rects.push(Rect::new(10.0, 40.0 + row as f32 * 25.0, kits_width, 20.0));
         }
-        draw_texture(self.assets.repair_kit, 15.0, 45.0, WHITE)
+        rects
+    }
+
+    /// Gives where the text under the repair kit rows starts.
+    fn hud_text_top(&self) -> f32 {
+        80.0 + (self.game.players().len() - 1) as f32 * 25.0
     }
 
     fn draw_score(&self) {
-        draw_text(&format!("Score: {}", self.score + self.get_timer_score()), 10.0, 80.0, 25.0, WHITE);
-        draw_text(&format!("Time: {:.2}", self.time_survived), 10.0, 110.0, 25.0, WHITE);
+        let text_scale = self.settings.text_scale;
+        let top = self.hud_text_top();
+        draw_text(&format!("Score: {}", self.score + self.get_timer_score()), 10.0, top, 25.0 * text_scale, WHITE);
+        draw_text(&format!("Time: {:.2}", self.time_survived), 10.0, top + 30.0 * text_scale, 25.0 * text_scale, WHITE);
+    }
+
+    fn draw_crew_ui(&self) {
+        let crew = self.game.crew();
+        if crew.is_empty() {
+            return;
+        }
+        let free = crew
+            .iter()
+            .filter(|member| !matches!(member.task(), CrewTask::Repair(_)) && *member.repair_kits() > 0)
+            .count();
+        let text_scale = self.settings.text_scale;
+        let text = format!("Crew ready: {}/{} <{}> to send", free, crew.len(), self.key_hint(Action::Dispatch));
+        draw_text(&text, 10.0, self.hud_text_top() + 60.0 * text_scale, 25.0 * text_scale, ORANGE);
     }
 
-    fn new_lightning(sound: &Sound, origin: Vec2, max_duration: f32) -> Lightning {
-        let sound_params = PlaySoundParams {
-            ..PlaySoundParams::default()
+    /// Lists the objectives of the campaign level being played, green once they are met.
+    fn draw_objectives_ui(&self) {
+        let Some(run) = &self.campaign_run else {
+            return;
+        };
+        let level = &self.campaign.levels()[run.level()];
+        let text_scale = self.settings.text_scale;
+        let top = self.hud_text_top() + 90.0 * text_scale;
+        draw_text(&level.name, 10.0, top, 25.0 * text_scale, YELLOW);
+        for (i, objective) in level.objectives.iter().enumerate() {
+            let colour = if objective.met(run, &self.game) { GREEN } else { WHITE };
+            let y = top + 25.0 * text_scale * (i + 1) as f32;
+            draw_text(&objective.progress(run, &self.game), 10.0, y, 20.0 * text_scale, colour);
+        }
+    }
+
+    /// Circles where the tutorial wants the player to go.
+    fn draw_tutorial_target(&self) {
+        let Some(target) = self.tutorial.as_ref().and_then(|tutorial| tutorial.target(&self.game)) else {
+            return;
+        };
+        let radius = 1.5 + (get_time() as f32 * 5.0).sin() * 0.3;
+        draw_circle_lines(target.x, target.y, radius, 0.15, YELLOW);
+    }
+
+    /// Shows what the tutorial asks for, and points at the target when it's off screen.
+    fn draw_tutorial_ui(&self) {
+        let Some(tutorial) = &self.tutorial else {
+            return;
+        };
+        let text_scale = self.settings.text_scale;
+        let size = 22.0 * text_scale;
+        let mut lines = vec![tutorial.message().to_string()];
+        if tutorial.step() == TutorialStep::Done {
+            lines.push(format!("Press <{}> to go back to the title", self.key_hint(Action::Confirm)));
+        }
+        let width = lines
+            .iter()
+            .map(|line| measure_text(line, None, size as u16, 1.0).width)
+            .fold(0.0, f32::max);
+        let line_height = size * 1.3;
+        let top = screen_height() - 90.0 - line_height * lines.len() as f32;
+        let left = (screen_width() - width) / 2.0;
+        let box_height = line_height * lines.len() as f32 + 10.0;
+        draw_rectangle(left - 10.0, top, width + 20.0, box_height, Color::new(0.0, 0.0, 0.0, 0.7));
+        for (i, line) in lines.iter().enumerate() {
+            draw_text(line, left, top + line_height * (i as f32 + 1.0), size, WHITE);
+        }
+
+        let pulse = (get_time() as f32 * 5.0).sin() * 0.5 + 0.5;
+        let highlight = Color::new(1.0, 1.0, 0.0, 0.5 + pulse * 0.5);
+        if matches!(tutorial.step(), TutorialStep::Blackout | TutorialStep::SaveFuel) {
+            // the fuel bar
+            draw_rectangle_lines(6.0, 6.0, 153.0, 28.0, 3.0, highlight);
+        }
+
+        let Some(target) = tutorial.target(&self.game) else {
+            return;
         };
+        let on_screen = self.camera.view().world_to_screen(target);
+        let margin = 30.0;
+        let inside = Rect::new(margin, margin, screen_width() - margin * 2.0, screen_height() - margin * 2.0);
+        if inside.contains(on_screen) {
+            return;
+        }
+        let center = vec2(screen_width(), screen_height()) / 2.0;
+        let direction = (on_screen - center).normalize_or_zero();
+        let tip = on_screen.clamp(inside.point(), inside.point() + inside.size());
+        let side = vec2(-direction.y, direction.x) * 10.0;
+        let back = tip - direction * 20.0;
+        draw_triangle(tip, back + side, back - side, highlight);
+    }
 
-        play_sound(*sound, sound_params);
+    /// Gives where the map is on the screen and how many pixels a unit of the city takes on it.
+    fn minimap_layout(&self) -> (Rect, f32) {
+        let width = (screen_width() * 0.3).min(400.0);
+        let scale = width / self.game.map_width();
+        let height = self.game.map_height() * scale;
+        (Rect::new(screen_width() - width - 10.0, 10.0, width, height), scale)
+    }
 
-        Lightning::new(origin, max_duration)
+    /// Gives the broken box clicked on the map this frame, to send a helper to.
+    fn minimap_pick(&self) -> Option<usize> {
+        if !self.show_minimap || !is_mouse_button_pressed(MouseButton::Left) {
+            return None;
+        }
+        let (area, scale) = self.minimap_layout();
+        let mouse = Vec2::from(mouse_position());
+        if !area.contains(mouse) {
+            return None;
+        }
+        // the world's y axis points up while the screen's points down
+        let clicked = vec2((mouse.x - area.x) / scale, self.game.map_height() - (mouse.y - area.y) / scale);
+        self.game
+            .electrical_boxes()
+            .iter()
+            .enumerate()
+            .filter(|(_, ebox)| *ebox.broken())
+            .map(|(i, ebox)| (i, rect_center(ebox.hit_box()).distance(clicked) * scale))
+            .filter(|(_, distance)| *distance < MINIMAP_PICK_RADIUS)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
     }
 
-    fn lock_camera(&mut self) {
-        let screen_x = self.camera.screen_to_world(vec2(0.0, 0.0)).x;
-        let screen_y = self.camera.screen_to_world(vec2(0.0, screen_height())).y;
+    fn draw_minimap(&self) {
+        let map_height = self.game.map_height();
+        let (area, scale) = self.minimap_layout();
+        let (left, top, width, height) = (area.x, area.y, area.w, area.h);
+
+        // the world's y axis points up while the screen's points down
+        let to_screen = |rect: &Rect| {
+            Rect::new(
+                left + rect.x * scale,
+                top + (map_height - rect.y - rect.h) * scale,
+                rect.w * scale,
+                rect.h * scale,
+            )
+        };
+
+        draw_rectangle(left - 2.0, top - 2.0, width + 4.0, height + 4.0, DARKGRAY);
+        let texture = self.assets.texture("map");
+        let source = self.map_source(&texture);
+        let picture = source.size() / PIXELS_PER_UNIT * scale;
+        let draw_param = DrawTextureParams {
+            dest_size: Some(picture),
+            source: Some(source),
+            ..DrawTextureParams::default()
+        };
+        draw_texture_ex(texture, left, top + height - picture.y, WHITE, draw_param);
+
+        for wall in self.game.walls() {
+            let rect = to_screen(wall.hit_box());
+            draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 1.0, GRAY);
+        }
+
+        let restock = to_screen(self.game.restock());
+        draw_rectangle_lines(restock.x, restock.y, restock.w, restock.h, 2.0, YELLOW);
+
+        for puddle in self.game.puddles() {
+            let rect = to_screen(puddle.hit_box());
+            draw_rectangle(rect.x, rect.y, rect.w, rect.h, Color::new(0.2, 0.4, 1.0, 0.6));
+        }
+
+        for ebox in self.game.electrical_boxes() {
+            let rect = to_screen(ebox.hit_box());
+            let colour = if *ebox.broken() { RED } else { GREEN };
+            draw_rectangle(rect.x, rect.y, rect.w.max(3.0), rect.h.max(3.0), colour);
+        }
+
+        let view_min = self.camera.view().screen_to_world(vec2(0.0, screen_height()));
+        let view_max = self.camera.view().screen_to_world(vec2(screen_width(), 0.0));
+        let view = to_screen(&Rect::new(
+            view_min.x,
+            view_min.y,
+            view_max.x - view_min.x,
+            view_max.y - view_min.y,
+        ));
+        draw_rectangle_lines(view.x, view.y, view.w, view.h, 1.0, WHITE);
+
+        let mut previous = self.player_center(self.local_players()[0]);
+        for point in &self.repair_route {
+            let from = to_screen(&Rect::new(previous.x, previous.y, 0.0, 0.0));
+            let to = to_screen(&Rect::new(point.x, point.y, 0.0, 0.0));
+            draw_line(from.x, from.y, to.x, to.y, 2.0, YELLOW);
+            previous = *point;
+        }
+
+        for member in self.game.crew() {
+            let rect = to_screen(member.hit_box());
+            let mut previous = vec2(rect.x + rect.w / 2.0, rect.y + rect.h / 2.0);
+            for point in member.path() {
+                let next = to_screen(&Rect::new(point.x, point.y, 0.0, 0.0));
+                draw_line(previous.x, previous.y, next.x, next.y, 1.0, Color::new(1.0, 0.6, 0.0, 0.6));
+                previous = next.point();
+            }
+            draw_circle(rect.x + rect.w / 2.0, rect.y + rect.h / 2.0, 2.5, ORANGE);
+        }
+
+        for (i, player) in self.game.players().iter().enumerate() {
+            let player = to_screen(player.hit_box());
+            draw_circle(player.x + player.w / 2.0, player.y + player.h / 2.0, 3.0, PLAYER_COLOURS[i % PLAYER_COLOURS.len()]);
+        }
+    }
 
-        if screen_x < 0.0 {
-            self.camera.offset.x = -1.0;
+    /// Finds the closest broken box by how far it is to walk there, not as the crow flies.
+    fn update_repair_route(&mut self, delta: f32) {
+        if !self.show_minimap {
+            self.repair_route.clear();
+            return;
         }
-        if screen_y < 0.0 {
-            self.camera.offset.y = -1.0;
+        self.route_timer -= delta;
+        if self.route_timer > 0.0 {
+            return;
         }
+        self.route_timer = ROUTE_INTERVAL;
+
+        let player = self.local_players()[0];
+        let from = self.player_center(player);
+        let agent_size = self.game.players()[player].hit_box().w;
+        let broken: Vec<Vec2> = self
+            .game
+            .electrical_boxes()
+            .iter()
+            .filter(|ebox| *ebox.broken())
+            .map(|ebox| rect_center(ebox.hit_box()))
+            .collect();
+        self.repair_route = self
+            .game
+            .find_nearest_path(from, &broken, agent_size)
+            .map(|(_, path)| path)
+            .unwrap_or_default();
+    }
 
-        let screen_x = self.camera.screen_to_world(vec2(screen_width(), 0.0)).x;
-        let screen_y = self.camera.screen_to_world(vec2(0.0, 0.0)).y;
+    /// Strikes lightning at `origin`, with its flash, its sound and everything it shakes.
+    fn strike_lightning(&mut self, origin: Vec2) {
+        let seed = self.bolt_rng.next_u32() as u64;
+        self.lightnings
+            .push(Lightning::with_config(origin, 1.0, BoltConfig::default(), Rng::new(seed)));
+        // the flash is seen at once but the thunder takes its time to arrive
+        let distance = origin.distance(self.group_center());
+        self.thunder.push(Thunder {
+            origin,
+            time_left: distance / SPEED_OF_SOUND,
+        });
+        self.react_to_strike(origin);
+    }
+
+    fn update_thunder(&mut self, delta: f32) {
+        let listener = self.group_center();
+        for thunder in &mut self.thunder {
+            thunder.time_left -= delta;
+            if thunder.time_left <= 0.0 {
+                let sound = if thunder.origin.distance(listener) > DISTANT_THUNDER {
+                    "thunder_rumble"
+                } else {
+                    "lightning"
+                };
+                self.audio.play_at(self.assets.sound(sound), thunder.origin, 1.0);
+            }
+        }
+        self.thunder.retain(|thunder| thunder.time_left > 0.0);
+    }
 
-        let width = 1600.0 / PIXELS_PER_UNIT;
-        let height = 800.0 / PIXELS_PER_UNIT;
+    /// Zooms with the zoom keys or the mouse wheel, a step per press or notch.
+    fn zoom_input(&mut self, delta: f32) {
+        let mut steps = 0.0;
+        if self.actions.is_pressed(Action::ZoomIn) {
+            steps += 1.0;
+        }
+        if self.actions.is_pressed(Action::ZoomOut) {
+            steps -= 1.0;
+        }
+        let wheel = mouse_wheel().1;
+        if wheel > 0.0 {
+            steps += 1.0;
+        } else if wheel < 0.0 {
+            steps -= 1.0;
+        }
 
-        if screen_x > width {
-            self.camera.offset.x = -width * self.camera.zoom.x + 1.0;
+        if steps != 0.0 {
+            self.settings.camera_zoom = (self.settings.camera_zoom + 0.1 * steps)
+                .clamp(Settings::MIN_CAMERA_ZOOM, Settings::MAX_CAMERA_ZOOM);
+            self.zoom_save_timer = Some(ZOOM_SAVE_DELAY);
+        } else if let Some(left) = self.zoom_save_timer {
+            if left > delta {
+                self.zoom_save_timer = Some(left - delta);
+            } else {
+                self.save_zoom();
+            }
         }
-        if screen_y > height {
-            self.camera.offset.y = -height * self.camera.zoom.y + 1.0;
+    }
+
+    /// Saves a zoom change that is still waiting, for when the run stops.
+    fn save_zoom(&mut self) {
+        if self.zoom_save_timer.take().is_some() {
+            self.settings.save();
         }
     }
 
     fn update_score(&mut self, delta: f32) {
-        self.time_survived += delta;
+        match &self.network {
+            // the server keeps count so everyone sees the same
+            Some(network) => {
+                self.score = network.score();
+                self.time_survived = network.time();
+            }
+            None => self.time_survived += delta,
+        }
     }
 
     fn get_timer_score(&self) -> f32 {
-        let score = self.time_survived.floor();
-        
-        score*10.0
-    } 
+        timer_score(self.time_survived)
+    }
+
+    /// Gives how many players a run has, set by the server when on one.
+    fn player_count(&self) -> usize {
+        match &self.network {
+            Some(network) => network.players(),
+            None => self.settings.players as usize,
+        }
+    }
+
+    /// Starts a new run, of the campaign level being played if there is one.
+    fn restart(&mut self) {
+        let mut seed = new_seed();
+        let mut players = self.player_count();
+        let (level, rules) = match &mut self.campaign_run {
+            // the lessons follow the first player
+            _ if self.tutorial.is_some() => {
+                players = 1;
+                Tutorial::level(&self.rules)
+            }
+            // everyone plays the day's storm alone so scores can be compared
+            _ if self.daily_attempt.is_some() => {
+                seed = self.daily_challenge.seed;
+                players = 1;
+                (self.daily_challenge.level.clone(), self.daily_challenge.rules)
+            }
+            Some(run) => {
+                *run = CampaignRun::new(run.level());
+                let level = &self.campaign.levels()[run.level()];
+                (level.level.clone(), level.rules)
+            }
+            None => (self.level.clone(), self.endless_rules()),
+        };
+        self.game = Game::new(&level, &rules, seed, players);
+        // the tutorial and the sandbox break boxes themselves, which a replay wouldn't know about
+        self.recording = match (&self.network, &mut self.tutorial) {
+            (Some(_), _) => None,
+            (None, Some(tutorial)) => {
+                tutorial.start(&mut self.game);
+                None
+            }
+            _ if self.sandbox.is_some() => None,
+            (None, None) => Some(Recording::new(seed, players, &level, &rules)),
+        };
+        self.reset_run();
+    }
+
+    /// Gives the rules of an endless run, made easier or harder by the difficulty setting.
+    fn endless_rules(&self) -> Rules {
+        match self.network {
+            // the server plays by its own rules
+            Some(_) => self.rules,
+            None => self.settings.difficulty.apply(self.rules),
+        }
+    }
+
+    /// Starts the campaign level at `index` from the beginning.
+    fn start_level(&mut self, index: usize) {
+        self.leave_modes();
+        self.campaign_run = Some(CampaignRun::new(index));
+        self.restart();
+        self.game_state = GameState::Running;
+        get_frame_time();
+    }
+
+    /// Stops playing a campaign level, the daily challenge, the tutorial or the sandbox, giving whether one was.
+    fn leave_modes(&mut self) -> bool {
+        let campaign = self.campaign_run.take().is_some();
+        let daily = self.daily_attempt.take().is_some();
+        let tutorial = self.tutorial.take().is_some();
+        let sandbox = self.sandbox.take().is_some();
+        campaign || daily || tutorial || sandbox
+    }
+
+    /// Uses the sandbox tool where the city was clicked and keeps the generator going.
+    fn update_sandbox(&mut self) {
+        let mouse = Vec2::from(mouse_position());
+        // clicks on the controls, the HUD and the map are theirs, not the city's
+        let on_ui = self.touch_controls.contains(mouse)
+            || self.hud_rects().iter().any(|rect| rect.contains(mouse))
+            || (self.show_minimap && self.minimap_layout().0.contains(mouse));
+        let Some(sandbox) = &mut self.sandbox else {
+            return;
+        };
+        let mut strike = None;
+        if is_mouse_button_pressed(MouseButton::Left) && !on_ui {
+            let world = self.camera.view().screen_to_world(mouse);
+            strike = sandbox.click(&mut self.game, mouse, world, self.settings.text_scale);
+        }
+        sandbox.update(&mut self.game);
+        if let Some(origin) = strike {
+            self.strike_lightning(origin);
+        }
+    }
+
+    /// Marks what a click would hit in the sandbox.
+    fn draw_sandbox_cursor(&self) {
+        if let Some(sandbox) = &self.sandbox {
+            let world = self.camera.view().screen_to_world(Vec2::from(mouse_position()));
+            sandbox.draw_cursor(&self.game, world);
+        }
+    }
+
+    /// Checks off the objectives of the campaign level being played and keeps the stars earned.
+    fn finish_level(&mut self) {
+        if let Some(run) = &mut self.campaign_run {
+            let stars = run.finish(&self.campaign.levels()[run.level()], &self.game);
+            self.campaign.record(run.level(), stars);
+            self.level_end_menu.reset();
+        }
+    }
+
+    /// Clears everything left over from the last run once `game` is a new one.
+    fn reset_run(&mut self) {
+        self.camera.snap(self.group_center(), self.settings.camera_zoom);
+        self.lightnings.clear();
+        self.thunder.clear();
+        self.repair_route.clear();
+        self.route_timer = 0.0;
+        self.particles.clear();
+        self.sparks.clear();
+        self.players = self.game.players().iter().map(|_| PlayerView::new()).collect();
+        self.box_animators = App::new_box_animators(&self.game);
+        self.crew_animators = App::new_crew_animators(&self.game);
+        self.crew_facing_left = vec![false; self.crew_animators.len()];
+        self.score = 0.0;
+        self.time_survived = 0.0;
+    }
+
+    /// Gives the map and rules to play offline, from disk in dev mode since
+    /// designers want their files, not the ones the game was built with.
+    fn local_level() -> (Level, Rules) {
+        if !dev_mode() {
+            return (Level::embedded(), Rules::embedded());
+        }
+        App::read_level().unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            (Level::embedded(), Rules::embedded())
+        })
+    }
+
+    /// Gives the campaign played on `level` with `rules`, from disk in dev mode.
+    fn local_campaign(level: &Level, rules: &Rules) -> Campaign {
+        if !dev_mode() {
+            return Campaign::embedded(level, rules);
+        }
+        App::read_campaign(level, rules).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            Campaign::embedded(level, rules)
+        })
+    }
+
+    fn read_level() -> Result<(Level, Rules), String> {
+        let level = Level::from_json(&read_json(MAP_PATH)?).map_err(|e| format!("{}: {}", MAP_PATH, e))?;
+        let rules = Rules::from_json(&read_json(RULES_PATH)?);
+        Ok((level, rules))
+    }
+
+    fn read_campaign(level: &Level, rules: &Rules) -> Result<Campaign, String> {
+        Campaign::from_json(&read_json(CAMPAIGN_PATH)?, level, rules).map_err(|e| format!("{}: {}", CAMPAIGN_PATH, e))
+    }
+
+    /// Reloads whatever changed on disk since the last frame, in dev mode.
+    async fn hot_reload(&mut self) {
+        let reloads = match &mut self.hot_reload {
+            Some(hot_reload) => hot_reload.poll(get_frame_time()),
+            None => return,
+        };
+
+        for reload in reloads {
+            match reload {
+                Reload::Level if self.network.is_some() => {
+                    eprintln!("not reloading {}, the server's map is being played", MAP_PATH)
+                }
+                Reload::Level => match App::read_level() {
+                    Ok((level, rules)) => {
+                        match App::read_campaign(&level, &rules) {
+                            Ok(campaign) => self.campaign = campaign,
+                            Err(e) => eprintln!("{}", e),
+                        }
+                        if self.campaign_run.as_ref().is_some_and(|run| run.level() >= self.campaign.levels().len()) {
+                            self.campaign_run = None;
+                        }
+                        let reloaded = match &self.campaign_run {
+                            Some(run) => {
+                                let campaign_level = &self.campaign.levels()[run.level()];
+                                self.game.reload(&campaign_level.level, &campaign_level.rules);
+                                true
+                            }
+                            // the tutorial keeps its own few boxes, and a daily attempt
+                            // the day's storm so it can still be recorded and verified
+                            None if self.tutorial.is_some() || self.daily_attempt.is_some() => false,
+                            None => {
+                                self.game.reload(&level, &self.settings.difficulty.apply(rules));
+                                true
+                            }
+                        };
+                        if reloaded {
+                            // the run no longer plays out from its seed
+                            self.recording = None;
+                            self.box_animators = App::new_box_animators(&self.game);
+                            self.crew_animators = App::new_crew_animators(&self.game);
+                            self.crew_facing_left = vec![false; self.crew_animators.len()];
+                        }
+                        if self.daily_attempt.is_none() {
+                            self.daily_challenge =
+                                DailyChallenge::new(today(), &daily_maps(&level, &self.campaign), &rules);
+                        }
+                        self.level = level;
+                        self.rules = rules;
+                        eprintln!("reloaded {}, {} and {}", MAP_PATH, RULES_PATH, CAMPAIGN_PATH);
+                    }
+                    Err(e) => eprintln!("{}", e),
+                },
+                Reload::Assets => match Assets::load().await {
+                    Ok(assets) => {
+                        self.assets = assets;
+                        eprintln!("reloaded assets");
+                    }
+                    Err(errors) => {
+                        for error in errors {
+                            eprintln!("{}", error);
+                        }
+                    }
+                },
+            }
+        }
+    }
+
+    /// Picks the music for the screen the player is on.
+    fn update_music(&mut self, delta: f32) {
+        let in_run = self.game_state == GameState::Running
+            || self.game_state == GameState::Paused
+            || self.settings_return == GameState::Paused
+                && matches!(self.game_state, GameState::Settings | GameState::Controls);
+        let mood = if self.game_state == GameState::End {
+            Mood::GameOver
+        } else if in_run {
+            let total = self.game.electrical_boxes().len().max(1) as f32;
+            let generator = self.game.generator();
+            Mood::Game {
+                working: self.game.get_working_boxes() as f32 / total,
+                generator_running: generator.running(),
+                fuel: generator.feul() / self.game.rules().generator_fuel.max(f32::EPSILON),
+                paused: self.game_state != GameState::Running,
+            }
+        } else {
+            Mood::Menu
+        };
+        self.music
+            .update(&mood, self.settings.effective_music_volume(), delta);
+    }
+
+    fn pause(&mut self) {
+        self.save_zoom();
+        self.pause_menu.reset();
+        self.game_state = GameState::Paused;
+    }
+
+    fn open_settings(&mut self) {
+        self.settings_menu.reset();
+        self.settings_return = self.game_state;
+        self.game_state = GameState::Settings;
+    }
+
+    fn update_pause_menu(&mut self) {
+        if self.actions.is_pressed(Action::Pause) {
+            self.game_state = GameState::Running;
+            return;
+        }
+
+        if let MenuAction::Select(index) = self.pause_menu.update(3, &self.actions) {
+            match index {
+                0 => self.game_state = GameState::Running,
+                1 => self.open_settings(),
+                _ => {
+                    self.leave_modes();
+                    self.restart();
+                    self.game_state = GameState::Start;
+                }
+            }
+        }
+    }
+
+    fn draw_pause_menu(&self) {
+        set_default_camera();
+        draw_rectangle(0.0, 0.0, screen_width(), screen_height(), Color::new(0.0, 0.0, 0.0, 0.6));
+        let items = [
+            "Resume".to_string(),
+            "Settings".to_string(),
+            "Quit to title".to_string(),
+        ];
+        self.pause_menu.draw("Paused", &items, self.settings.text_scale);
+    }
+
+    fn settings_items(&self) -> Vec<String> {
+        let on_off = |value: bool| if value { "On" } else { "Off" };
+        vec![
+            format!("Master volume: {:.0}%", self.settings.master_volume * 100.0),
+            format!("Effects volume: {:.0}%", self.settings.sfx_volume * 100.0),
+            format!("Music volume: {:.0}%", self.settings.music_volume * 100.0),
+            format!("Fullscreen: {}", on_off(self.settings.fullscreen)),
+            format!("Camera zoom: {:.1}x", self.settings.camera_zoom),
+            format!("Reduce flashing: {}", on_off(self.settings.reduce_flashing)),
+            format!("Text size: {:.2}x", self.settings.text_scale),
+            format!("Controller rumble: {}", on_off(self.settings.rumble)),
+            format!("Screen shake: {}", on_off(self.settings.screen_shake)),
+            format!("Players: {}", self.settings.players),
+            format!("Difficulty: {}", self.settings.difficulty.name()),
+            "Controls".to_string(),
+            "Back".to_string(),
+        ]
+    }
+
+    fn update_settings_menu(&mut self) {
+        if self.actions.is_pressed(Action::Pause) {
+            self.close_settings();
+            return;
+        }
+
+        let len = self.settings_items().len();
+        let (index, direction) = match self.settings_menu.update(len, &self.actions) {
+            MenuAction::None => return,
+            MenuAction::Select(index) => (index, 1.0),
+            MenuAction::Adjust(index, direction) => (index, direction),
+        };
+
+        let settings = &mut self.settings;
+        match index {
+            0 => settings.master_volume = (settings.master_volume + 0.1 * direction).clamp(0.0, 1.0),
+            1 => settings.sfx_volume = (settings.sfx_volume + 0.1 * direction).clamp(0.0, 1.0),
+            2 => settings.music_volume = (settings.music_volume + 0.1 * direction).clamp(0.0, 1.0),
+            3 => {
+                settings.fullscreen = !settings.fullscreen;
+                set_fullscreen(settings.fullscreen);
+            }
+            4 => {
+                settings.camera_zoom = (settings.camera_zoom + 0.1 * direction)
+                    .clamp(Settings::MIN_CAMERA_ZOOM, Settings::MAX_CAMERA_ZOOM)
+            }
+            5 => settings.reduce_flashing = !settings.reduce_flashing,
+            6 => {
+                settings.text_scale = (settings.text_scale + 0.25 * direction)
+                    .clamp(Settings::MIN_TEXT_SCALE, Settings::MAX_TEXT_SCALE)
+            }
+            7 => settings.rumble = !settings.rumble,
+            8 => settings.screen_shake = !settings.screen_shake,
+            // wraps around so selecting alone goes through every count
+            9 => {
+                let count = Settings::MAX_PLAYERS as i32;
+                settings.players = ((settings.players as i32 - 1 + direction as i32).rem_euclid(count) + 1) as u32
+            }
+            10 => {
+                let count = Difficulty::ALL.len() as i32;
+                let index = Difficulty::ALL.iter().position(|d| *d == settings.difficulty).unwrap() as i32;
+                settings.difficulty = Difficulty::ALL[(index + direction as i32).rem_euclid(count) as usize];
+            }
+            11 => {
+                self.controls_menu.reset();
+                self.game_state = GameState::Controls;
+            }
+            _ => self.close_settings(),
+        }
+    }
+
+    fn close_settings(&mut self) {
+        self.settings.save();
+        self.game_state = self.settings_return;
+    }
+
+    fn draw_settings_menu(&self) {
+        set_default_camera();
+        if self.settings_return == GameState::Paused {
+            draw_rectangle(0.0, 0.0, screen_width(), screen_height(), Color::new(0.0, 0.0, 0.0, 0.6));
+        } else {
+            clear_background(BLACK);
+        }
+        self.settings_menu
+            .draw("Settings", &self.settings_items(), self.settings.text_scale);
+    }
+
+    fn controls_items(&self) -> Vec<String> {
+        let mut items: Vec<String> = Action::ALL
+            .iter()
+            .map(|action| format!("{}: {}", action.name(), self.settings.bindings.describe(*action)))
+            .collect();
+
+        if let Some(action) = self.rebinding {
+            let index = Action::ALL.iter().position(|a| *a == action).unwrap();
+            items[index] = match self.unbindable_key {
+                Some(key) => format!("{}: {} can't be used, press another key...", action.name(), key_name(key)),
+                None => format!("{}: press a key...", action.name()),
+            };
+        }
+
+        items.push("Reset to defaults".to_string());
+        items.push("Back".to_string());
+        items
+    }
+
+    fn update_controls_menu(&mut self) {
+        if let Some(action) = self.rebinding {
+            // read the raw keyboard here, the bindings are what is being changed
+            let tapped = touches().iter().any(|touch| touch.phase == TouchPhase::Started);
+            if is_key_pressed(KeyCode::Escape) || tapped {
+                self.rebinding = None;
+                self.unbindable_key = None;
+            } else if let Some(key) = get_last_key_pressed() {
+                if self.settings.bindings.bind(action, key) {
+                    self.rebinding = None;
+                    self.unbindable_key = None;
+                } else {
+                    self.unbindable_key = Some(key);
+                }
+            }
+            return;
+        }
+
+        if self.actions.is_pressed(Action::Pause) {
+            self.game_state = GameState::Settings;
+            return;
+        }
+
+        let len = self.controls_items().len();
+        if let MenuAction::Select(index) = self.controls_menu.update(len, &self.actions) {
+            if index < Action::ALL.len() {
+                self.rebinding = Some(Action::ALL[index]);
+            } else if index == Action::ALL.len() {
+                self.settings.bindings = Bindings::default();
+            } else {
+                self.game_state = GameState::Settings;
+            }
+        }
+    }
+
+    fn draw_controls_menu(&self) {
+        set_default_camera();
+        if self.settings_return == GameState::Paused {
+            draw_rectangle(0.0, 0.0, screen_width(), screen_height(), Color::new(0.0, 0.0, 0.0, 0.6));
+        } else {
+            clear_background(BLACK);
+        }
+        self.controls_menu
+            .draw("Controls", &self.controls_items(), self.settings.text_scale);
+    }
+
+    /// Gives what can be picked on the title screen, there is no campaign on a server.
+    fn start_choices(&self) -> Vec<(String, StartChoice)> {
+        let mut choices = vec![];
+        match self.network {
+            Some(_) => choices.push(("Join the run".to_string(), StartChoice::Endless)),
+            None => {
+                choices.push(("Endless storm".to_string(), StartChoice::Endless));
+                choices.push(("Tutorial".to_string(), StartChoice::Tutorial));
+                choices.push(("Campaign".to_string(), StartChoice::Campaign));
+                choices.push(("Daily challenge".to_string(), StartChoice::Daily));
+                choices.push(("Sandbox".to_string(), StartChoice::Sandbox));
+            }
+        }
+        choices.push(("Settings".to_string(), StartChoice::Settings));
+        choices
+    }
+
+    fn update_start_menu(&mut self) {
+        if self.actions.is_pressed(Action::Pause) {
+            self.open_settings();
+            return;
+        }
+
+        let choices = self.start_choices();
+        if let MenuAction::Select(index) = self.start_menu.update(choices.len(), &self.actions) {
+            match choices[index].1 {
+                StartChoice::Endless => {
+                    let changed = self.players.len() != self.player_count() || *self.game.rules() != self.endless_rules();
+                    if self.leave_modes() || changed {
+                        self.restart();
+                    }
+                    self.game_state = GameState::Running;
+                    self.score = 0.0;
+                    self.time_survived = 0.0;
+                    get_frame_time();
+                }
+                StartChoice::Campaign => {
+                    self.level_select.reset();
+                    self.game_state = GameState::LevelSelect;
+                }
+                StartChoice::Daily => self.open_daily(),
+                StartChoice::Tutorial => {
+                    self.leave_modes();
+                    self.tutorial = Some(Tutorial::new());
+                    self.restart();
+                    self.game_state = GameState::Running;
+                    get_frame_time();
+                }
+                StartChoice::Sandbox => {
+                    self.leave_modes();
+                    self.sandbox = Some(Sandbox::new());
+                    self.restart();
+                    self.game_state = GameState::Running;
+                    get_frame_time();
+                }
+                StartChoice::Settings => self.open_settings(),
+            }
+        }
+    }
+
+    fn level_select_items(&self) -> Vec<String> {
+        let mut items: Vec<String> = self
+            .campaign
+            .levels()
+            .iter()
+            .enumerate()
+            .map(|(i, level)| {
+                if self.campaign.unlocked(i) {
+                    format!("{}. {}  {}", i + 1, level.name, stars_text(self.campaign.stars(i)))
+                } else {
+                    format!("{}. Locked", i + 1)
+                }
+            })
+            .collect();
+        items.push("Back".to_string());
+        items
+    }
+
+    fn update_level_select(&mut self) {
+        if self.actions.is_pressed(Action::Pause) {
+            self.game_state = GameState::Start;
+            return;
+        }
+
+        let len = self.campaign.levels().len() + 1;
+        if let MenuAction::Select(index) = self.level_select.update(len, &self.actions) {
+            if index == len - 1 {
+                self.game_state = GameState::Start;
+            } else if self.campaign.unlocked(index) {
+                self.start_level(index);
+            }
+        }
+    }
+
+    /// Draws the levels, with what has to be done in the one picked.
+    fn draw_level_select(&self) {
+        set_default_camera();
+        clear_background(BLACK);
+        let text_scale = self.settings.text_scale;
+        let items = self.level_select_items();
+        self.level_select.draw("Campaign", &items, text_scale);
+
+        let index = self.level_select.selected();
+        let Some(level) = self.campaign.levels().get(index) else {
+            return;
+        };
+        let size = 20.0 * text_scale;
+        let line = 30.0 * text_scale;
+        let x = screen_width() / 4.0;
+        let mut y = screen_height() / 2.0 + line * (items.len() as f32 / 2.0 + 1.5);
+        if !self.campaign.unlocked(index) {
+            draw_text("Clear the level before this one to unlock it", x, y, size, GRAY);
+            return;
+        }
+        draw_text(&level.description, x, y, size, LIGHTGRAY);
+        for objective in &level.objectives {
+            y += line;
+            draw_text(&format!("- {}", objective.describe()), x, y, size, WHITE);
+        }
+    }
+
+    /// Gives what can be picked once a campaign level is over.
+    fn level_end_choices(&self) -> Vec<(String, LevelEndChoice)> {
+        let mut choices = vec![];
+        if let Some(run) = &self.campaign_run {
+            let next = run.level() + 1;
+            if run.survived() && next < self.campaign.levels().len() {
+                let name = &self.campaign.levels()[next].name;
+                choices.push((format!("Next: {}", name), LevelEndChoice::Next(next)));
+            }
+        }
+        choices.push(("Retry".to_string(), LevelEndChoice::Retry));
+        if self.last_run.is_some() {
+            choices.push(("Watch the replay".to_string(), LevelEndChoice::WatchReplay));
+        }
+        choices.push(("Level select".to_string(), LevelEndChoice::LevelSelect));
+        choices
+    }
+
+    fn update_level_end(&mut self) {
+        let choices = self.level_end_choices();
+        if let MenuAction::Select(index) = self.level_end_menu.update(choices.len(), &self.actions) {
+            match choices[index].1 {
+                LevelEndChoice::Next(level) => self.start_level(level),
+                LevelEndChoice::Retry => {
+                    self.restart();
+                    self.game_state = GameState::Running;
+                    get_frame_time();
+                }
+                LevelEndChoice::WatchReplay => {
+                    if let Some(recording) = self.last_run.clone() {
+                        self.watch(recording);
+                    }
+                }
+                LevelEndChoice::LevelSelect => self.game_state = GameState::LevelSelect,
+            }
+        }
+    }
+
+    /// Shows how the campaign level went, every objective checked off and the stars earned.
+    fn draw_level_end(&self) {
+        let Some(run) = &self.campaign_run else {
+            return;
+        };
+        set_default_camera();
+        clear_background(BLACK);
+        let level = &self.campaign.levels()[run.level()];
+        let text_scale = self.settings.text_scale;
+        let size = 20.0 * text_scale;
+        let line = 30.0 * text_scale;
+        let x = if screen_width() < screen_height() { 10.0 } else { screen_width() / 4.0 };
+        let mut y = screen_height() / 4.0;
+
+        let title = if run.survived() {
+            format!("{} cleared!", level.name)
+        } else {
+            format!("{} lost, the generator ran dry", level.name)
+        };
+        draw_text(&title, x, y, size * 1.5, WHITE);
+        y += line;
+        for i in 0..MAX_STARS {
+            let colour = if i < run.stars() { GOLD } else { DARKGRAY };
+            draw_star(vec2(x + 20.0 + i as f32 * 50.0, y + 20.0), 20.0, colour);
+        }
+        y += line * 2.5;
+
+        let result = run.result().unwrap_or_default();
+        for (objective, met) in level.objectives.iter().zip(result) {
+            let (mark, colour) = if *met { ("[x]", GREEN) } else { ("[ ]", RED) };
+            draw_text(&format!("{} {}", mark, objective.describe()), x, y, size, colour);
+            y += line;
+        }
+        if !run.survived() {
+            draw_text("Survive the storm to earn stars", x, y, size, LIGHTGRAY);
+            y += line;
+        }
+        draw_text(&format!("Score: {}", self.score + self.get_timer_score()), x, y, size, WHITE);
+        y += line * 1.5;
+
+        let items: Vec<String> = self.level_end_choices().into_iter().map(|(item, _)| item).collect();
+        self.level_end_menu.draw_items(&items, x, y, text_scale);
+    }
+
+    /// Shows today's challenge, working it out again in case the day changed.
+    fn open_daily(&mut self) {
+        let maps = daily_maps(&self.level, &self.campaign);
+        self.daily_challenge = DailyChallenge::new(today(), &maps, &self.rules);
+        self.daily_menu.reset();
+        self.game_state = GameState::Daily;
+    }
+
+    fn daily_choices(&self) -> Vec<(String, DailyChoice)> {
+        let attempts = self.daily_challenge.attempts();
+        let start = if attempts < MAX_ATTEMPTS {
+            format!("Start attempt {} of {}", attempts + 1, MAX_ATTEMPTS)
+        } else {
+            "No attempts left, come back tomorrow".to_string()
+        };
+        let mut choices = vec![(start, DailyChoice::Start)];
+        if self.daily_challenge.best().is_some() {
+            choices.push(("Watch the best run".to_string(), DailyChoice::WatchBest));
+        }
+        choices.push(("Back".to_string(), DailyChoice::Back));
+        choices
+    }
+
+    fn update_daily(&mut self) {
+        if self.actions.is_pressed(Action::Pause) {
+            self.game_state = GameState::Start;
+            return;
+        }
+
+        let choices = self.daily_choices();
+        if let MenuAction::Select(index) = self.daily_menu.update(choices.len(), &self.actions) {
+            match choices[index].1 {
+                DailyChoice::Start => {
+                    if let Some(attempt) = self.daily_challenge.start_attempt() {
+                        self.leave_modes();
+                        self.daily_attempt = Some(attempt);
+                        self.restart();
+                        self.game_state = GameState::Running;
+                        get_frame_time();
+                    }
+                }
+                DailyChoice::WatchBest => {
+                    let Some(best) = self.daily_challenge.best() else {
+                        return;
+                    };
+                    match Recording::load_saved(&self.daily_challenge.replay_key(best.attempt)) {
+                        Ok(recording) => self.watch(recording),
+                        Err(e) => eprintln!("{}", e),
+                    }
+                }
+                DailyChoice::Back => self.game_state = GameState::Start,
+            }
+        }
+    }
+
+    /// Draws the day's storm, how it has gone so far and what can be done.
+    fn draw_daily(&self) {
+        set_default_camera();
+        clear_background(BLACK);
+        let daily = &self.daily_challenge;
+        let text_scale = self.settings.text_scale;
+        let size = 20.0 * text_scale;
+        let line = 30.0 * text_scale;
+        let x = if screen_width() < screen_height() { 10.0 } else { screen_width() / 4.0 };
+        let mut y = screen_height() / 4.0;
+
+        draw_text(&format!("Daily challenge {}", daily.date), x, y, size * 1.5, WHITE);
+        y += line * 1.5;
+        draw_text(&format!("Map: {}", daily.map_name), x, y, size, WHITE);
+        for modifier in &daily.modifiers {
+            y += line;
+            draw_text(&format!("{}: {}", modifier.name(), modifier.description()), x, y, size, ORANGE);
+        }
+        y += line * 1.5;
+        for result in daily.results() {
+            let text = format!(
+                "Attempt {}: {} points, survived {}",
+                result.attempt,
+                result.score,
+                clock(result.time_survived)
+            );
+            draw_text(&text, x, y, size, LIGHTGRAY);
+            y += line;
+        }
+        y += line * 0.5;
+
+        let items: Vec<String> = self.daily_choices().into_iter().map(|(item, _)| item).collect();
+        self.daily_menu.draw_items(&items, x, y, text_scale);
+    }
+
+    /// Shows how the attempt at the daily challenge went.
+    fn draw_daily_end(&self) {
+        set_default_camera();
+        clear_background(BLACK);
+        let text_scale = self.settings.text_scale;
+        let size = 20.0 * text_scale;
+        let line = 30.0 * text_scale;
+        let x = if screen_width() < screen_height() { 10.0 } else { screen_width() / 4.0 };
+        let y = screen_height() / 2.0;
+
+        let daily = &self.daily_challenge;
+        let attempt = self.daily_attempt.unwrap_or_default();
+        let title = format!("Daily challenge {}, attempt {} of {}", daily.date, attempt, MAX_ATTEMPTS);
+        draw_text(&title, x, y - line * 2.0, size, WHITE);
+        let score = self.score + self.get_timer_score();
+        let result = format!("You survived {} and scored {}", clock(self.time_survived), score);
+        draw_text(&result, x, y - line, size, WHITE);
+        if let Some(best) = daily.best() {
+            let text = format!("Best today: {} points on attempt {}", best.score, best.attempt);
+            draw_text(&text, x, y, size, YELLOW);
+        }
+        let back = format!(
+            "Press <{}> to go back to the challenge, <{}> to watch the replay",
+            self.key_hint(Action::Confirm),
+            self.key_hint(Action::Pause)
+        );
+        draw_text(&back, x, y + line * 2.0, size, WHITE);
+    }
+
+    fn update_daily_end(&mut self) {
+        if self.actions.is_pressed(Action::Confirm) {
+            self.open_daily();
+        } else if self.actions.is_pressed(Action::Pause) {
+            if let Some(recording) = self.last_run.clone() {
+                self.watch(recording);
+            }
+        }
+    }
+
+    /// Gives the first key bound to an action, for telling the player what to press.
+    fn key_hint(&self, action: Action) -> String {
+        match self.settings.bindings.keys(action).first() {
+            Some(key) => key_name(*key),
+            None => String::new(),
+        }
+    }
+}
+
+/// Moves a player and sends out the crew, the same for people and the bot.
+fn drive_player(game: &mut Game, index: usize, actions: &ActionState) {
+    move_player(game, index, actions);
+
+    let player = rect_center(game.players()[index].hit_box());
+    let target = match actions.dispatch_target() {
+        Some(target) => Some(target),
+        None if actions.is_pressed(Action::Dispatch) => game.closest_unassigned_box(player),
+        None => None,
+    };
+    if let Some(ebox) = target {
+        game.dispatch(ebox);
+    }
+}
+
+/// Pushes a player the way they want to go, faster when sprinting.
+fn move_player(game: &mut Game, index: usize, actions: &ActionState) {
+    let mut speed = 2.0;
+    if actions.is_down(Action::Sprint) {
+        speed *= 1.5
+    }
+    game.player_mut(index).add_velocity(actions.movement() * speed);
+}
+
+/// Gives where the replay timeline is on the screen.
+fn replay_timeline() -> Rect {
+    Rect::new(20.0, screen_height() - 30.0, screen_width() - 40.0, 10.0)
 }
 
-#[macroquad::main("Power Crisis")]
-async fn main() {
-    let mut app = App::new().await;
+/// Gives stars earned out of the most a level has, as text for the menus.
+fn stars_text(stars: u32) -> String {
+    format!("{}{}", "*".repeat(stars as usize), "-".repeat((MAX_STARS - stars) as usize))
+}
+
+/// Draws a five pointed star `radius` across from its `center` to a point, in screen space.
+fn draw_star(center: Vec2, radius: f32, colour: Color) {
+    let point = |i: usize| {
+        let angle = std::f32::consts::PI * (i as f32 / 5.0 - 0.5);
+        let distance = if i.is_multiple_of(2) { radius } else { radius * 0.4 };
+        center + vec2(angle.cos(), angle.sin()) * distance
+    };
+    for i in 0..10 {
+        draw_triangle(center, point(i), point(i + 1), colour);
+    }
+}
+
+/// Gives `seconds` as minutes and seconds.
+fn clock(seconds: f32) -> String {
+    let seconds = seconds.max(0.0) as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Gives the points for surviving `time_survived` seconds.
+fn timer_score(time_survived: f32) -> f32 {
+    time_survived.floor() * 10.0
+}
+
+/// Gives a seed for a run nobody asked to replay.
+fn new_seed() -> u64 {
+    (miniquad::date::now() * 1000.0) as u64
+}
+
+fn rect_center(rect: &Rect) -> Vec2 {
+    vec2(rect.x + rect.w / 2.0, rect.y + rect.h / 2.0)
+}
+
+fn set_fullscreen(fullscreen: bool) {
+    // macroquad does not expose this yet so it goes through miniquad
+    unsafe {
+        get_internal_gl().quad_context.set_fullscreen(fullscreen);
+    }
+}
+
+fn window_conf() -> Conf {
+    Conf {
+        window_title: "Power Crisis".to_string(),
+        fullscreen: Settings::load().fullscreen,
+        ..Conf::default()
+    }
+}
+
+fn main() {
+    // soak tests, checking replays and servers have nothing to show, so they don't open a window
+    if let Some(options) = SoakOptions::from_args() {
+        run_soak(&options);
+        return;
+    }
+    if let Some(date) = verify_arg() {
+        // the same map, rules and campaign the game would give the day from
+        let (level, rules) = App::local_level();
+        let campaign = App::local_campaign(&level, &rules);
+        run_verify(date.as_deref(), &level, &rules, &campaign);
+        return;
+    }
+    if let Some(options) = ServerOptions::from_args() {
+        run_server(&options);
+        return;
+    }
+    // join before opening the window so a wrong address fails straight away
+    let network = connect_address().map(|address| match NetClient::connect(&address) {
+        Ok(network) => network,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    });
+    let replay = replay_arg().map(|path| match Recording::load(path.as_deref()) {
+        Ok(recording) => recording,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    });
+    macroquad::Window::from_config(window_conf(), run(network, replay));
+}
+
+async fn run(network: Option<(NetClient, Level, Rules)>, replay: Option<Recording>) {
+    let mut app = match App::new(network).await {
+        Ok(app) => app,
+        Err(errors) => {
+            show_asset_errors(&errors).await;
+            return;
+        }
+    };
+    if let Some(recording) = replay {
+        app.watch(recording);
+    }
     
     loop {
-        let text_x = screen_width()/4.0;
+        app.hot_reload().await;
+        app.poll_network();
+        app.update_music(get_frame_time());
+
+        // portrait screens are too narrow to indent the text
+        let text_x = if screen_width() < screen_height() { 10.0 } else { screen_width()/4.0 };
         let text_y = screen_height()/2.0;
+        let size = 20.0 * app.settings.text_scale;
+        let line = 30.0 * app.settings.text_scale;
+        app.actions = ActionState::from_keyboard(&app.settings.bindings);
+        app.gamepads.update(&mut app.actions, get_frame_time());
+        let touch_screen = match app.game_state {
+            GameState::Running => TouchScreen::Game,
+            GameState::Replay => TouchScreen::Replay,
+            _ => TouchScreen::Menu,
+        };
+        app.touch_controls.update(&mut app.actions, touch_screen);
         match &app.game_state {
             GameState::Start => {
                 set_default_camera();
-                draw_text("A severe thunderstorm is threatening a city!", text_x, text_y - 60.0, 20.0, WHITE);
-                draw_text("You are an electrician tasked with keeping the city running!", text_x, text_y - 30.0, 20.0, WHITE);
-                draw_text("Use <WASD> to move around", text_x, text_y, 20.0, WHITE);
-                draw_text("Get close to the electrical boxes to fix them,", text_x, text_y + 30.0, 20.0, WHITE);
-                draw_text("Get close to your van to retock your repair kits,", text_x, text_y + 60.0, 20.0, WHITE);
-                draw_text("Don't let the backup generator run out of fuel!", text_x, text_y + 90.0, 20.0, WHITE);
-                draw_text("Press <ENTER> to Start", text_x, text_y + 120.0, 20.0, WHITE);
-                if is_key_pressed(KeyCode::Enter) {
-                    app.game_state = GameState::Running;
-                    app.score = 0.0;
-                    app.time_survived = 0.0;
-                    get_frame_time();
-                }
+                draw_text("A severe thunderstorm is threatening a city!", text_x, text_y - line * 2.0, size, WHITE);
+                draw_text("You are an electrician tasked with keeping the city running!", text_x, text_y - line, size, WHITE);
+                let movement = format!(
+                    "Use <{} {} {} {}> to move around and <{}> to run",
+                    app.key_hint(Action::MoveUp),
+                    app.key_hint(Action::MoveLeft),
+                    app.key_hint(Action::MoveDown),
+                    app.key_hint(Action::MoveRight),
+                    app.key_hint(Action::Sprint),
+                );
+                draw_text(&movement, text_x, text_y, size, WHITE);
+                let map = format!(
+                    "Press <{}> to toggle the city map, <{}> to pause",
+                    app.key_hint(Action::ToggleMinimap),
+                    app.key_hint(Action::Pause),
+                );
+                draw_text(&map, text_x, text_y + line, size, WHITE);
+                draw_text("New to the job? The tutorial shows you the ropes.", text_x, text_y + line * 2.0, size, WHITE);
+                let choices: Vec<String> = app.start_choices().into_iter().map(|(item, _)| item).collect();
+                app.start_menu.draw_items(&choices, text_x, text_y + line * 3.5, app.settings.text_scale);
+                let choose = if app.touch_controls.active() {
+                    "Tap a choice to start".to_string()
+                } else {
+                    format!("Press <{}> to choose", app.key_hint(Action::Confirm))
+                };
+                draw_text(&choose, text_x, text_y + line * (3.5 + choices.len() as f32), size, GRAY);
+                app.update_start_menu();
             }
             GameState::Running => {
-                app.update(get_frame_time());
+                let stalled = cfg!(not(target_arch = "wasm32")) && get_frame_time() > MAX_FRAME_TIME;
+                if app.actions.is_pressed(Action::Pause) || !browser::has_focus() || stalled {
+                    app.pause();
+                } else {
+                    app.update(get_frame_time().min(MAX_FRAME_TIME));
+                }
+                app.draw();
+            }
+            GameState::Paused => {
                 app.draw();
+                app.update_pause_menu();
+                app.draw_pause_menu();
+            }
+            GameState::Settings => {
+                if app.settings_return == GameState::Paused {
+                    app.draw();
+                }
+                app.update_settings_menu();
+                app.draw_settings_menu();
+            }
+            GameState::Controls => {
+                if app.settings_return == GameState::Paused {
+                    app.draw();
+                }
+                app.update_controls_menu();
+                app.draw_controls_menu();
+            }
+            GameState::End if app.daily_attempt.is_some() => {
+                app.draw_daily_end();
+                app.update_daily_end();
+            }
+            GameState::End if app.campaign_run.is_some() => {
+                app.draw_level_end();
+                app.update_level_end();
             }
             GameState::End => {
                 set_default_camera();
                 clear_background(BLACK);
-                draw_text(&format!("You survived for {:.2} Seconds!", app.time_survived), text_x, text_y-30.0, 20.0, WHITE);
-                draw_text(&format!("Your final score is {}!", app.score+app.get_timer_score()), text_x, text_y, 20.0, WHITE);
-                draw_text("Press <ENTER> to restart", text_x, text_y + 30.0, 20.0, WHITE);
+                draw_text(&format!("You survived for {:.2} Seconds!", app.time_survived), text_x, text_y - line, size, WHITE);
+                draw_text(&format!("Your final score is {}!", app.score+app.get_timer_score()), text_x, text_y, size, WHITE);
+                if app.network.is_some() {
+                    draw_text("Waiting for the server to start the next run...", text_x, text_y + line, size, WHITE);
+                } else {
+                    let mut restart = format!("Press <{}> to restart", app.key_hint(Action::Confirm));
+                    if app.last_run.is_some() {
+                        restart += &format!(", <{}> to watch the replay", app.key_hint(Action::Pause));
+                    }
+                    draw_text(&restart, text_x, text_y + line, size, WHITE);
+                    if app.touch_controls.active() {
+                        draw_text("Tap anywhere to restart", text_x, text_y + line * 2.0, size, WHITE);
+                    }
+                }
+                if let Some(error) = &app.connection_error {
+                    draw_text(error, text_x, text_y - line * 2.0, size, RED);
+                }
+                if app.players.len() > 1 && app.network.is_none() && app.connection_error.is_none() {
+                    for (i, view) in app.players.iter().enumerate() {
+                        let stats = &view.stats;
+                        let text = format!(
+                            "Player {}: {} repairs, {} restocks, walked {:.0}m",
+                            i + 1,
+                            stats.repairs,
+                            stats.restocks,
+                            stats.distance
+                        );
+                        let colour = PLAYER_COLOURS[i % PLAYER_COLOURS.len()];
+                        draw_text(&text, text_x, text_y + line * (3.0 + i as f32), size, colour);
+                    }
+                }
 
-                if is_key_pressed(KeyCode::Enter) {
-                    app.game = Game::load();
+                if app.network.is_none() && app.actions.is_pressed(Action::Confirm) {
+                    app.connection_error = None;
+                    app.restart();
                     app.game_state = GameState::Running;
-                    app.score = 0.0;
-                    app.time_survived = 0.0;
                     get_frame_time();
+                } else if app.actions.is_pressed(Action::Pause) {
+                    if let Some(recording) = app.last_run.clone() {
+                        app.watch(recording);
+                    }
                 }
-                
+            }
+            GameState::Replay => {
+                app.update_replay(get_frame_time().min(MAX_FRAME_TIME));
+                app.draw();
+                app.draw_replay_ui();
+            }
+            GameState::LevelSelect => {
+                app.update_level_select();
+                app.draw_level_select();
+            }
+            GameState::Daily => {
+                app.update_daily();
+                app.draw_daily();
             }
         }
 
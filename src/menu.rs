@@ -0,0 +1,147 @@
+use std::cell::Cell;
+
+use macroquad::prelude::*;
+
+use crate::input::{Action, ActionState};
+
+pub enum MenuAction {
+    None,
+    /// The item at the index was activated.
+    Select(usize),
+    /// The item at the index should be decreased (-1.0) or increased (1.0).
+    Adjust(usize, f32),
+}
+
+/// A vertical list of text items navigated with the keyboard, or tapped.
+pub struct Menu {
+    selected: usize,
+    /// Whether the items have values changed with left and right, like the settings.
+    adjustable: bool,
+    /// Where the items were last drawn and how far apart they are, for finding which was tapped.
+    drawn: Cell<Option<(Rect, f32)>>,
+}
+impl Menu {
+    pub fn new() -> Self {
+        Self {
+            selected: 0,
+            adjustable: false,
+            drawn: Cell::new(None),
+        }
+    }
+
+    /// A menu of values changed with left and right.
+    ///
+    /// Tapping an item only selects it, tapping it again lowers it on its left
+    /// half and raises or picks it on its right half.
+    pub fn adjustable() -> Self {
+        Self {
+            adjustable: true,
+            ..Menu::new()
+        }
+    }
+
+    /// Moves the selection and reports what the player did with a menu of `len` items.
+    pub fn update(&mut self, len: usize, actions: &ActionState) -> MenuAction {
+        if len == 0 {
+            return MenuAction::None;
+        }
+        if self.selected >= len {
+            self.selected = len - 1;
+        }
+
+        // a tap counts as confirming, which would pick what was selected before
+        let tap = touches().into_iter().find(|touch| touch.phase == TouchPhase::Started);
+        if let Some(tap) = tap {
+            return self.tap(tap.position, len);
+        }
+
+        if actions.is_pressed(Action::MoveUp) {
+            self.selected = (self.selected + len - 1) % len;
+        }
+        if actions.is_pressed(Action::MoveDown) {
+            self.selected = (self.selected + 1) % len;
+        }
+
+        if actions.is_pressed(Action::MoveLeft) {
+            return MenuAction::Adjust(self.selected, -1.0);
+        }
+        if actions.is_pressed(Action::MoveRight) {
+            return MenuAction::Adjust(self.selected, 1.0);
+        }
+        if actions.is_pressed(Action::Confirm) {
+            return MenuAction::Select(self.selected);
+        }
+
+        MenuAction::None
+    }
+
+    fn tap(&mut self, position: Vec2, len: usize) -> MenuAction {
+        let Some((area, spacing)) = self.drawn.get() else {
+            return MenuAction::None;
+        };
+        if !area.contains(position) {
+            return MenuAction::None;
+        }
+        let row = ((position.y - area.y) / spacing) as usize;
+        if row >= len {
+            return MenuAction::None;
+        }
+        let index = row;
+        if !self.adjustable {
+            self.selected = index;
+            return MenuAction::Select(index);
+        }
+        if index != self.selected {
+            self.selected = index;
+            return MenuAction::None;
+        }
+        if position.x < area.x + area.w / 2.0 {
+            MenuAction::Adjust(index, -1.0)
+        } else {
+            MenuAction::Select(index)
+        }
+    }
+
+    pub fn draw(&self, title: &str, items: &[String], text_scale: f32) {
+        let font_size = 20.0 * text_scale;
+        let spacing = 30.0 * text_scale;
+        let x = screen_width() / 4.0;
+        let y = screen_height() / 2.0 - spacing * (items.len() as f32 / 2.0 + 1.0);
+
+        draw_text(title, x, y, font_size * 1.5, WHITE);
+        self.draw_items(items, x, y + spacing * 1.5, text_scale);
+    }
+
+    /// Draws only the items, the first at `x`, `y`, for menus sharing the screen with other text.
+    pub fn draw_items(&self, items: &[String], x: f32, y: f32, text_scale: f32) {
+        let font_size = 20.0 * text_scale;
+        let spacing = 30.0 * text_scale;
+        let width = items
+            .iter()
+            .map(|item| measure_text(&format!("< {} >", item), None, font_size as u16, 1.0).width)
+            .fold(0.0, f32::max);
+        // items are drawn from their baseline, each row reaches most of a spacing up from it
+        let top = y - spacing * 0.75;
+        self.drawn.set(Some((Rect::new(x, top, width, spacing * items.len() as f32), spacing)));
+        for (i, item) in items.iter().enumerate() {
+            let item_y = y + spacing * i as f32;
+            if i == self.selected && self.adjustable {
+                draw_text(&format!("< {} >", item), x, item_y, font_size, YELLOW);
+            } else if i == self.selected {
+                draw_text(&format!("> {}", item), x, item_y, font_size, YELLOW);
+            } else {
+                draw_text(&format!("  {}", item), x, item_y, font_size, WHITE);
+            }
+        }
+    }
+
+    /// Get the index of the selected item.
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Moves the selection back to the first item.
+    pub fn reset(&mut self) {
+        self.selected = 0;
+    }
+}
@@ -0,0 +1,280 @@
+//! Music that follows the game in layers.
+//!
+//! The game ships without music files, so the stems are played by a tiny synth
+//! when the game starts. They are kept simple and quiet on purpose.
+
+use std::f32::consts::TAU;
+
+use macroquad::audio::{
+    load_sound_from_bytes, play_sound, set_sound_volume, stop_sound, PlaySoundParams, Sound,
+};
+
+use crate::audio::wav_bytes;
+
+const SAMPLE_RATE: u32 = 22050;
+
+/// Beats per minute of the in game stems, they all share it so they stay in step.
+const GAME_TEMPO: f32 = 96.0;
+
+const MENU_TEMPO: f32 = 72.0;
+
+/// Volume change per second when a layer fades in or out.
+const FADE_SPEED: f32 = 0.5;
+
+/// How loud the game music plays behind the pause menu.
+const PAUSED_VOLUME: f32 = 0.4;
+
+/// Share of a full generator below which the urgency layer comes in, louder as it runs out.
+const LOW_FUEL: f32 = 0.3;
+
+/// Root notes of the chords, in Hz, with a minor or major third.
+const PROGRESSION: [(f32, bool); 4] = [(110.0, true), (87.31, false), (130.81, false), (98.0, false)];
+
+/// What the music should be doing.
+pub enum Mood {
+    Menu,
+    Game {
+        /// Fraction of electrical boxes still working.
+        working: f32,
+        generator_running: bool,
+        /// Fraction of a full generator's fuel left.
+        fuel: f32,
+        paused: bool,
+    },
+    GameOver,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Layer {
+    Menu,
+    Calm,
+    Tension,
+    Urgency,
+    GameOver,
+}
+impl Layer {
+    const ALL: [Layer; 5] = [Layer::Menu, Layer::Calm, Layer::Tension, Layer::Urgency, Layer::GameOver];
+
+    /// Returns true for the layers that loop together during a run.
+    fn in_game(&self) -> bool {
+        matches!(self, Layer::Calm | Layer::Tension | Layer::Urgency)
+    }
+}
+
+struct Stem {
+    layer: Layer,
+    sound: Sound,
+    looped: bool,
+    volume: f32,
+    playing: bool,
+}
+
+/// Crossfades the music layers to fit what is happening in the game.
+pub struct MusicManager {
+    stems: Vec<Stem>,
+}
+impl MusicManager {
+    pub async fn new() -> Self {
+        let mut stems = Vec::with_capacity(Layer::ALL.len());
+        for layer in Layer::ALL {
+            let samples = match layer {
+                Layer::Menu => menu(),
+                Layer::Calm => calm(),
+                Layer::Tension => tension(),
+                Layer::Urgency => urgency(),
+                Layer::GameOver => game_over(),
+            };
+            // the bytes are made right here, so they can't fail to decode
+            let sound = load_sound_from_bytes(&wav_bytes(SAMPLE_RATE, 1, &samples))
+                .await
+                .unwrap();
+            stems.push(Stem {
+                layer,
+                sound,
+                looped: layer != Layer::GameOver,
+                volume: 0.0,
+                playing: false,
+            });
+        }
+        Self { stems }
+    }
+
+    /// Fades every layer towards how loud it should be for `mood`.
+    pub fn update(&mut self, mood: &Mood, music_volume: f32, delta: f32) {
+        for stem in &mut self.stems {
+            let target = MusicManager::target(stem.layer, mood);
+            // in game layers all start with the run, even silent, so they stay in step
+            let wanted = target > 0.0 || (stem.layer.in_game() && matches!(mood, Mood::Game { .. }));
+
+            if wanted && !stem.playing {
+                let sound_params = PlaySoundParams {
+                    looped: stem.looped,
+                    volume: 0.0,
+                };
+                play_sound(stem.sound, sound_params);
+                stem.playing = true;
+                stem.volume = 0.0;
+            }
+            if !stem.playing {
+                continue;
+            }
+
+            let step = FADE_SPEED * delta;
+            stem.volume = if stem.volume < target {
+                (stem.volume + step).min(target)
+            } else {
+                (stem.volume - step).max(target)
+            };
+
+            // the game over sting cuts in instead of fading
+            if stem.layer == Layer::GameOver && target > 0.0 {
+                stem.volume = target;
+            }
+
+            if stem.volume <= 0.0 && !wanted {
+                stop_sound(stem.sound);
+                stem.playing = false;
+            } else {
+                set_sound_volume(stem.sound, stem.volume * music_volume);
+            }
+        }
+    }
+
+    fn target(layer: Layer, mood: &Mood) -> f32 {
+        match (layer, mood) {
+            (Layer::Menu, Mood::Menu) => 1.0,
+            (Layer::GameOver, Mood::GameOver) => 1.0,
+            (
+                Layer::Calm | Layer::Tension | Layer::Urgency,
+                Mood::Game {
+                    working,
+                    generator_running,
+                    fuel,
+                    paused,
+                },
+            ) => {
+                let volume = match layer {
+                    // the calm bed thins out as the city goes dark
+                    Layer::Calm => 0.4 + 0.6 * working.clamp(0.0, 1.0),
+                    Layer::Tension if *generator_running => 1.0,
+                    Layer::Urgency => (1.0 - fuel / LOW_FUEL).clamp(0.0, 1.0),
+                    _ => 0.0,
+                };
+                if *paused {
+                    volume * PAUSED_VOLUME
+                } else {
+                    volume
+                }
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// Gives the frequency of the note `semitones` above `root`.
+fn note(root: f32, semitones: f32) -> f32 {
+    root * 2.0_f32.powf(semitones / 12.0)
+}
+
+/// Gives the three notes of the chord on `root`.
+fn triad(root: f32, minor: bool) -> [f32; 3] {
+    [root, note(root, if minor { 3.0 } else { 4.0 }), note(root, 7.0)]
+}
+
+/// A soft tone made of a sine and a little of its octave.
+fn tone(frequency: f32, time: f32) -> f32 {
+    (TAU * frequency * time).sin() * 0.8 + (TAU * frequency * 2.0 * time).sin() * 0.2
+}
+
+/// A buzzy tone made of the first few odd harmonics.
+fn buzz(frequency: f32, time: f32) -> f32 {
+    (1..=4)
+        .map(|h| {
+            let harmonic = (h * 2 - 1) as f32;
+            (TAU * frequency * harmonic * time).sin() / harmonic
+        })
+        .sum()
+}
+
+/// Calls `sample` for every sample of `beats` beats, passing the beat number and
+/// how far into that beat it is in seconds.
+fn render(beats: usize, tempo: f32, mut sample: impl FnMut(usize, f32, f32) -> f32) -> Vec<f32> {
+    let beat_length = 60.0 / tempo;
+    let length = (beats as f32 * beat_length * SAMPLE_RATE as f32) as usize;
+    (0..length)
+        .map(|i| {
+            let time = i as f32 / SAMPLE_RATE as f32;
+            let beat = (time / beat_length) as usize;
+            sample(beat, time - beat as f32 * beat_length, time)
+        })
+        .collect()
+}
+
+/// Gives the chord playing on `beat` when every chord lasts `beats_per_chord`.
+fn chord_at(beat: usize, beats_per_chord: usize) -> (f32, bool) {
+    PROGRESSION[(beat / beats_per_chord) % PROGRESSION.len()]
+}
+
+/// A slow pad holding each chord for two bars.
+fn calm() -> Vec<f32> {
+    let chord_length = 8.0 * 60.0 / GAME_TEMPO;
+    render(32, GAME_TEMPO, |beat, _, time| {
+        let (root, minor) = chord_at(beat, 8);
+        let into_chord = time % chord_length;
+        // swell in and out so the chord changes don't click
+        let swell = (into_chord / chord_length * std::f32::consts::PI).sin().powf(0.5);
+        let pad: f32 = triad(root * 2.0, minor).iter().map(|f| tone(*f, time)).sum();
+        pad * swell * 0.08
+    })
+}
+
+/// A pulsing bass on every eighth note.
+fn tension() -> Vec<f32> {
+    let eighth = 30.0 / GAME_TEMPO;
+    render(32, GAME_TEMPO, |beat, _, time| {
+        let (root, _) = chord_at(beat, 8);
+        let into_eighth = time % eighth;
+        let envelope = (-into_eighth * 8.0).exp();
+        buzz(root / 2.0, time) * envelope * 0.18
+    })
+}
+
+/// A quick arpeggio running over the chords on every sixteenth note.
+fn urgency() -> Vec<f32> {
+    let sixteenth = 15.0 / GAME_TEMPO;
+    render(32, GAME_TEMPO, |beat, _, time| {
+        let (root, minor) = chord_at(beat, 8);
+        let step = (time / sixteenth) as usize;
+        let notes = triad(root * 4.0, minor);
+        let frequency = notes[step % notes.len()];
+        let envelope = (-(time % sixteenth) * 20.0).exp();
+        buzz(frequency, time) * envelope * 0.06
+    })
+}
+
+/// A gentle arpeggio for the menus, one note a beat.
+fn menu() -> Vec<f32> {
+    render(16, MENU_TEMPO, |beat, into_beat, time| {
+        let (root, minor) = chord_at(beat, 4);
+        let notes = triad(root * 2.0, minor);
+        let frequency = notes[beat % notes.len()];
+        let envelope = (-into_beat * 2.5).exp();
+        let bass = tone(root / 2.0, time) * 0.3;
+        (tone(frequency, time) * envelope + bass) * 0.12
+    })
+}
+
+/// Three falling notes and a fading chord.
+fn game_over() -> Vec<f32> {
+    let falling = [note(440.0, 7.0), note(440.0, 3.0), 440.0];
+    render(8, 90.0, |beat, into_beat, time| {
+        let envelope = (-into_beat * 2.0).exp();
+        if beat < falling.len() {
+            tone(falling[beat] / 2.0, time) * envelope * 0.2
+        } else {
+            let fade = (1.0 - (beat - falling.len()) as f32 / 5.0 - into_beat / 3.3).max(0.0);
+            let chord: f32 = triad(110.0, true).iter().map(|f| tone(*f, time)).sum();
+            chord * fade * 0.1
+        }
+    })
+}
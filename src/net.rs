@@ -0,0 +1,578 @@
+//! Playing together over the network, `--server` runs the city without a window
+//! and `--connect <address>` joins it.
+//!
+//! Only the server runs the simulation. Clients send what they press every
+//! tick and get snapshots of the run back. Their own player moves straight
+//! away and is corrected when a snapshot says otherwise, everyone else is
+//! shown a moment in the past, smoothly between two snapshots. Messages are
+//! lines of json over TCP.
+//!
+//! Browsers can't open sockets, so this only works on desktop.
+
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, BufReader, ErrorKind, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::mpsc::{self, Receiver, SyncSender, TryRecvError, TrySendError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use macroquad::math::{vec2, Vec2};
+use serde_json::{json, Value};
+
+use crate::{
+    drive_player,
+    game::{Game, GameEvent, HitBox, Level, Rules},
+    hot_reload::{read_json, MAP_PATH, RULES_PATH},
+    input::{Action, ActionState, PlayerInput},
+    move_player, new_seed, timer_score, REPAIR_SCORE,
+};
+
+/// Port the server listens on when none is given.
+pub const DEFAULT_PORT: u16 = 7777;
+
+/// Seconds per simulation step, the server and the clients' guesses step the same.
+const TICK: f32 = 1.0 / 60.0;
+
+/// Ticks between snapshots sent to the clients.
+const SNAPSHOT_INTERVAL: u32 = 2;
+
+/// Seconds others are shown behind the latest snapshot, so there is nearly
+/// always a newer one to move towards.
+const INTERPOLATION_DELAY: f32 = 0.1;
+
+/// A client's inputs queued past this are dropped, so after a stall it catches
+/// up instead of lagging behind for the rest of the run.
+const MAX_QUEUED_INPUTS: usize = 10;
+
+/// Inputs a client keeps to replay, in case the server stops answering.
+const MAX_PENDING_INPUTS: usize = 120;
+
+/// Seconds the server waits after the generator runs dry before the next run.
+const RESTART_DELAY: f32 = 5.0;
+
+/// How long a client waits for the server to let it in.
+const JOIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Messages that can wait to be written to a connection. Past this the other
+/// end has stopped reading and is dropped, rather than holding up everyone else.
+const MAX_QUEUED_MESSAGES: usize = 60;
+
+/// How long writing one message may take before the connection is given up on.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What a client pressed during one tick, numbered so the server can say
+/// which it has played.
+#[derive(Clone, Copy)]
+struct Input {
+    seq: u32,
+    input: PlayerInput,
+}
+impl Input {
+    fn to_json(self) -> Value {
+        let input = self.input;
+        json!({
+            "type": "input",
+            "seq": self.seq,
+            "movement": [input.movement.x, input.movement.y],
+            "sprint": input.sprint,
+            "dispatch": input.dispatch,
+            "target": input.target,
+        })
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        if value["type"] != "input" {
+            return None;
+        }
+        let movement = vec2(value["movement"][0].as_f64()? as f32, value["movement"][1].as_f64()? as f32);
+        // anyone can send anything, only a stick's worth of movement is played
+        if !movement.is_finite() {
+            return None;
+        }
+        let input = PlayerInput {
+            movement: movement.clamp_length_max(1.0),
+            sprint: value["sprint"].as_bool().unwrap_or(false),
+            dispatch: value["dispatch"].as_bool().unwrap_or(false),
+            target: value["target"].as_u64().map(|target| target as usize),
+        };
+        Some(Self {
+            seq: value["seq"].as_u64()? as u32,
+            input,
+        })
+    }
+}
+
+/// Reads messages on their own thread so the game never waits on the
+/// network, the channel closes when the connection does.
+fn spawn_reader(stream: &TcpStream) -> std::io::Result<Receiver<Value>> {
+    let reader = BufReader::new(stream.try_clone()?);
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in reader.lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            // a garbled line is skipped rather than ending the game
+            if let Ok(message) = serde_json::from_str(&line) {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    Ok(receiver)
+}
+
+/// Writes messages on their own thread so a slow connection never holds up
+/// the game, the connection is closed when the sender is dropped.
+fn spawn_writer(stream: &TcpStream) -> io::Result<SyncSender<String>> {
+    let mut writer = stream.try_clone()?;
+    writer.set_nonblocking(false)?;
+    writer.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    let (sender, receiver) = mpsc::sync_channel::<String>(MAX_QUEUED_MESSAGES);
+    thread::spawn(move || {
+        for line in receiver {
+            if writer.write_all(line.as_bytes()).is_err() {
+                break;
+            }
+        }
+        // lets the reader on the other end, and the one here, know it's over
+        let _ = writer.shutdown(Shutdown::Both);
+    });
+    Ok(sender)
+}
+
+/// Queues a message for the writer, failing if the connection is gone or has
+/// stopped keeping up.
+fn send(writer: &SyncSender<String>, message: &Value) -> io::Result<()> {
+    let mut line = message.to_string();
+    line.push('\n');
+    writer.try_send(line).map_err(|e| match e {
+        TrySendError::Full(_) => io::Error::new(ErrorKind::TimedOut, "not keeping up"),
+        TrySendError::Disconnected(_) => io::Error::from(ErrorKind::BrokenPipe),
+    })
+}
+
+/// How the server should be run, read from the command line.
+pub struct ServerOptions {
+    port: u16,
+    players: usize,
+}
+impl ServerOptions {
+    /// Gives the options if the game was started with `--server [port]`,
+    /// taking `--players` too.
+    pub fn from_args() -> Option<Self> {
+        let args: Vec<String> = std::env::args().collect();
+        let server = args.iter().position(|arg| arg == "--server")?;
+        let players = args
+            .iter()
+            .position(|arg| arg == "--players")
+            .and_then(|i| args.get(i + 1))
+            .and_then(|n| n.parse().ok())
+            .unwrap_or(2);
+
+        Some(Self {
+            port: args.get(server + 1).and_then(|n| n.parse().ok()).unwrap_or(DEFAULT_PORT),
+            players: usize::max(players, 1),
+        })
+    }
+}
+
+/// Gives the address to join if the game was started with `--connect <address>`.
+pub fn connect_address() -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let connect = args.iter().position(|arg| arg == "--connect")?;
+    let address = args.get(connect + 1).cloned().unwrap_or_else(|| "127.0.0.1".to_string());
+    // the port can be left off when it's the usual one
+    if address.contains(':') {
+        Some(address)
+    } else {
+        Some(format!("{}:{}", address, DEFAULT_PORT))
+    }
+}
+
+/// Someone playing on the server.
+struct Connection {
+    writer: SyncSender<String>,
+    messages: Receiver<Value>,
+    inputs: VecDeque<Input>,
+    /// The last input the server played, so the client knows what to replay.
+    last_seq: u32,
+}
+
+/// Runs the city without a window, letting people join until every player is taken.
+///
+/// Players nobody has joined as just stand where they are. When the generator
+/// runs dry the server waits a moment and starts the next run for everyone.
+pub fn run_server(options: &ServerOptions) {
+    let (map, rules_json) = match (read_json(MAP_PATH), read_json(RULES_PATH)) {
+        (Ok(map), Ok(rules)) => (map, rules),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let level = match Level::from_json(&map) {
+        Ok(level) => level,
+        Err(e) => {
+            eprintln!("{}: {}", MAP_PATH, e);
+            std::process::exit(1);
+        }
+    };
+    let rules = Rules::from_json(&rules_json);
+
+    let listener = match TcpListener::bind(("0.0.0.0", options.port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("can't listen on port {}: {}", options.port, e);
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = listener.set_nonblocking(true) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    println!("waiting for {} players on port {}", options.players, options.port);
+
+    let mut connections: Vec<Option<Connection>> = (0..options.players).map(|_| None).collect();
+    let mut game = Game::new(&level, &rules, new_seed(), options.players);
+    let mut run = 0;
+    let mut time = 0.0;
+    let mut score = 0.0;
+    let mut restart_timer = None;
+    let mut events = Vec::new();
+    let mut tick = 0;
+    let mut next_tick = Instant::now();
+
+    loop {
+        while let Ok((stream, address)) = listener.accept() {
+            let Some(slot) = connections.iter().position(|connection| connection.is_none()) else {
+                if let Ok(writer) = spawn_writer(&stream) {
+                    let _ = send(&writer, &json!({ "type": "full" }));
+                }
+                continue;
+            };
+            let welcome = json!({
+                "type": "welcome",
+                "player": slot,
+                "players": options.players,
+                "run": run,
+                "map": map,
+                "rules": rules_json,
+            });
+            let joined = stream
+                .set_nonblocking(false)
+                .and_then(|_| stream.set_nodelay(true))
+                .and_then(|_| spawn_writer(&stream))
+                .and_then(|writer| send(&writer, &welcome).map(|_| writer))
+                .and_then(|writer| Ok((writer, spawn_reader(&stream)?)));
+            match joined {
+                Ok((writer, messages)) => {
+                    println!("{} joined as player {}", address, slot + 1);
+                    connections[slot] = Some(Connection {
+                        writer,
+                        messages,
+                        inputs: VecDeque::new(),
+                        last_seq: 0,
+                    });
+                }
+                Err(e) => eprintln!("{} couldn't join: {}", address, e),
+            }
+        }
+
+        for (slot, connection) in connections.iter_mut().enumerate() {
+            let Some(client) = connection else {
+                continue;
+            };
+            let mut left = false;
+            loop {
+                match client.messages.try_recv() {
+                    Ok(message) => client.inputs.extend(Input::from_json(&message)),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        left = true;
+                        break;
+                    }
+                }
+            }
+            if left {
+                println!("player {} left", slot + 1);
+                *connection = None;
+                continue;
+            }
+
+            while client.inputs.len() > MAX_QUEUED_INPUTS {
+                client.inputs.pop_front();
+            }
+            if let Some(input) = client.inputs.pop_front() {
+                client.last_seq = input.seq;
+                drive_player(&mut game, slot, &input.input.actions());
+            }
+        }
+
+        match restart_timer {
+            None => {
+                game.update(TICK);
+                time += TICK;
+                while let Some(event) = game.poll_event() {
+                    if matches!(event, GameEvent::FixEBox(..) | GameEvent::CrewFixEBox(..)) {
+                        score += REPAIR_SCORE;
+                    }
+                    events.push(game.event_json(&event));
+                }
+                if game.generator().feul() <= 0.0 {
+                    println!("run {} lasted {:.1}s, score {}", run + 1, time, score + timer_score(time));
+                    restart_timer = Some(RESTART_DELAY);
+                }
+            }
+            Some(left) if left > TICK => restart_timer = Some(left - TICK),
+            Some(_) => {
+                game = Game::new(&level, &rules, new_seed(), options.players);
+                run += 1;
+                time = 0.0;
+                score = 0.0;
+                restart_timer = None;
+            }
+        }
+
+        tick += 1;
+        if tick % SNAPSHOT_INTERVAL == 0 {
+            let state = game.snapshot();
+            for (slot, connection) in connections.iter_mut().enumerate() {
+                let Some(client) = connection else {
+                    continue;
+                };
+                let snapshot = json!({
+                    "type": "snapshot",
+                    "run": run,
+                    "ack": client.last_seq,
+                    "time": time,
+                    "score": score,
+                    "state": state,
+                    "events": events,
+                });
+                if let Err(e) = send(&client.writer, &snapshot) {
+                    println!("player {} left: {}", slot + 1, e);
+                    *connection = None;
+                }
+            }
+            events.clear();
+        }
+
+        next_tick += Duration::from_secs_f32(TICK);
+        let now = Instant::now();
+        if next_tick > now {
+            thread::sleep(next_tick - now);
+        } else if now - next_tick > Duration::from_secs(1) {
+            // the computer was busy for a while, carry on from now instead of rushing to catch up
+            next_tick = now;
+        }
+    }
+}
+
+/// Where everyone else was in one snapshot.
+struct Frame {
+    /// Seconds into the run on the server.
+    time: f32,
+    players: Vec<Vec2>,
+    crew: Vec<Vec2>,
+}
+
+/// A connection to a server, playing one of its players.
+pub struct NetClient {
+    writer: SyncSender<String>,
+    messages: Receiver<Value>,
+    player: usize,
+    players: usize,
+    run: u64,
+    next_seq: u32,
+    /// Inputs sent that the server hasn't played yet, replayed on top of every snapshot.
+    pending: VecDeque<Input>,
+    tick_timer: f32,
+    /// A dispatch pressed on a frame that had no tick to send it with.
+    dispatch: bool,
+    /// A box picked on the map on a frame that had no tick to send it with.
+    target: Option<usize>,
+    history: VecDeque<Frame>,
+    /// Local time the latest snapshot arrived.
+    received_at: f64,
+    time: f32,
+    score: f32,
+}
+impl NetClient {
+    /// Joins the server at `address`, giving the map and rules it plays with.
+    pub fn connect(address: &str) -> Result<(Self, Level, Rules), String> {
+        let error = |e: std::io::Error| format!("{}: {}", address, e);
+        let stream = TcpStream::connect(address).map_err(error)?;
+        stream.set_nodelay(true).map_err(error)?;
+        let messages = spawn_reader(&stream).map_err(error)?;
+        let writer = spawn_writer(&stream).map_err(error)?;
+
+        let welcome = messages
+            .recv_timeout(JOIN_TIMEOUT)
+            .map_err(|_| format!("{}: no answer from the server", address))?;
+        match welcome["type"].as_str() {
+            Some("welcome") => {}
+            Some("full") => return Err(format!("{}: the server is full", address)),
+            _ => return Err(format!("{}: not a Power Crisis server", address)),
+        }
+        let level = Level::from_json(&welcome["map"]).map_err(|e| format!("map from {}: {}", address, e))?;
+        let rules = Rules::from_json(&welcome["rules"]);
+        let players = welcome["players"].as_u64().unwrap_or(1) as usize;
+        let player = welcome["player"].as_u64().unwrap_or(0) as usize;
+        if player >= players {
+            return Err(format!("{}: sent player {} of {}", address, player + 1, players));
+        }
+
+        let client = Self {
+            writer,
+            messages,
+            player,
+            players,
+            run: welcome["run"].as_u64().unwrap_or(0),
+            next_seq: 0,
+            pending: VecDeque::new(),
+            tick_timer: 0.0,
+            dispatch: false,
+            target: None,
+            history: VecDeque::new(),
+            received_at: 0.0,
+            time: 0.0,
+            score: 0.0,
+        };
+        Ok((client, level, rules))
+    }
+
+    /// Get the index of the player this client plays.
+    pub fn player(&self) -> usize {
+        self.player
+    }
+
+    /// Get how many players the server's runs have.
+    pub fn players(&self) -> usize {
+        self.players
+    }
+
+    /// Get the seconds the current run has lasted on the server.
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Get the score the server has counted for the current run.
+    pub fn score(&self) -> f32 {
+        self.score
+    }
+
+    /// Sends what was pressed once per tick and moves the local player right
+    /// away instead of waiting to hear back from the server.
+    pub fn send_input(&mut self, game: &mut Game, actions: &ActionState, delta: f32) -> Result<(), String> {
+        self.dispatch |= actions.is_pressed(Action::Dispatch);
+        self.target = actions.dispatch_target().or(self.target);
+        self.tick_timer += delta;
+        while self.tick_timer >= TICK {
+            self.tick_timer -= TICK;
+            self.next_seq += 1;
+            let mut input = PlayerInput::from_actions(actions);
+            input.dispatch = std::mem::take(&mut self.dispatch);
+            input.target = self.target.take();
+            let input = Input {
+                seq: self.next_seq,
+                input,
+            };
+            send(&self.writer, &input.to_json()).map_err(|e| format!("lost the server: {}", e))?;
+
+            NetClient::predict(game, self.player, &input);
+            self.pending.push_back(input);
+            if self.pending.len() > MAX_PENDING_INPUTS {
+                self.pending.pop_front();
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the local player the way the server will once it gets `input`.
+    fn predict(game: &mut Game, player: usize, input: &Input) {
+        move_player(game, player, &input.input.actions());
+        game.step_player(player, TICK);
+    }
+
+    /// Applies every snapshot that arrived since the last frame, `now` is the
+    /// local time in seconds.
+    ///
+    /// Returns true when the server started a new run, `game` is then a new
+    /// one built from `level` and `rules`.
+    pub fn receive(&mut self, game: &mut Game, level: &Level, rules: &Rules, now: f64) -> Result<bool, String> {
+        let mut new_run = false;
+        let mut ack = None;
+        loop {
+            let message = match self.messages.try_recv() {
+                Ok(message) => message,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Err("lost the server".to_string()),
+            };
+            if message["type"] != "snapshot" {
+                continue;
+            }
+
+            let run = message["run"].as_u64().unwrap_or(self.run);
+            if run != self.run {
+                self.run = run;
+                *game = Game::new(level, rules, 0, self.players);
+                self.history.clear();
+                new_run = true;
+            }
+            let events = message["events"].as_array().map_or(&[][..], |events| events.as_slice());
+            game.apply_snapshot(&message["state"], events);
+            self.time = message["time"].as_f64().unwrap_or(0.0) as f32;
+            self.score = message["score"].as_f64().unwrap_or(0.0) as f32;
+            ack = message["ack"].as_u64();
+
+            self.history.push_back(Frame {
+                time: self.time,
+                players: game.players().iter().map(|player| player.hit_box().point()).collect(),
+                crew: game.crew().iter().map(|member| member.hit_box().point()).collect(),
+            });
+            self.received_at = now;
+        }
+
+        if let Some(ack) = ack {
+            // the snapshot has the local player where they were after `ack`, guess the rest again on top
+            self.pending.retain(|input| input.seq as u64 > ack);
+            for input in &self.pending {
+                NetClient::predict(game, self.player, input);
+            }
+        }
+        self.interpolate(game, now);
+        Ok(new_run)
+    }
+
+    /// Puts everyone but the local player where they were a moment ago, part
+    /// way between the two snapshots around that moment.
+    fn interpolate(&mut self, game: &mut Game, now: f64) {
+        let Some(latest) = self.history.back() else {
+            return;
+        };
+        let render_time = latest.time + (now - self.received_at) as f32 - INTERPOLATION_DELAY;
+        while self.history.len() > 2 && self.history[1].time <= render_time {
+            self.history.pop_front();
+        }
+
+        let from = &self.history[0];
+        let to = self.history.get(1).unwrap_or(from);
+        let t = if to.time > from.time {
+            ((render_time - from.time) / (to.time - from.time)).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        for (i, (a, b)) in from.players.iter().zip(&to.players).enumerate() {
+            if i != self.player {
+                game.player_mut(i).hit_box_mut().move_to(a.lerp(*b, t));
+            }
+        }
+        for (member, (a, b)) in game.crew_mut().iter_mut().zip(from.crew.iter().zip(&to.crew)) {
+            member.hit_box_mut().move_to(a.lerp(*b, t));
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use macroquad::prelude::*;
+use macroquad::rand::gen_range;
+
+#[derive(Clone, Copy)]
+pub enum ParticleShape {
+    Dot,
+    /// A line along the particle's velocity, for things that move too fast to see as a dot.
+    Streak,
+}
+
+/// Colour of a particle from birth (`start`) to death (`end`).
+#[derive(Clone, Copy)]
+pub struct ColourRamp {
+    pub start: Color,
+    pub end: Color,
+}
+impl ColourRamp {
+    fn at(&self, t: f32) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        Color::new(
+            self.start.r + (self.end.r - self.start.r) * t,
+            self.start.g + (self.end.g - self.start.g) * t,
+            self.start.b + (self.end.b - self.start.b) * t,
+            self.start.a + (self.end.a - self.start.a) * t,
+        )
+    }
+}
+
+/// Describes what kind of particles an emitter makes.
+#[derive(Clone, Copy)]
+pub struct EmitterConfig {
+    /// Direction particles are launched in, in radians.
+    pub direction: f32,
+    /// How far from `direction` a particle may be launched, in radians.
+    pub spread: f32,
+    pub min_speed: f32,
+    pub max_speed: f32,
+    pub min_lifetime: f32,
+    pub max_lifetime: f32,
+    pub size: f32,
+    /// Constant acceleration, like gravity.
+    pub acceleration: Vec2,
+    /// Fraction of the velocity kept after a second.
+    pub drag: f32,
+    pub colours: ColourRamp,
+    pub shape: ParticleShape,
+}
+
+struct Particle {
+    position: Vec2,
+    velocity: Vec2,
+    age: f32,
+    lifetime: f32,
+    size: f32,
+    acceleration: Vec2,
+    drag: f32,
+    colours: ColourRamp,
+    shape: ParticleShape,
+}
+
+/// A pool of particles that never allocates once it is full.
+///
+/// Live particles are kept at the front of the pool, dead ones are swapped to
+/// the back and reused by the next spawn.
+pub struct ParticleSystem {
+    particles: Vec<Particle>,
+    alive: usize,
+    capacity: usize,
+}
+impl ParticleSystem {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            particles: Vec::with_capacity(capacity),
+            alive: 0,
+            capacity,
+        }
+    }
+
+    /// Spawns one particle at `position`, dropping it if the pool is full.
+    pub fn emit(&mut self, config: &EmitterConfig, position: Vec2) {
+        let angle = config.direction + gen_range(-config.spread, config.spread);
+        let speed = gen_range(config.min_speed, config.max_speed);
+        let particle = Particle {
+            position,
+            velocity: vec2(angle.cos(), angle.sin()) * speed,
+            age: 0.0,
+            lifetime: gen_range(config.min_lifetime, config.max_lifetime),
+            size: config.size,
+            acceleration: config.acceleration,
+            drag: config.drag,
+            colours: config.colours,
+            shape: config.shape,
+        };
+
+        if self.alive < self.particles.len() {
+            self.particles[self.alive] = particle;
+        } else if self.particles.len() < self.capacity {
+            self.particles.push(particle);
+        } else {
+            return;
+        }
+        self.alive += 1;
+    }
+
+    /// Spawns `count` particles at once.
+    pub fn burst(&mut self, config: &EmitterConfig, position: Vec2, count: usize) {
+        for _ in 0..count {
+            self.emit(config, position);
+        }
+    }
+
+    pub fn update(&mut self, delta: f32) {
+        let mut i = 0;
+        while i < self.alive {
+            let particle = &mut self.particles[i];
+            particle.age += delta;
+            if particle.age >= particle.lifetime {
+                self.alive -= 1;
+                self.particles.swap(i, self.alive);
+                continue;
+            }
+
+            particle.velocity += particle.acceleration * delta;
+            particle.velocity *= particle.drag.powf(delta);
+            particle.position += particle.velocity * delta;
+            i += 1;
+        }
+    }
+
+    pub fn draw(&self) {
+        for particle in &self.particles[..self.alive] {
+            let colour = particle.colours.at(particle.age / particle.lifetime);
+            match particle.shape {
+                ParticleShape::Dot => {
+                    draw_circle(particle.position.x, particle.position.y, particle.size, colour);
+                }
+                ParticleShape::Streak => {
+                    let tail = particle.position - particle.velocity * 0.02;
+                    draw_line(
+                        particle.position.x,
+                        particle.position.y,
+                        tail.x,
+                        tail.y,
+                        particle.size,
+                        colour,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Removes every particle.
+    pub fn clear(&mut self) {
+        self.alive = 0;
+    }
+}
+
+/// Emits a steady stream of particles, `rate` per second.
+pub struct Emitter {
+    pub config: EmitterConfig,
+    pub rate: f32,
+    accumulator: f32,
+}
+impl Emitter {
+    pub fn new(config: EmitterConfig, rate: f32) -> Self {
+        Self {
+            config,
+            rate,
+            accumulator: 0.0,
+        }
+    }
+
+    /// Spawns this frame's share of particles somewhere inside `area`.
+    pub fn update(&mut self, particles: &mut ParticleSystem, area: Rect, delta: f32) {
+        self.accumulator += self.rate * delta;
+        while self.accumulator >= 1.0 {
+            self.accumulator -= 1.0;
+            let position = vec2(
+                gen_range(area.x, area.x + area.w),
+                gen_range(area.y, area.y + area.h),
+            );
+            particles.emit(&self.config, position);
+        }
+    }
+}
+
+/// Falling rain, seen from above it streaks down the screen.
+pub fn rain() -> EmitterConfig {
+    EmitterConfig {
+        direction: -1.45,
+        spread: 0.05,
+        min_speed: 20.0,
+        max_speed: 28.0,
+        min_lifetime: 0.2,
+        max_lifetime: 0.45,
+        size: 0.04,
+        acceleration: vec2(0.0, 0.0),
+        drag: 1.0,
+        colours: ColourRamp {
+            start: Color::new(0.6, 0.7, 1.0, 0.5),
+            end: Color::new(0.6, 0.7, 1.0, 0.1),
+        },
+        shape: ParticleShape::Streak,
+    }
+}
+
+/// Water kicked up by running through a puddle.
+pub fn splash() -> EmitterConfig {
+    EmitterConfig {
+        direction: std::f32::consts::FRAC_PI_2,
+        spread: std::f32::consts::PI,
+        min_speed: 1.0,
+        max_speed: 3.0,
+        min_lifetime: 0.15,
+        max_lifetime: 0.35,
+        size: 0.06,
+        acceleration: vec2(0.0, 0.0),
+        drag: 0.05,
+        colours: ColourRamp {
+            start: Color::new(0.7, 0.8, 1.0, 0.8),
+            end: Color::new(0.7, 0.8, 1.0, 0.0),
+        },
+        shape: ParticleShape::Dot,
+    }
+}
+
+/// Sparks flying out of an electrical box hit by lightning.
+pub fn sparks() -> EmitterConfig {
+    EmitterConfig {
+        direction: std::f32::consts::FRAC_PI_2,
+        spread: std::f32::consts::PI,
+        min_speed: 3.0,
+        max_speed: 9.0,
+        min_lifetime: 0.3,
+        max_lifetime: 0.8,
+        size: 0.05,
+        acceleration: vec2(0.0, -6.0),
+        drag: 0.2,
+        colours: ColourRamp {
+            start: Color::new(1.0, 1.0, 0.6, 1.0),
+            end: Color::new(1.0, 0.3, 0.0, 0.0),
+        },
+        shape: ParticleShape::Streak,
+    }
+}
+
+/// A gentle burst when a box is fixed.
+pub fn repair_burst() -> EmitterConfig {
+    EmitterConfig {
+        direction: std::f32::consts::FRAC_PI_2,
+        spread: std::f32::consts::PI,
+        min_speed: 1.5,
+        max_speed: 4.0,
+        min_lifetime: 0.4,
+        max_lifetime: 0.7,
+        size: 0.08,
+        acceleration: vec2(0.0, 0.0),
+        drag: 0.1,
+        colours: ColourRamp {
+            start: Color::new(0.6, 1.0, 0.6, 1.0),
+            end: Color::new(1.0, 1.0, 1.0, 0.0),
+        },
+        shape: ParticleShape::Dot,
+    }
+}
@@ -0,0 +1,443 @@
+//! Recording runs and watching them back.
+//!
+//! A run plays out the same every time from its seed, so a recording is only
+//! the seed, the map and rules, and what every player pressed each frame. The
+//! viewer plays it through once when loaded, keeping a copy of the game every
+//! few seconds, so jumping anywhere only has to replay from the copy before it.
+
+use macroquad::math::vec2;
+use serde_json::{json, Value};
+
+use crate::{
+    drive_player,
+    game::{Game, GameEvent, Level, Rules},
+    input::PlayerInput,
+    storage, timer_score, REPAIR_SCORE,
+};
+
+/// The last finished run is saved under this key.
+pub const REPLAY_KEY: &str = "replay";
+
+/// Seconds of the run between the copies the viewer jumps back to.
+const KEYFRAME_INTERVAL: f32 = 5.0;
+
+/// Slowest and fastest the viewer plays at.
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 8.0;
+
+/// Gives the file to watch if the game was started with `--replay [file]`,
+/// with no file the last run is watched.
+pub fn replay_arg() -> Option<Option<String>> {
+    let args: Vec<String> = std::env::args().collect();
+    let replay = args.iter().position(|arg| arg == "--replay")?;
+    Some(args.get(replay + 1).filter(|path| !path.starts_with("--")).cloned())
+}
+
+/// One frame of a run, how long it was and what each player pressed.
+#[derive(Clone)]
+struct Frame {
+    delta: f32,
+    inputs: Vec<PlayerInput>,
+}
+
+/// Everything needed to play a run again.
+#[derive(Clone)]
+pub struct Recording {
+    seed: u64,
+    players: usize,
+    level: Level,
+    rules: Rules,
+    frames: Vec<Frame>,
+}
+impl Recording {
+    pub fn new(seed: u64, players: usize, level: &Level, rules: &Rules) -> Self {
+        Self {
+            seed,
+            players,
+            level: level.clone(),
+            rules: *rules,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Adds a frame, `inputs` has what each player pressed before the game was updated by `delta`.
+    pub fn push(&mut self, delta: f32, inputs: Vec<PlayerInput>) {
+        self.frames.push(Frame { delta, inputs });
+    }
+
+    /// Gives the game as it was when the run started.
+    fn new_game(&self) -> Game {
+        Game::new(&self.level, &self.rules, self.seed, self.players)
+    }
+
+    /// Loads a recording saved with `save`, or from a file when `path` is given.
+    pub fn load(path: Option<&str>) -> Result<Self, String> {
+        let (name, text) = match path {
+            Some(path) => (path, std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?),
+            None => ("saved replay", storage::load(REPLAY_KEY).ok_or("no run has been recorded yet")?),
+        };
+        Recording::parse(name, &text)
+    }
+
+    /// Loads a recording saved with `save_as`.
+    pub fn load_saved(key: &str) -> Result<Self, String> {
+        let text = storage::load(key).ok_or(format!("{}: nothing is saved", key))?;
+        Recording::parse(key, &text)
+    }
+
+    fn parse(name: &str, text: &str) -> Result<Self, String> {
+        let value = serde_json::from_str(text).map_err(|e| format!("{}: {}", name, e))?;
+        Recording::from_json(&value).map_err(|e| format!("{}: {}", name, e))
+    }
+
+    /// Saves the recording over the last one.
+    pub fn save(&self) {
+        self.save_as(REPLAY_KEY);
+    }
+
+    /// Saves the recording under `key`, to keep it past the next run.
+    pub fn save_as(&self, key: &str) {
+        storage::save(key, &self.to_json().to_string());
+    }
+
+    /// Plays the whole run without showing it, counting the time and points
+    /// the same way the game did while it was played.
+    pub fn outcome(&self) -> Outcome {
+        let mut game = self.new_game();
+        let mut time_survived = 0.0;
+        let mut repairs = 0;
+        for frame in &self.frames {
+            // the game counts what happened last frame before playing the next
+            while let Some(event) = game.poll_event() {
+                if matches!(event, GameEvent::FixEBox(..) | GameEvent::CrewFixEBox(..)) {
+                    repairs += 1;
+                }
+            }
+            play_frame(&mut game, frame);
+            if game.generator().feul() <= 0.0 {
+                break;
+            }
+            time_survived += frame.delta;
+        }
+        Outcome {
+            time_survived,
+            score: repairs as f32 * REPAIR_SCORE + timer_score(time_survived),
+        }
+    }
+
+    /// Get the seed the run was played from.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Get how many played the run.
+    pub fn players(&self) -> usize {
+        self.players
+    }
+
+    /// Get a reference to the map the run was played on.
+    pub fn level(&self) -> &Level {
+        &self.level
+    }
+
+    /// Get the rules the run was played with.
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    /// Every frame is a flat list, its length then the movement and buttons of
+    /// each player, to keep long runs small. The buttons are sprint, dispatch
+    /// and above them the box picked on the map plus one, 0 when there is none.
+    fn to_json(&self) -> Value {
+        let frames: Vec<Value> = self
+            .frames
+            .iter()
+            .map(|frame| {
+                let mut values = vec![json!(frame.delta)];
+                for input in &frame.inputs {
+                    values.push(json!(input.movement.x));
+                    values.push(json!(input.movement.y));
+                    let target = input.target.map_or(0, |target| target as u64 + 1);
+                    values.push(json!(input.sprint as u64 | (input.dispatch as u64) << 1 | target << 2));
+                }
+                Value::Array(values)
+            })
+            .collect();
+        json!({
+            "seed": self.seed,
+            "players": self.players,
+            "map": self.level.to_json(),
+            "rules": self.rules.to_json(),
+            "frames": frames,
+        })
+    }
+
+    fn from_json(value: &Value) -> Result<Self, String> {
+        let seed = value["seed"].as_u64().ok_or("seed is missing")?;
+        let players = value["players"].as_u64().ok_or("players is missing")? as usize;
+        let level = Level::from_json(&value["map"])?;
+        let rules = Rules::from_json(&value["rules"]);
+
+        let mut frames = vec![];
+        for (i, frame) in value["frames"].as_array().ok_or("frames is not a list")?.iter().enumerate() {
+            let read = || -> Option<Frame> {
+                let values = frame.as_array()?;
+                if values.len() != 1 + players * 3 {
+                    return None;
+                }
+                let number = |i: usize| values[i].as_f64().map(|n| n as f32);
+                let mut inputs = vec![];
+                for player in 0..players {
+                    let at = 1 + player * 3;
+                    let buttons = values[at + 2].as_u64()?;
+                    inputs.push(PlayerInput {
+                        movement: vec2(number(at)?, number(at + 1)?),
+                        sprint: buttons & 1 != 0,
+                        dispatch: buttons & 2 != 0,
+                        target: (buttons >> 2).checked_sub(1).map(|target| target as usize),
+                    });
+                }
+                Some(Frame {
+                    delta: number(0)?,
+                    inputs,
+                })
+            };
+            frames.push(read().ok_or(format!("frame {} is broken", i))?);
+        }
+
+        Ok(Self {
+            seed,
+            players,
+            level,
+            rules,
+            frames,
+        })
+    }
+}
+
+/// How a recorded run went.
+pub struct Outcome {
+    pub time_survived: f32,
+    pub score: f32,
+}
+
+/// Plays a frame of a recording, the same way the run played it.
+fn play_frame(game: &mut Game, frame: &Frame) {
+    for (i, input) in frame.inputs.iter().enumerate() {
+        drive_player(game, i, &input.actions());
+    }
+    game.update(frame.delta);
+}
+
+/// What a mark on the timeline stands for.
+#[derive(Clone, Copy, PartialEq)]
+pub enum MarkerKind {
+    /// A player fixed a box.
+    Fix,
+    /// A helper fixed a box.
+    CrewFix,
+    /// Lightning broke a box.
+    Destroy,
+}
+
+/// Something worth finding on the timeline.
+pub struct Marker {
+    /// Seconds into the run.
+    pub time: f32,
+    pub kind: MarkerKind,
+}
+
+/// Plays a recording back at any speed, forwards or jumping around.
+///
+/// The game being shown isn't kept here, it's handed in so the app can draw
+/// it the same way as a run being played.
+pub struct ReplayViewer {
+    recording: Recording,
+    /// Seconds into the run each frame starts.
+    frame_times: Vec<f32>,
+    /// The game as it was before some frames, every `KEYFRAME_INTERVAL` seconds.
+    keyframes: Vec<(usize, Game)>,
+    markers: Vec<Marker>,
+    duration: f32,
+    /// The next frame to play.
+    frame: usize,
+    time: f32,
+    speed: f32,
+    paused: bool,
+}
+impl ReplayViewer {
+    /// Plays the whole recording through once to find the keyframes and markers.
+    pub fn new(recording: Recording) -> Self {
+        let mut game = recording.new_game();
+        let mut frame_times = Vec::with_capacity(recording.frames.len());
+        let mut keyframes = vec![];
+        let mut markers = vec![];
+        let mut time = 0.0;
+        for (i, frame) in recording.frames.iter().enumerate() {
+            if i == 0 || time >= keyframes.len() as f32 * KEYFRAME_INTERVAL {
+                keyframes.push((i, game.clone()));
+            }
+            frame_times.push(time);
+            play_frame(&mut game, frame);
+            time += frame.delta;
+
+            while let Some(event) = game.poll_event() {
+                let kind = match event {
+                    GameEvent::FixEBox(..) => MarkerKind::Fix,
+                    GameEvent::CrewFixEBox(..) => MarkerKind::CrewFix,
+                    GameEvent::DestroyEBox(_) => MarkerKind::Destroy,
+                    GameEvent::Restock(_) => continue,
+                };
+                markers.push(Marker { time, kind });
+            }
+        }
+        if keyframes.is_empty() {
+            keyframes.push((0, game));
+        }
+
+        Self {
+            recording,
+            frame_times,
+            keyframes,
+            markers,
+            duration: time,
+            frame: 0,
+            time: 0.0,
+            speed: 1.0,
+            paused: false,
+        }
+    }
+
+    /// Gives the game as the run started, to watch from the beginning.
+    pub fn start(&mut self) -> Game {
+        self.frame = 0;
+        self.time = 0.0;
+        self.keyframes[0].1.clone()
+    }
+
+    /// Plays the frames that fit in `delta` seconds at the current speed.
+    pub fn advance(&mut self, game: &mut Game, delta: f32) {
+        if self.paused {
+            return;
+        }
+        self.time = (self.time + delta * self.speed).min(self.duration);
+        self.play_until(game);
+        if self.frame >= self.recording.frames.len() {
+            self.paused = true;
+        }
+    }
+
+    /// Jumps to `time` seconds into the run, nothing that happened on the way is heard or seen.
+    pub fn seek(&mut self, game: &mut Game, time: f32) {
+        self.time = time.clamp(0.0, self.duration);
+        let (frame, keyframe) = self
+            .keyframes
+            .iter()
+            .rev()
+            .find(|(frame, _)| self.frame_times.get(*frame).is_none_or(|start| *start <= self.time))
+            .unwrap_or(&self.keyframes[0]);
+        // playing on from where we are is quicker than going back to a keyframe
+        let ahead_of_keyframe = self.frame >= *frame;
+        let before_time = self.frame_times.get(self.frame).is_some_and(|start| *start <= self.time);
+        if !(ahead_of_keyframe && before_time) {
+            *game = keyframe.clone();
+            self.frame = *frame;
+        }
+        self.play_until(game);
+        while game.poll_event().is_some() {}
+    }
+
+    /// Plays every frame that ends by the current time.
+    fn play_until(&mut self, game: &mut Game) {
+        while let Some(frame) = self.recording.frames.get(self.frame) {
+            if self.frame_times[self.frame] + frame.delta > self.time {
+                break;
+            }
+            play_frame(game, frame);
+            self.frame += 1;
+        }
+    }
+
+    /// Pauses or plays on, playing again from the end starts over.
+    pub fn toggle_pause(&mut self, game: &mut Game) {
+        if self.paused && self.time >= self.duration {
+            self.seek(game, 0.0);
+        }
+        self.paused = !self.paused;
+    }
+
+    /// Multiplies the speed by `factor`, staying between a quarter and eight times as fast.
+    pub fn change_speed(&mut self, factor: f32) {
+        self.speed = (self.speed * factor).clamp(MIN_SPEED, MAX_SPEED);
+    }
+
+    /// Gives the points scored by the current time.
+    pub fn score(&self) -> f32 {
+        let repairs = self
+            .markers
+            .iter()
+            .filter(|marker| marker.time <= self.time && marker.kind != MarkerKind::Destroy)
+            .count();
+        repairs as f32 * REPAIR_SCORE
+    }
+
+    /// Get the seconds into the run being shown.
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Get how long the recorded run lasted in seconds.
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    /// Get how many times faster than real time the run plays.
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Get whether playing is paused.
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Get a reference to the boxes fixed and broken along the run.
+    pub fn markers(&self) -> &[Marker] {
+        self.markers.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    #[test]
+    fn saved_recordings_play_out_the_same() {
+        let mut rules = Rules::embedded();
+        // a short storm that breaks boxes often, so there are repairs to count
+        rules.generator_fuel *= 0.25;
+        rules.break_interval = (0.5, 1.0);
+        let level = Level::embedded();
+        let mut recording = Recording::new(1234, 2, &level, &rules);
+        let mut rng = Rng::new(99);
+        for _ in 0..60 * 60 {
+            let inputs = (0..2)
+                .map(|_| PlayerInput {
+                    movement: vec2(rng.range(-1.0, 1.0), rng.range(-1.0, 1.0)),
+                    sprint: rng.chance(0.5),
+                    dispatch: rng.chance(0.05),
+                    target: rng.chance(0.02).then(|| rng.next_u32() as usize % level.electrical_boxes.len()),
+                })
+                .collect();
+            recording.push(1.0 / 60.0, inputs);
+        }
+
+        let text = recording.to_json().to_string();
+        let loaded = Recording::parse("test", &text).unwrap();
+        let (played, replayed) = (recording.outcome(), loaded.outcome());
+        assert!(played.score > 0.0);
+        assert_eq!(played.score, replayed.score);
+        assert_eq!(played.time_survived, replayed.time_survived);
+    }
+}
@@ -0,0 +1,50 @@
+/// A small seedable random number generator (PCG32).
+///
+/// macroquad's `gen_range` shares one global state with everything else that
+/// rolls dice, so anything that has to replay the same way from a seed owns
+/// one of these instead.
+#[derive(Clone)]
+pub struct Rng {
+    state: u64,
+}
+impl Rng {
+    const MULTIPLIER: u64 = 6364136223846793005;
+    const INCREMENT: u64 = 1442695040888963407;
+
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Self { state: 0 };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    /// Seeds a new generator from macroquad's global one, for things that don't need replaying.
+    pub fn from_global() -> Self {
+        let high = macroquad::rand::rand() as u64;
+        let low = macroquad::rand::rand() as u64;
+        Rng::new(high << 32 | low)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old
+            .wrapping_mul(Self::MULTIPLIER)
+            .wrapping_add(Self::INCREMENT);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    /// Gives a number from `low` up to but not including `high`.
+    pub fn range(&mut self, low: f32, high: f32) -> f32 {
+        // 24 bits is all an f32 can hold exactly, so `t` never rounds up to 1.0
+        let t = (self.next_u32() >> 8) as f32 / (1 << 24) as f32;
+        low + (high - low) * t
+    }
+
+    /// Returns true with the given probability.
+    pub fn chance(&mut self, probability: f32) -> bool {
+        self.range(0.0, 1.0) < probability
+    }
+}
@@ -0,0 +1,272 @@
+//! The sandbox, the endless storm with a panel to play with it.
+//!
+//! The generator is kept full, and clicking the city strikes lightning,
+//! breaks or fixes the box under the cursor or leaves a puddle there, depending
+//! on the tool picked on the panel. The panel also changes how often the storm
+//! breaks boxes and makes puddles while the game goes on.
+
+use macroquad::prelude::*;
+
+use crate::{
+    game::{Game, HitBox},
+    rect_center,
+};
+
+/// How close to a box the cursor has to be to pick it.
+const PICK_RADIUS: f32 = 2.0;
+
+/// How much one press of a timer button stretches or shrinks it.
+const INTERVAL_STEP: f32 = 1.25;
+
+/// Shortest and longest time a timer can be set to wait, in seconds.
+const INTERVAL_RANGE: (f32, f32) = (0.05, 120.0);
+
+const PANEL_WIDTH: f32 = 280.0;
+const ROW_HEIGHT: f32 = 28.0;
+
+/// What a click on the city does.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SandboxTool {
+    Lightning,
+    BreakBox,
+    FixBox,
+    Puddle,
+}
+impl SandboxTool {
+    const ALL: [SandboxTool; 4] = [
+        SandboxTool::Lightning,
+        SandboxTool::BreakBox,
+        SandboxTool::FixBox,
+        SandboxTool::Puddle,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            SandboxTool::Lightning => "Strike lightning",
+            SandboxTool::BreakBox => "Break a box",
+            SandboxTool::FixBox => "Fix a box",
+            SandboxTool::Puddle => "Spawn a puddle",
+        }
+    }
+}
+
+/// Something on the panel that can be clicked.
+#[derive(Clone, Copy)]
+enum Button {
+    Tool(SandboxTool),
+    BreakAll,
+    FixAll,
+    ClearPuddles,
+    InfiniteKits,
+    BreakSooner,
+    BreakLater,
+    PuddlesSooner,
+    PuddlesLater,
+}
+
+/// The sandbox panel and the tool picked on it.
+pub struct Sandbox {
+    tool: SandboxTool,
+    infinite_kits: bool,
+}
+impl Sandbox {
+    pub fn new() -> Self {
+        Self {
+            tool: SandboxTool::Lightning,
+            infinite_kits: false,
+        }
+    }
+
+    /// Keeps the generator full, and the kits too if asked, after the game was updated.
+    pub fn update(&mut self, game: &mut Game) {
+        *game.generator_mut().feul_mut() = game.rules().generator_fuel;
+        if self.infinite_kits {
+            game.restock_everyone();
+        }
+    }
+
+    /// Handles a click at `mouse` on the screen, `world` being the same point in the city.
+    ///
+    /// Gives where lightning should be seen striking, when it hit no box.
+    pub fn click(&mut self, game: &mut Game, mouse: Vec2, world: Vec2, text_scale: f32) -> Option<Vec2> {
+        if Sandbox::area(text_scale).contains(mouse) {
+            let clicked = Sandbox::layout(text_scale)
+                .into_iter()
+                .find(|(rect, _)| rect.contains(mouse));
+            if let Some((_, button)) = clicked {
+                self.press(button, game);
+            }
+            return None;
+        }
+
+        let picked = closest_box(game, world);
+        match (self.tool, picked) {
+            // breaking the box brings its own bolt down
+            (SandboxTool::Lightning, Some(index)) if !*game.electrical_boxes()[index].broken() => {
+                game.break_ebox(index);
+                None
+            }
+            (SandboxTool::Lightning, _) => Some(world),
+            (SandboxTool::BreakBox, Some(index)) => {
+                game.break_ebox(index);
+                None
+            }
+            (SandboxTool::FixBox, Some(index)) => {
+                game.fix_ebox(index);
+                None
+            }
+            (SandboxTool::Puddle, _) => {
+                game.spawn_puddle_at(world);
+                None
+            }
+            (SandboxTool::BreakBox | SandboxTool::FixBox, None) => None,
+        }
+    }
+
+    fn press(&mut self, button: Button, game: &mut Game) {
+        let rules = *game.rules();
+        match button {
+            Button::Tool(tool) => self.tool = tool,
+            Button::BreakAll => {
+                for index in 0..game.electrical_boxes().len() {
+                    game.break_ebox(index);
+                }
+            }
+            Button::FixAll => {
+                for index in 0..game.electrical_boxes().len() {
+                    game.fix_ebox(index);
+                }
+            }
+            Button::ClearPuddles => game.clear_puddles(),
+            Button::InfiniteKits => self.infinite_kits = !self.infinite_kits,
+            Button::BreakSooner => {
+                game.set_intervals(scale_interval(rules.break_interval, 1.0 / INTERVAL_STEP), rules.puddle_interval)
+            }
+            Button::BreakLater => {
+                game.set_intervals(scale_interval(rules.break_interval, INTERVAL_STEP), rules.puddle_interval)
+            }
+            Button::PuddlesSooner => {
+                game.set_intervals(rules.break_interval, scale_interval(rules.puddle_interval, 1.0 / INTERVAL_STEP))
+            }
+            Button::PuddlesLater => {
+                game.set_intervals(rules.break_interval, scale_interval(rules.puddle_interval, INTERVAL_STEP))
+            }
+        }
+    }
+
+    /// Gives where the panel is on the screen, in the bottom right corner.
+    fn area(text_scale: f32) -> Rect {
+        let width = PANEL_WIDTH * text_scale;
+        let height = ROW_HEIGHT * text_scale * 12.0;
+        Rect::new(screen_width() - width - 10.0, screen_height() - height - 10.0, width, height)
+    }
+
+    /// Gives every button on the panel and where it is.
+    fn layout(text_scale: f32) -> Vec<(Rect, Button)> {
+        let area = Sandbox::area(text_scale);
+        let row_height = ROW_HEIGHT * text_scale;
+        let row = |i: usize| Rect::new(area.x, area.y + row_height * (i as f32 + 1.0), area.w, row_height - 2.0);
+        // a timer's row is its label between a button on either side
+        let minus = |rect: Rect| Rect::new(rect.x, rect.y, row_height, rect.h);
+        let plus = |rect: Rect| Rect::new(rect.right() - row_height, rect.y, row_height, rect.h);
+
+        let mut buttons: Vec<(Rect, Button)> = SandboxTool::ALL
+            .iter()
+            .enumerate()
+            .map(|(i, tool)| (row(i), Button::Tool(*tool)))
+            .collect();
+        buttons.push((row(4), Button::BreakAll));
+        buttons.push((row(5), Button::FixAll));
+        buttons.push((row(6), Button::ClearPuddles));
+        buttons.push((row(7), Button::InfiniteKits));
+        buttons.push((minus(row(8)), Button::BreakSooner));
+        buttons.push((plus(row(8)), Button::BreakLater));
+        buttons.push((minus(row(9)), Button::PuddlesSooner));
+        buttons.push((plus(row(9)), Button::PuddlesLater));
+        buttons
+    }
+
+    fn label(&self, button: Button) -> String {
+        match button {
+            Button::Tool(tool) => tool.name().to_string(),
+            Button::BreakAll => "Break every box".to_string(),
+            Button::FixAll => "Fix every box".to_string(),
+            Button::ClearPuddles => "Clear puddles".to_string(),
+            Button::InfiniteKits => {
+                format!("Infinite repair kits: {}", if self.infinite_kits { "On" } else { "Off" })
+            }
+            Button::BreakSooner | Button::PuddlesSooner => "-".to_string(),
+            Button::BreakLater | Button::PuddlesLater => "+".to_string(),
+        }
+    }
+
+    /// Draws the panel, on the default camera.
+    pub fn draw(&self, game: &Game, text_scale: f32) {
+        let area = Sandbox::area(text_scale);
+        let size = 20.0 * text_scale;
+        let row_height = ROW_HEIGHT * text_scale;
+        let mouse = Vec2::from(mouse_position());
+        draw_rectangle(area.x, area.y, area.w, area.h, Color::new(0.0, 0.0, 0.0, 0.7));
+        draw_text("Sandbox", area.x + 8.0, area.y + row_height * 0.75, size * 1.1, YELLOW);
+
+        for (rect, button) in Sandbox::layout(text_scale) {
+            let picked = matches!(button, Button::Tool(tool) if tool == self.tool)
+                || matches!(button, Button::InfiniteKits if self.infinite_kits);
+            let colour = if picked {
+                Color::new(0.9, 0.7, 0.1, 0.6)
+            } else if rect.contains(mouse) {
+                Color::new(1.0, 1.0, 1.0, 0.25)
+            } else {
+                Color::new(1.0, 1.0, 1.0, 0.1)
+            };
+            draw_rectangle(rect.x, rect.y, rect.w, rect.h, colour);
+            let label = self.label(button);
+            let width = measure_text(&label, None, size as u16, 1.0).width;
+            draw_text(&label, rect.x + (rect.w - width) / 2.0, rect.y + rect.h * 0.7, size, WHITE);
+        }
+
+        let rules = game.rules();
+        let timers = [
+            ("Boxes break", rules.break_interval),
+            ("Puddles form", rules.puddle_interval),
+        ];
+        for (i, (name, (min, max))) in timers.into_iter().enumerate() {
+            let y = area.y + row_height * (i as f32 + 9.0);
+            let label = format!("{} every {:.2}-{:.2}s", name, min, max);
+            let width = measure_text(&label, None, size as u16, 1.0).width;
+            draw_text(&label, area.x + (area.w - width) / 2.0, y + row_height * 0.7, size, WHITE);
+        }
+
+        let hint = "Click the city to use the tool";
+        draw_text(hint, area.x + 8.0, area.bottom() - row_height * 0.3, size * 0.8, GRAY);
+    }
+
+    /// Marks what a click at `world` would hit, drawn in the world.
+    pub fn draw_cursor(&self, game: &Game, world: Vec2) {
+        let colour = Color::new(1.0, 1.0, 0.0, 0.8);
+        match (self.tool, closest_box(game, world)) {
+            (SandboxTool::Lightning | SandboxTool::BreakBox | SandboxTool::FixBox, Some(index)) => {
+                let ebox = rect_center(game.electrical_boxes()[index].hit_box());
+                draw_circle_lines(ebox.x, ebox.y, 1.2, 0.1, colour);
+            }
+            (SandboxTool::Puddle, _) => draw_rectangle_lines(world.x - 0.5, world.y - 0.5, 1.0, 1.0, 0.1, colour),
+            _ => draw_circle_lines(world.x, world.y, 0.4, 0.08, colour),
+        }
+    }
+}
+
+/// Gives the box closest to `point`, if any is close enough to pick.
+fn closest_box(game: &Game, point: Vec2) -> Option<usize> {
+    game.electrical_boxes()
+        .iter()
+        .enumerate()
+        .map(|(i, ebox)| (i, rect_center(ebox.hit_box()).distance(point)))
+        .filter(|(_, distance)| *distance < PICK_RADIUS)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+}
+
+fn scale_interval((min, max): (f32, f32), factor: f32) -> (f32, f32) {
+    let clamp = |seconds: f32| seconds.clamp(INTERVAL_RANGE.0, INTERVAL_RANGE.1);
+    (clamp(min * factor), clamp(max * factor))
+}
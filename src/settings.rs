@@ -0,0 +1,129 @@
+use serde_json::json;
+
+use crate::game::Difficulty;
+use crate::input::Bindings;
+use crate::storage;
+
+const SETTINGS_KEY: &str = "settings";
+
+pub struct Settings {
+    pub master_volume: f32,
+    pub sfx_volume: f32,
+    pub music_volume: f32,
+    pub fullscreen: bool,
+    pub camera_zoom: f32,
+    pub reduce_flashing: bool,
+    pub text_scale: f32,
+    pub rumble: bool,
+    pub screen_shake: bool,
+    /// Players sharing the screen in local co-op.
+    pub players: u32,
+    /// How hard endless runs are, from the next run on.
+    pub difficulty: Difficulty,
+    pub bindings: Bindings,
+}
+impl Settings {
+    pub const MIN_CAMERA_ZOOM: f32 = 0.7;
+    pub const MAX_CAMERA_ZOOM: f32 = 1.5;
+    pub const MIN_TEXT_SCALE: f32 = 1.0;
+    pub const MAX_TEXT_SCALE: f32 = 2.0;
+    pub const MAX_PLAYERS: u32 = 4;
+
+    /// Loads the saved settings, using the default for anything missing.
+    pub fn load() -> Self {
+        let mut settings = Settings::default();
+
+        let saved = match storage::load(SETTINGS_KEY) {
+            Some(saved) => saved,
+            None => return settings,
+        };
+        let saved: serde_json::Value = match serde_json::from_str(&saved) {
+            Ok(saved) => saved,
+            Err(_) => return settings,
+        };
+
+        if let Some(v) = saved["master_volume"].as_f64() {
+            settings.master_volume = (v as f32).clamp(0.0, 1.0);
+        }
+        if let Some(v) = saved["sfx_volume"].as_f64() {
+            settings.sfx_volume = (v as f32).clamp(0.0, 1.0);
+        }
+        if let Some(v) = saved["music_volume"].as_f64() {
+            settings.music_volume = (v as f32).clamp(0.0, 1.0);
+        }
+        if let Some(v) = saved["fullscreen"].as_bool() {
+            settings.fullscreen = v;
+        }
+        if let Some(v) = saved["camera_zoom"].as_f64() {
+            settings.camera_zoom = (v as f32).clamp(Self::MIN_CAMERA_ZOOM, Self::MAX_CAMERA_ZOOM);
+        }
+        if let Some(v) = saved["reduce_flashing"].as_bool() {
+            settings.reduce_flashing = v;
+        }
+        if let Some(v) = saved["text_scale"].as_f64() {
+            settings.text_scale = (v as f32).clamp(Self::MIN_TEXT_SCALE, Self::MAX_TEXT_SCALE);
+        }
+        if let Some(v) = saved["rumble"].as_bool() {
+            settings.rumble = v;
+        }
+        if let Some(v) = saved["screen_shake"].as_bool() {
+            settings.screen_shake = v;
+        }
+        if let Some(v) = saved["players"].as_u64() {
+            settings.players = (v as u32).clamp(1, Self::MAX_PLAYERS);
+        }
+        if let Some(difficulty) = saved["difficulty"].as_str().and_then(Difficulty::from_name) {
+            settings.difficulty = difficulty;
+        }
+        settings.bindings = Bindings::from_json(&saved["bindings"]);
+
+        settings
+    }
+
+    pub fn save(&self) {
+        let saved = json!({
+            "master_volume": self.master_volume,
+            "sfx_volume": self.sfx_volume,
+            "music_volume": self.music_volume,
+            "fullscreen": self.fullscreen,
+            "camera_zoom": self.camera_zoom,
+            "reduce_flashing": self.reduce_flashing,
+            "text_scale": self.text_scale,
+            "rumble": self.rumble,
+            "screen_shake": self.screen_shake,
+            "players": self.players,
+            "difficulty": self.difficulty.name(),
+            "bindings": self.bindings.to_json(),
+        });
+        storage::save(SETTINGS_KEY, &saved.to_string());
+    }
+
+    /// Gives the volume sound effects should be played at.
+    pub fn effective_sfx_volume(&self) -> f32 {
+        self.master_volume * self.sfx_volume
+    }
+
+    /// Gives the volume music should be played at.
+    pub fn effective_music_volume(&self) -> f32 {
+        self.master_volume * self.music_volume
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            master_volume: 1.0,
+            sfx_volume: 1.0,
+            music_volume: 0.7,
+            fullscreen: false,
+            camera_zoom: 1.0,
+            reduce_flashing: false,
+            text_scale: 1.0,
+            rumble: true,
+            screen_shake: true,
+            players: 1,
+            difficulty: Difficulty::Normal,
+            bindings: Bindings::default(),
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! Soak testing, started with `--soak`, where the bot plays runs without a window.
+//!
+//! `cargo run --release -- --soak 50 --seed 1 --max-time 600` plays 50 runs with
+//! seeds 1 to 50 on `map.json` and `rules.json`, as fast as the computer can,
+//! and prints how long the city held out.
+
+use crate::{
+    bot::Bot,
+    drive_player,
+    game::{Game, GameEvent},
+    hot_reload::{MAP_PATH, RULES_PATH},
+    timer_score, App, REPAIR_SCORE,
+};
+
+/// The step every run is played with, the same as a 60 fps screen.
+const TIME_STEP: f32 = 1.0 / 60.0;
+
+/// How a soak test should be run, read from the command line.
+pub struct SoakOptions {
+    runs: u32,
+    first_seed: u64,
+    /// Runs still going after this many seconds are stopped and counted as survived.
+    max_time: f32,
+}
+impl SoakOptions {
+    /// Gives the options if the game was started with `--soak [runs]`, taking
+    /// `--seed` and `--max-time` too.
+    pub fn from_args() -> Option<Self> {
+        let args: Vec<String> = std::env::args().collect();
+        let soak = args.iter().position(|arg| arg == "--soak")?;
+        let value = |flag: &str| {
+            let i = args.iter().position(|arg| arg == flag)?;
+            args.get(i + 1)
+        };
+
+        Some(Self {
+            runs: args.get(soak + 1).and_then(|n| n.parse().ok()).unwrap_or(20),
+            first_seed: value("--seed").and_then(|n| n.parse().ok()).unwrap_or(1),
+            max_time: value("--max-time").and_then(|n| n.parse().ok()).unwrap_or(600.0),
+        })
+    }
+}
+
+/// How one run went.
+struct RunResult {
+    seed: u64,
+    time_survived: f32,
+    score: f32,
+    /// Fraction of the run the generator was on.
+    generator_on: f32,
+}
+
+/// Plays every run and prints a line for each, then a summary.
+pub fn run_soak(options: &SoakOptions) {
+    let (level, rules) = match App::read_level() {
+        Ok(level) => level,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut results = Vec::new();
+    for seed in options.first_seed..options.first_seed + options.runs as u64 {
+        let mut game = Game::new(&level, &rules, seed, 1);
+        let mut bot = Bot::new();
+        let mut time_survived = 0.0;
+        let mut repairs = 0;
+        let mut generator_time = 0.0;
+
+        while game.generator().feul() > 0.0 && time_survived < options.max_time {
+            let actions = bot.actions(&game, TIME_STEP);
+            drive_player(&mut game, 0, &actions);
+            game.update(TIME_STEP);
+            while let Some(event) = game.poll_event() {
+                if matches!(event, GameEvent::FixEBox(..) | GameEvent::CrewFixEBox(..)) {
+                    repairs += 1;
+                }
+            }
+            if game.generator().running() {
+                generator_time += TIME_STEP;
+            }
+            time_survived += TIME_STEP;
+        }
+
+        let result = RunResult {
+            seed,
+            time_survived,
+            score: repairs as f32 * REPAIR_SCORE + timer_score(time_survived),
+            generator_on: generator_time / time_survived.max(TIME_STEP),
+        };
+        println!(
+            "seed {}: survived {:.1}s, score {}, generator on {:.0}%",
+            result.seed,
+            result.time_survived,
+            result.score,
+            result.generator_on * 100.0
+        );
+        results.push(result);
+    }
+
+    if results.is_empty() {
+        return;
+    }
+    let survivors = results
+        .iter()
+        .filter(|result| result.time_survived >= options.max_time)
+        .count();
+    println!();
+    println!(
+        "{} runs of {} with {}, stopped after {}s",
+        results.len(),
+        MAP_PATH,
+        RULES_PATH,
+        options.max_time
+    );
+    print_spread("survival", &results.iter().map(|r| r.time_survived).collect::<Vec<_>>(), "s");
+    print_spread("score", &results.iter().map(|r| r.score).collect::<Vec<_>>(), "");
+    let generator_on = results.iter().map(|r| r.generator_on).sum::<f32>() / results.len() as f32;
+    println!("generator on {:.0}% of the time", generator_on * 100.0);
+    println!("{} of {} runs lasted until they were stopped", survivors, results.len());
+}
+
+/// Prints the lowest, median, mean and highest of `values`.
+fn print_spread(name: &str, values: &[f32], unit: &str) {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mean = sorted.iter().sum::<f32>() / sorted.len() as f32;
+    println!(
+        "{:<9} min {:.1}{unit}  median {:.1}{unit}  mean {:.1}{unit}  max {:.1}{unit}",
+        name,
+        sorted[0],
+        sorted[sorted.len() / 2],
+        mean,
+        sorted[sorted.len() - 1],
+        unit = unit
+    );
+}
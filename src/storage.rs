@@ -0,0 +1,38 @@
+//! Tiny key value store for data that should survive between sessions.
+//!
+//! On native platforms every key is saved as a json file in the `saves` folder
+//! next to the executable's working directory. The browser build keeps them in
+//! the page's `localStorage` instead, through `power_crisis.js`.
+
+#[cfg(not(target_arch = "wasm32"))]
+const SAVE_DIRECTORY: &str = "saves";
+
+#[cfg(not(target_arch = "wasm32"))]
+fn path(key: &str) -> std::path::PathBuf {
+    std::path::Path::new(SAVE_DIRECTORY).join(format!("{}.json", key))
+}
+
+/// Loads the value saved under `key`, if there is one.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load(key: &str) -> Option<String> {
+    std::fs::read_to_string(path(key)).ok()
+}
+
+/// Saves `value` under `key`, overwriting what was there before.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save(key: &str, value: &str) {
+    if std::fs::create_dir_all(SAVE_DIRECTORY).is_err() {
+        return;
+    }
+    let _ = std::fs::write(path(key), value);
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn load(key: &str) -> Option<String> {
+    crate::browser::storage_load(key)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn save(key: &str, value: &str) {
+    crate::browser::storage_save(key, value)
+}
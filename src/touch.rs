@@ -0,0 +1,232 @@
+use macroquad::prelude::*;
+
+use crate::input::{Action, ActionState};
+
+/// How far the stick has to be dragged, relative to its radius, before the player moves.
+const STICK_DEAD_ZONE: f32 = 0.15;
+
+/// Replay controls shown along the top, with what they press and their label.
+const REPLAY_BUTTONS: [(Action, &str); 6] = [
+    (Action::SkipBack, "<<"),
+    (Action::PlayPause, "||"),
+    (Action::SkipForward, ">>"),
+    (Action::Slower, "-"),
+    (Action::Faster, "+"),
+    (Action::FreeCamera, "CAM"),
+];
+
+/// What is on the screen, which decides the controls shown.
+#[derive(Clone, Copy, PartialEq)]
+pub enum TouchScreen {
+    Menu,
+    Game,
+    Replay,
+}
+
+/// A round on-screen control.
+struct Button {
+    center: Vec2,
+    radius: f32,
+}
+impl Button {
+    fn contains(&self, point: Vec2) -> bool {
+        self.center.distance(point) <= self.radius
+    }
+}
+
+/// Where the on-screen controls go for the current screen size.
+struct Layout {
+    joystick: Button,
+    sprint: Button,
+    dispatch: Button,
+    pause: Button,
+    map: Button,
+    replay: Vec<Button>,
+}
+impl Layout {
+    fn new(width: f32, height: f32) -> Self {
+        // sized from the shorter side so thumbs reach in both portrait and landscape
+        let size = width.min(height);
+        let margin = size * 0.22;
+        Self {
+            joystick: Button {
+                center: vec2(margin, height - margin),
+                radius: size * 0.14,
+            },
+            sprint: Button {
+                center: vec2(width - margin, height - margin),
+                radius: size * 0.09,
+            },
+            dispatch: Button {
+                center: vec2(width - margin, height - margin - size * 0.22),
+                radius: size * 0.07,
+            },
+            pause: Button {
+                center: vec2(width / 2.0 - size * 0.08, size * 0.08),
+                radius: size * 0.05,
+            },
+            map: Button {
+                center: vec2(width / 2.0 + size * 0.08, size * 0.08),
+                radius: size * 0.05,
+            },
+            replay: (0..REPLAY_BUTTONS.len())
+                .map(|i| Button {
+                    center: vec2(width / 2.0 + size * 0.12 * (i as f32 - 2.5), size * 0.2),
+                    radius: size * 0.05,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Virtual joystick and buttons for phones and tablets.
+///
+/// Nothing is drawn until the first touch, so desktop players never see them.
+pub struct TouchControls {
+    active: bool,
+    screen: TouchScreen,
+    joystick_touch: Option<u64>,
+    stick: Vec2,
+}
+impl TouchControls {
+    pub fn new() -> Self {
+        Self {
+            active: false,
+            screen: TouchScreen::Menu,
+            joystick_touch: None,
+            stick: vec2(0.0, 0.0),
+        }
+    }
+
+    /// Adds the touches of this frame to the actions.
+    ///
+    /// Outside of the game any tap confirms, which restarts runs. Menus ignore
+    /// that and find the item that was tapped themselves. Replays keep the
+    /// joystick for the free camera, with their own buttons instead of running
+    /// and sending helpers.
+    pub fn update(&mut self, actions: &mut ActionState, screen: TouchScreen) {
+        self.screen = screen;
+        let touches = touches();
+        if touches.is_empty() {
+            self.joystick_touch = None;
+            self.stick = vec2(0.0, 0.0);
+            return;
+        }
+        self.active = true;
+
+        if screen == TouchScreen::Menu {
+            if touches.iter().any(|t| t.phase == TouchPhase::Started) {
+                actions.press(Action::Confirm);
+            }
+            return;
+        }
+
+        let layout = Layout::new(screen_width(), screen_height());
+        let replay_button = |position: Vec2| {
+            let buttons = if screen == TouchScreen::Replay { &layout.replay[..] } else { &[] };
+            buttons.iter().position(|button| button.contains(position))
+        };
+
+        for touch in &touches {
+            let ended = matches!(touch.phase, TouchPhase::Ended | TouchPhase::Cancelled);
+
+            if touch.phase == TouchPhase::Started {
+                if layout.pause.contains(touch.position) {
+                    actions.press(Action::Pause);
+                } else if layout.map.contains(touch.position) {
+                    actions.press(Action::ToggleMinimap);
+                } else if let Some(i) = replay_button(touch.position) {
+                    actions.press(REPLAY_BUTTONS[i].0);
+                } else if screen == TouchScreen::Game && layout.dispatch.contains(touch.position) {
+                    actions.press(Action::Dispatch);
+                } else if self.joystick_touch.is_none()
+                    && touch.position.distance(layout.joystick.center) < layout.joystick.radius * 2.0
+                {
+                    self.joystick_touch = Some(touch.id);
+                }
+            }
+
+            if self.joystick_touch == Some(touch.id) {
+                if ended {
+                    self.joystick_touch = None;
+                    self.stick = vec2(0.0, 0.0);
+                } else {
+                    let offset = (touch.position - layout.joystick.center) / layout.joystick.radius;
+                    // screen y points down, the world's y points up
+                    self.stick = vec2(offset.x, -offset.y);
+                    if self.stick.length() > 1.0 {
+                        self.stick = self.stick.normalize();
+                    }
+                }
+            } else if !ended && screen == TouchScreen::Game && layout.sprint.contains(touch.position) {
+                actions.hold(Action::Sprint);
+            }
+        }
+
+        if self.stick.length() > STICK_DEAD_ZONE {
+            actions.set_analog_movement(self.stick);
+        }
+    }
+
+    /// Returns true when `point` is on one of the controls shown, so a tap there
+    /// isn't taken for a tap on what is under it.
+    pub fn contains(&self, point: Vec2) -> bool {
+        if !self.active || self.screen == TouchScreen::Menu {
+            return false;
+        }
+        let layout = Layout::new(screen_width(), screen_height());
+        let mut buttons = vec![&layout.pause, &layout.map];
+        match self.screen {
+            TouchScreen::Game => buttons.extend([&layout.sprint, &layout.dispatch]),
+            TouchScreen::Replay => buttons.extend(&layout.replay),
+            TouchScreen::Menu => {}
+        }
+        // the stick is grabbed from twice its size
+        point.distance(layout.joystick.center) < layout.joystick.radius * 2.0
+            || buttons.iter().any(|button| button.contains(point))
+    }
+
+    /// Returns true once the player has touched the screen.
+    pub fn active(&self) -> bool {
+        self.active
+    }
+
+    /// Draws the controls in screen space.
+    pub fn draw(&self) {
+        if !self.active {
+            return;
+        }
+        let layout = Layout::new(screen_width(), screen_height());
+        let colour = Color::new(1.0, 1.0, 1.0, 0.3);
+
+        let joystick = &layout.joystick;
+        draw_circle_lines(joystick.center.x, joystick.center.y, joystick.radius, 3.0, colour);
+        let knob = joystick.center + vec2(self.stick.x, -self.stick.y) * joystick.radius;
+        draw_circle(knob.x, knob.y, joystick.radius * 0.4, colour);
+
+        let map = &layout.map;
+        draw_circle_lines(map.center.x, map.center.y, map.radius, 2.0, colour);
+        draw_text("M", map.center.x - map.radius * 0.35, map.center.y + map.radius * 0.35, map.radius, WHITE);
+
+        let pause = &layout.pause;
+        draw_circle_lines(pause.center.x, pause.center.y, pause.radius, 2.0, colour);
+        draw_text("II", pause.center.x - pause.radius * 0.35, pause.center.y + pause.radius * 0.35, pause.radius, WHITE);
+
+        if self.screen == TouchScreen::Replay {
+            for (button, (_, label)) in layout.replay.iter().zip(REPLAY_BUTTONS) {
+                draw_circle_lines(button.center.x, button.center.y, button.radius, 2.0, colour);
+                let width = measure_text(label, None, button.radius as u16, 1.0).width;
+                draw_text(label, button.center.x - width / 2.0, button.center.y + button.radius * 0.35, button.radius, WHITE);
+            }
+            return;
+        }
+
+        let sprint = &layout.sprint;
+        draw_circle(sprint.center.x, sprint.center.y, sprint.radius, colour);
+        draw_text("RUN", sprint.center.x - sprint.radius * 0.6, sprint.center.y + sprint.radius * 0.2, sprint.radius * 0.7, WHITE);
+
+        let dispatch = &layout.dispatch;
+        draw_circle_lines(dispatch.center.x, dispatch.center.y, dispatch.radius, 2.0, colour);
+        draw_text("CREW", dispatch.center.x - dispatch.radius * 0.75, dispatch.center.y + dispatch.radius * 0.2, dispatch.radius * 0.6, WHITE);
+    }
+}
@@ -0,0 +1,193 @@
+//! The tutorial, a calm corner of the city where the storm does what the lesson needs.
+//!
+//! `tutorial.json` has a small corner of the city with a handful of boxes close
+//! to the van, and turns off the random storm. Boxes break when a step starts
+//! instead, and a step is done when the game gets there, a box being fixed or
+//! the van being reached, not after some time has passed.
+
+use macroquad::math::Vec2;
+
+use crate::{
+    game::{Game, GameEvent, HitBox, Level, Rules},
+    rect_center,
+};
+
+/// How close the player has to get to a box for the walk to be over.
+const NEAR_BOX: f32 = 3.0;
+
+/// Fuel the generator has to burn before the player is told how to stop it.
+const FUEL_TO_WATCH: f32 = 0.15;
+
+/// Boxes broken at once to show the generator coming on.
+const BLACKOUT_BOXES: usize = 4;
+
+/// Where the player is in the tutorial.
+#[derive(Clone, Copy, PartialEq)]
+pub enum TutorialStep {
+    WalkToBox,
+    FixBox,
+    RunOutOfKits,
+    Restock,
+    FixLastBox,
+    Blackout,
+    SaveFuel,
+    Done,
+}
+
+/// Plays the tutorial along with the game, breaking boxes and moving on as each step is done.
+pub struct Tutorial {
+    step: TutorialStep,
+    /// The box the first steps are about.
+    first_box: usize,
+    /// Fuel in the generator when the blackout started.
+    blackout_fuel: f32,
+    /// Whether the step just started and its boxes still have to be broken.
+    strike: bool,
+}
+impl Tutorial {
+    pub fn new() -> Self {
+        Self {
+            step: TutorialStep::WalkToBox,
+            first_box: 0,
+            blackout_fuel: 0.0,
+            strike: false,
+        }
+    }
+
+    fn enter(&mut self, step: TutorialStep) {
+        self.step = step;
+        self.strike = true;
+    }
+
+    /// Gives the tutorial map from `tutorial.json`, and its changes to `rules`.
+    pub fn level(rules: &Rules) -> (Level, Rules) {
+        let tutorial: serde_json::Value = serde_json::from_slice(include_bytes!("../tutorial.json")).unwrap();
+        (Level::from_json(&tutorial["map"]).unwrap(), rules.with_changes(&tutorial["rules"]))
+    }
+
+    /// Starts the lesson on a new `game`, breaking the box closest to the player.
+    pub fn start(&mut self, game: &mut Game) {
+        *self = Tutorial::new();
+        let player = rect_center(game.players()[0].hit_box());
+        self.first_box = closest_boxes(game, player)[0];
+        game.break_ebox(self.first_box);
+    }
+
+    /// Moves on when what happened in the game is what the step was waiting for.
+    pub fn on_event(&mut self, event: &GameEvent, game: &Game) {
+        match (self.step, event) {
+            (TutorialStep::FixBox, GameEvent::FixEBox(_, ebox)) => {
+                let first_box = game.electrical_boxes()[self.first_box].hit_box().point();
+                if ebox.hit_box().point() == first_box {
+                    self.enter(TutorialStep::RunOutOfKits);
+                }
+            }
+            (TutorialStep::Restock, GameEvent::Restock(_)) => self.enter(TutorialStep::FixLastBox),
+            _ => {}
+        }
+    }
+
+    /// Checks the game for the steps that wait on how things are, after the game was updated.
+    pub fn update(&mut self, game: &mut Game) {
+        let player = rect_center(game.players()[0].hit_box());
+        if self.strike {
+            self.strike = false;
+            self.break_boxes(game, player);
+        }
+        match self.step {
+            TutorialStep::WalkToBox => {
+                let first_box = rect_center(game.electrical_boxes()[self.first_box].hit_box());
+                if player.distance(first_box) < NEAR_BOX {
+                    self.enter(TutorialStep::FixBox);
+                }
+            }
+            TutorialStep::RunOutOfKits if *game.players()[0].repair_kits() == 0 => self.enter(TutorialStep::Restock),
+            // restocking early leaves kits over, so more boxes break
+            TutorialStep::RunOutOfKits if game.get_working_boxes() == game.electrical_boxes().len() => {
+                self.enter(TutorialStep::RunOutOfKits);
+            }
+            TutorialStep::FixLastBox if game.get_working_boxes() == game.electrical_boxes().len() => {
+                self.enter(TutorialStep::Blackout);
+            }
+            TutorialStep::Blackout => {
+                let burned = self.blackout_fuel - game.generator().feul();
+                // a quick fix turns it off before the player had a good look
+                if burned >= FUEL_TO_WATCH || burned > 0.0 && !game.generator().running() {
+                    self.enter(TutorialStep::SaveFuel);
+                }
+            }
+            TutorialStep::SaveFuel if !game.generator().running() => self.enter(TutorialStep::Done),
+            _ => {}
+        }
+    }
+
+    /// Breaks the boxes the step just started needs broken.
+    fn break_boxes(&mut self, game: &mut Game, player: Vec2) {
+        let boxes = closest_boxes(game, player);
+        match self.step {
+            TutorialStep::RunOutOfKits => {
+                // one more than the player has kits left for
+                let count = *game.players()[0].repair_kits() as usize + 1;
+                for index in boxes.into_iter().filter(|index| *index != self.first_box).take(count) {
+                    game.break_ebox(index);
+                }
+            }
+            TutorialStep::Blackout => {
+                self.blackout_fuel = game.generator().feul();
+                // the boxes furthest away, so none are fixed before the generator comes on
+                for index in boxes.into_iter().rev().take(BLACKOUT_BOXES) {
+                    game.break_ebox(index);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Gives what the player is told to do.
+    pub fn message(&self) -> &'static str {
+        match self.step {
+            TutorialStep::WalkToBox => "Lightning struck a box nearby! Walk over to the flashing circle.",
+            TutorialStep::FixBox => "Stand next to the broken box to fix it, that uses up a repair kit.",
+            TutorialStep::RunOutOfKits => "More boxes broke. Fix them until you run out of repair kits.",
+            TutorialStep::Restock => "You are out of kits. Walk back to your van to restock.",
+            TutorialStep::FixLastBox => "Full up again! Now fix the last broken box.",
+            TutorialStep::Blackout => "The storm took out most of the city! The backup generator is burning fuel.",
+            TutorialStep::SaveFuel => "It runs while half the boxes are broken. Fix one to turn it off and save fuel.",
+            TutorialStep::Done => "That's everything, good luck out there!",
+        }
+    }
+
+    /// Gives where in the world the player should go, if anywhere.
+    pub fn target(&self, game: &Game) -> Option<Vec2> {
+        let player = rect_center(game.players()[0].hit_box());
+        let closest_broken = || {
+            closest_boxes(game, player)
+                .into_iter()
+                .find(|index| *game.electrical_boxes()[*index].broken())
+                .map(|index| rect_center(game.electrical_boxes()[index].hit_box()))
+        };
+        match self.step {
+            TutorialStep::WalkToBox | TutorialStep::FixBox => Some(rect_center(game.electrical_boxes()[self.first_box].hit_box())),
+            TutorialStep::RunOutOfKits | TutorialStep::FixLastBox | TutorialStep::SaveFuel => closest_broken(),
+            TutorialStep::Restock => Some(rect_center(game.restock())),
+            TutorialStep::Blackout | TutorialStep::Done => None,
+        }
+    }
+
+    /// Get the step the player is on.
+    pub fn step(&self) -> TutorialStep {
+        self.step
+    }
+}
+
+/// Gives the index of every box, the closest to `point` first.
+fn closest_boxes(game: &Game, point: Vec2) -> Vec<usize> {
+    let boxes = game.electrical_boxes();
+    let mut indices: Vec<usize> = (0..boxes.len()).collect();
+    indices.sort_by(|a, b| {
+        let a = rect_center(boxes[*a].hit_box()).distance(point);
+        let b = rect_center(boxes[*b].hit_box()).distance(point);
+        a.total_cmp(&b)
+    });
+    indices
+}